@@ -0,0 +1,38 @@
+-- CHAT: Messages sent to the station, with the song that was playing
+-- Timestamps are written by sqlx from UTC, like 2026-10-18T22:09:31.123456+00:00, with 0, 3, 6 or 9
+-- digits of fraction. Always the same offset, so they still compare as text.
+CREATE TABLE chat_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    song_id INTEGER,
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_chat_messages_created_at ON chat_messages(created_at);
+
+-- REACTIONS: Emoji placed at a position of a song
+CREATE TABLE song_reactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    song_id INTEGER NOT NULL,
+    emoji TEXT NOT NULL,
+    position_ms INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_song_reactions_song ON song_reactions(song_id, position_ms);
+
+-- MUTES: Users that can't chat or react until a given time
+CREATE TABLE chat_mutes (
+    user_id INTEGER PRIMARY KEY,
+    muted_until TEXT NOT NULL,
+    reason TEXT,
+    muted_by INTEGER,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (muted_by) REFERENCES users(id) ON DELETE SET NULL
+);
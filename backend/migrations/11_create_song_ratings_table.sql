@@ -0,0 +1,13 @@
+-- RATINGS: One like (1) or dislike (-1) per user and song
+CREATE TABLE song_ratings (
+    user_id INTEGER NOT NULL,
+    song_id INTEGER NOT NULL,
+    rating INTEGER NOT NULL CHECK(rating IN (-1, 1)),
+    rated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, song_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE
+);
+
+-- Aggregates per song
+CREATE INDEX idx_song_ratings_song ON song_ratings(song_id, rating);
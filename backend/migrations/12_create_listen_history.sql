@@ -0,0 +1,24 @@
+-- PLAY HISTORY: Every song the station played
+CREATE TABLE play_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    song_id INTEGER NOT NULL,
+    started_at TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_play_history_started_at ON play_history(started_at);
+
+-- LISTEN LOG: How long each user listened to each play
+CREATE TABLE listen_log (
+    user_id INTEGER NOT NULL,
+    play_id INTEGER NOT NULL,
+    song_id INTEGER NOT NULL,
+    listened_ms INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (user_id, play_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (play_id) REFERENCES play_history(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_listen_log_song ON listen_log(song_id);
@@ -0,0 +1,33 @@
+-- LISTEN SESSIONS: One row per stream connection of a user
+CREATE TABLE listen_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    started_at TEXT NOT NULL,
+    last_active_at TEXT NOT NULL,
+    listened_ms INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (user_id, started_at),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_listen_sessions_active ON listen_sessions(last_active_at);
+
+-- Carry the old counters over as one session at the epoch, so all time totals are kept
+INSERT INTO listen_sessions (user_id, started_at, last_active_at, listened_ms)
+SELECT id, '1970-01-01T00:00:00+00:00', '1970-01-01T00:00:00+00:00', total_listen_time * 1000
+FROM users
+WHERE total_listen_time > 0;
+
+-- SONG REQUEST LOG: Every song request a listener made
+CREATE TABLE song_request_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    song_id INTEGER NOT NULL,
+    requested_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_song_request_log_requested_at ON song_request_log(requested_at);
+
+-- Lets users keep their name off the leaderboards
+ALTER TABLE users ADD COLUMN leaderboard_opt_out BOOLEAN NOT NULL DEFAULT 0;
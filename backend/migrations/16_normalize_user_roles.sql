@@ -0,0 +1,2 @@
+-- ROLES: Stored as UserRole (user, curator, dj, admin), anything else becomes a plain user
+UPDATE users SET role = 'user' WHERE role NOT IN ('user', 'curator', 'dj', 'admin');
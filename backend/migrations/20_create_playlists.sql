@@ -0,0 +1,31 @@
+-- PLAYLISTS: Song lists made by users. 'private' ones only the owner sees, 'public' ones everyone sees
+-- and 'collaborative' ones everyone can also add, move and remove songs in
+CREATE TABLE playlists (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    version INTEGER NOT NULL DEFAULT 1, -- Bumped by every change, edits can name the version they were based on
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_playlists_owner ON playlists(owner_id);
+
+-- PLAYLIST ENTRIES: A song at a position, the same song can be in a playlist more than once.
+-- Deleted songs leave gaps, edits number the positions from 0 again.
+CREATE TABLE playlist_songs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playlist_id INTEGER NOT NULL,
+    song_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    added_by INTEGER,
+    added_at TEXT NOT NULL,
+    FOREIGN KEY (playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE,
+    FOREIGN KEY (added_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_playlist_songs_position ON playlist_songs(playlist_id, position);
@@ -0,0 +1,2 @@
+-- TEMPO: Tempo set by hand, re-running the analysis keeps it instead of the detected one
+ALTER TABLE songs ADD COLUMN bpm_override REAL;
@@ -0,0 +1,17 @@
+-- LISTEN TIME HOURLY: Listening time per user and hour, so leaderboard periods only count what was listened within them
+CREATE TABLE listen_time_hourly (
+    user_id INTEGER NOT NULL,
+    hour_start TEXT NOT NULL,
+    listened_ms INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (user_id, hour_start),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_listen_time_hourly_hour ON listen_time_hourly(hour_start);
+
+-- Sessions so far go to the hour they were last active in, the best that is known about them
+INSERT INTO listen_time_hourly (user_id, hour_start, listened_ms)
+SELECT user_id, substr(last_active_at, 1, 13) || ':00:00+00:00', SUM(listened_ms)
+FROM listen_sessions
+WHERE listened_ms > 0
+GROUP BY user_id, substr(last_active_at, 1, 13);
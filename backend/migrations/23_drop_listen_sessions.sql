@@ -0,0 +1,2 @@
+-- Listening time is kept by the hour in listen_time_hourly, which took over the sessions recorded so far
+DROP TABLE listen_sessions;
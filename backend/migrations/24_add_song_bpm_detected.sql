@@ -0,0 +1,4 @@
+-- TEMPO: The last detected tempo, what the song goes back to when its override is cleared
+ALTER TABLE songs ADD COLUMN bpm_detected REAL;
+
+UPDATE songs SET bpm_detected = bpm WHERE bpm_override IS NULL;
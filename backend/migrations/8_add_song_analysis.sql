@@ -0,0 +1,3 @@
+-- ANALYSIS: Results of the decoding pass run on ingest
+ALTER TABLE songs ADD COLUMN duration_ms INTEGER;
+ALTER TABLE songs ADD COLUMN loudness_db REAL; -- Average RMS level in dBFS
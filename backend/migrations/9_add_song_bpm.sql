@@ -0,0 +1,2 @@
+-- TEMPO: Detected (or admin overridden) beats per minute
+ALTER TABLE songs ADD COLUMN bpm REAL;
@@ -0,0 +1,154 @@
+pub mod decode;
+pub mod tempo;
+pub mod waveform;
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tempo::BeatGrid;
+use waveform::Waveform;
+
+#[derive(Debug, Default, Clone)]
+pub struct AnalysisOptions {
+    /// Fit the beat grid to this tempo instead of detecting it, and keep it as the song's tempo
+    pub bpm_override: Option<f64>,
+    /// Replace an existing rhythm file with the generated one
+    pub overwrite_rhythm: bool,
+}
+
+/// Everything we extract from a song in a single decoding pass
+pub struct SongAnalysis {
+    pub duration_ms: u64,
+    /// Average RMS level of the whole song, in dBFS
+    pub loudness_db: f64,
+    pub waveform: Waveform,
+    pub beat_grid: Option<BeatGrid>,
+}
+
+/// Decodes the file once and runs every analysis step over the same PCM.
+/// Blocking, call it from `spawn_blocking`.
+pub fn analyze_file(song_id: i64, path: &Path, options: &AnalysisOptions) -> Result<SongAnalysis, String> {
+    let pcm = decode::decode_mono(path)?;
+    let duration_ms = pcm.duration_ms();
+
+    let sum_squares: f64 = pcm.samples.iter().map(|&s| (s as f64) * (s as f64)).sum();
+    let rms = (sum_squares / pcm.samples.len() as f64).sqrt();
+    let loudness_db = 20.0 * rms.max(1e-9).log10();
+
+    let waveform = Waveform {
+        song_id,
+        sample_rate: pcm.sample_rate,
+        duration_ms,
+        peaks: waveform::compute_peaks(&pcm.samples, waveform::WAVEFORM_BUCKETS),
+        spectrogram: waveform::compute_spectrogram(&pcm.samples, pcm.sample_rate),
+    };
+
+    let beat_grid = tempo::detect(&pcm.samples, pcm.sample_rate, options.bpm_override);
+
+    Ok(SongAnalysis {
+        duration_ms,
+        loudness_db,
+        waveform,
+        beat_grid,
+    })
+}
+
+/// Analyzes a stored song, writes its waveform next to the audio, generates its baseline
+/// rhythm file and saves the results in the DB
+pub async fn run_for_song(pool: &SqlitePool, song_id: i64, options: AnalysisOptions) -> Result<SongAnalysis, String> {
+    let audio_path = crate::config::get_music_dir().join(format!("{}.mp3", song_id));
+    if !audio_path.exists() {
+        return Err(format!("Audio file not found at {:?}", audio_path));
+    }
+
+    let blocking_options = options.clone();
+    let analysis = tokio::task::spawn_blocking(move || analyze_file(song_id, &audio_path, &blocking_options))
+        .await
+        .map_err(|e| format!("Analysis task failed: {}", e))??;
+
+    let json = serde_json::to_vec(&analysis.waveform).map_err(|e| e.to_string())?;
+    tokio::fs::write(crate::config::get_waveform_path(song_id), json)
+        .await
+        .map_err(|e| format!("Failed to save waveform: {}", e))?;
+
+    if let Some(grid) = &analysis.beat_grid {
+        let source_path = crate::config::get_rhythm_source_path(song_id);
+
+        // Never clobber hand-authored rhythm data unless asked to
+        if options.overwrite_rhythm || !source_path.exists() {
+            crate::rhythm::store::save_track(song_id, &grid.to_rhythm_track()).await?;
+        }
+    }
+
+    let bpm = analysis.beat_grid.as_ref().map(|grid| grid.bpm);
+    // With an override the grid only has the given tempo, nothing was detected
+    let detected_bpm = bpm.filter(|_| options.bpm_override.is_none());
+    crate::orm::songs::repository::set_analysis(
+        pool,
+        song_id,
+        analysis.duration_ms as i64,
+        analysis.loudness_db,
+        detected_bpm,
+        options.bpm_override,
+    )
+    .await?;
+
+    tracing::info!(
+        "Analyzed song #{}: {} ms, {:.1} dBFS, {:?} BPM",
+        song_id,
+        analysis.duration_ms,
+        analysis.loudness_db,
+        bpm
+    );
+
+    Ok(analysis)
+}
+
+type PendingAnalysis = Shared<BoxFuture<'static, Result<(), String>>>;
+
+/// Analyses run because a request needed them. A song is only analysed once at a time,
+/// requests for it meanwhile wait for the same run, and only a few songs are analysed at once.
+#[derive(Clone)]
+pub struct AnalysisQueue {
+    pending: Arc<Mutex<HashMap<i64, PendingAnalysis>>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for AnalysisQueue {
+    fn default() -> Self {
+        Self {
+            pending: Default::default(),
+            permits: Arc::new(Semaphore::new(crate::config::MAX_CONCURRENT_ANALYSES)),
+        }
+    }
+}
+
+impl AnalysisQueue {
+    /// Analyzes the song, or waits for the analysis already running
+    pub async fn run(&self, pool: &SqlitePool, song_id: i64) -> Result<(), String> {
+        let pending = self.pending.lock().unwrap()
+            .entry(song_id)
+            .or_insert_with(|| {
+                let queue = self.clone();
+                let pool = pool.clone();
+                // Spawned so it finishes even if every request waiting for it goes away
+                let task = tokio::spawn(async move {
+                    let result = match queue.permits.acquire().await {
+                        Ok(_permit) => run_for_song(&pool, song_id, Default::default()).await.map(|_| ()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    queue.pending.lock().unwrap().remove(&song_id);
+                    result
+                });
+                async move { task.await.map_err(|e| format!("Analysis task failed: {}", e))? }
+                    .boxed()
+                    .shared()
+            })
+            .clone();
+
+        pending.await
+    }
+}
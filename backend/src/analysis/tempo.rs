@@ -0,0 +1,326 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::Serialize;
+
+use crate::rhythm::model::{RhythmEvent, RhythmEventKind, RhythmTrack};
+
+// Onset envelope resolution: 1024-sample frames every 512 samples (~11.6 ms at 44.1kHz)
+const FRAME_SIZE: usize = 1024;
+const HOP_SIZE: usize = 512;
+
+// Tempo search range and the tempo we favour when several multiples fit
+pub const MIN_BPM: f64 = 60.0;
+pub const MAX_BPM: f64 = 200.0;
+const PREFERRED_BPM: f64 = 120.0;
+
+pub const BEATS_PER_BAR: usize = 4;
+
+// Onsets closer than this are merged into the first one
+const MIN_ONSET_GAP_MS: u64 = 80;
+
+/// Detected tempo and beat positions of a song
+#[derive(Debug, Clone, Serialize)]
+pub struct BeatGrid {
+    pub bpm: f64,
+    pub beats_ms: Vec<u64>,
+    pub downbeats_ms: Vec<u64>,
+    pub onsets_ms: Vec<u64>,
+}
+
+impl BeatGrid {
+    /// Builds the baseline rhythm track: every beat (marking downbeats) plus the detected onsets
+    pub fn to_rhythm_track(&self) -> RhythmTrack {
+        let mut events = Vec::with_capacity(self.beats_ms.len() + self.onsets_ms.len());
+
+        for &time in &self.beats_ms {
+            let kind = if self.downbeats_ms.binary_search(&time).is_ok() {
+                RhythmEventKind::Downbeat
+            } else {
+                RhythmEventKind::Beat
+            };
+            events.push(RhythmEvent { time, kind });
+        }
+        for &time in &self.onsets_ms {
+            events.push(RhythmEvent { time, kind: RhythmEventKind::Onset });
+        }
+
+        // Stable, so a beat stays ahead of an onset at the same time
+        events.sort_by_key(|event| event.time);
+
+        RhythmTrack::new(Some(self.bpm), events)
+    }
+}
+
+/// Detects tempo, beats, downbeats and onsets from mono PCM.
+/// `bpm_override` skips the tempo estimation and only fits the grid phase.
+pub fn detect(samples: &[f32], sample_rate: u32, bpm_override: Option<f64>) -> Option<BeatGrid> {
+    if sample_rate == 0 || samples.len() < FRAME_SIZE * 8 {
+        return None;
+    }
+
+    let envelope = onset_envelope(samples);
+    let frame_ms = HOP_SIZE as f64 * 1000.0 / sample_rate as f64;
+    let frames_per_minute = 60_000.0 / frame_ms;
+
+    let period = match bpm_override {
+        Some(bpm) if bpm > 0.0 => frames_per_minute / bpm,
+        _ => estimate_period(&envelope, frames_per_minute)?,
+    };
+    let bpm = frames_per_minute / period;
+
+    let beats = track_beats(&envelope, period);
+    if beats.is_empty() {
+        return None;
+    }
+
+    // The bar phase is whichever of the 4 offsets lands on the strongest beats
+    let downbeat_offset = (0..BEATS_PER_BAR)
+        .max_by(|&a, &b| {
+            let strength = |offset: usize| -> f32 {
+                beats.iter().skip(offset).step_by(BEATS_PER_BAR).map(|&f| envelope[f]).sum()
+            };
+            strength(a).total_cmp(&strength(b))
+        })
+        .unwrap_or(0);
+
+    let to_ms = |frame: usize| (frame as f64 * frame_ms).round() as u64;
+
+    let beats_ms: Vec<u64> = beats.iter().map(|&f| to_ms(f)).collect();
+    let downbeats_ms: Vec<u64> = beats_ms.iter().skip(downbeat_offset).step_by(BEATS_PER_BAR).copied().collect();
+
+    let onsets_ms = pick_onsets(&envelope)
+        .into_iter()
+        .map(to_ms)
+        .fold(Vec::<u64>::new(), |mut acc, time| {
+            if acc.last().is_none_or(|&last| time - last >= MIN_ONSET_GAP_MS) {
+                acc.push(time);
+            }
+            acc
+        });
+
+    Some(BeatGrid {
+        bpm: (bpm * 100.0).round() / 100.0,
+        beats_ms,
+        downbeats_ms,
+        onsets_ms,
+    })
+}
+
+/// Half-wave rectified spectral flux on a log-magnitude spectrum, with the local mean removed
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(FRAME_SIZE);
+
+    let window: Vec<f32> = (0..FRAME_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME_SIZE - 1) as f32).cos())
+        .collect();
+
+    let frames = (samples.len() - FRAME_SIZE) / HOP_SIZE + 1;
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FRAME_SIZE];
+    let mut previous = vec![0.0f32; FRAME_SIZE / 2];
+    let mut flux = Vec::with_capacity(frames);
+
+    for frame in 0..frames {
+        let start = frame * HOP_SIZE;
+        for (i, slot) in buffer.iter_mut().enumerate() {
+            *slot = Complex::new(samples[start + i] * window[i], 0.0);
+        }
+
+        fft.process(&mut buffer);
+
+        let mut value = 0.0;
+        for (bin, prev) in previous.iter_mut().enumerate() {
+            let magnitude = (1.0 + 1000.0 * buffer[bin].norm()).ln();
+            value += (magnitude - *prev).max(0.0);
+            *prev = magnitude;
+        }
+        flux.push(value);
+    }
+
+    // Remove the slowly moving average (~0.5s) so loud passages don't dominate
+    let half_window = 22;
+    let mut prefix = vec![0.0f64; flux.len() + 1];
+    for (i, &v) in flux.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v as f64;
+    }
+
+    flux.iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let lo = i.saturating_sub(half_window);
+            let hi = (i + half_window + 1).min(flux.len());
+            let mean = (prefix[hi] - prefix[lo]) / (hi - lo) as f64;
+            (v - mean as f32).max(0.0)
+        })
+        .collect()
+}
+
+/// Beat period in envelope frames, from the autocorrelation peak weighted towards `PREFERRED_BPM`
+fn estimate_period(envelope: &[f32], frames_per_minute: f64) -> Option<f64> {
+    let min_lag = (frames_per_minute / MAX_BPM).floor() as usize;
+    let max_lag = (frames_per_minute / MIN_BPM).ceil() as usize;
+
+    if min_lag < 1 || envelope.len() <= max_lag * 2 {
+        return None;
+    }
+
+    let autocorrelation = |lag: usize| -> f64 {
+        envelope.iter()
+            .zip(&envelope[lag..])
+            .map(|(&a, &b)| (a * b) as f64)
+            .sum::<f64>()
+            / (envelope.len() - lag) as f64
+    };
+
+    let scores: Vec<f64> = (min_lag..=max_lag).map(autocorrelation).collect();
+
+    let (best_index, _) = scores.iter()
+        .enumerate()
+        .map(|(i, &score)| {
+            let bpm = frames_per_minute / (min_lag + i) as f64;
+            // Log-gaussian prior, one octave wide
+            let octaves = (bpm / PREFERRED_BPM).log2();
+            (i, score * (-0.5 * octaves * octaves).exp())
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    if scores[best_index] <= 0.0 {
+        return None;
+    }
+
+    // Parabolic interpolation around the peak for sub-frame precision
+    let mut offset = 0.0;
+    if best_index > 0 && best_index + 1 < scores.len() {
+        let (a, b, c) = (scores[best_index - 1], scores[best_index], scores[best_index + 1]);
+        let denominator = a - 2.0 * b + c;
+        if denominator.abs() > f64::EPSILON {
+            offset = (0.5 * (a - c) / denominator).clamp(-0.5, 0.5);
+        }
+    }
+
+    Some((min_lag + best_index) as f64 + offset)
+}
+
+/// Places beats every `period` frames from the best phase, letting each one snap to a nearby peak
+fn track_beats(envelope: &[f32], period: f64) -> Vec<usize> {
+    let period_frames = period.round().max(1.0) as usize;
+
+    let phase = (0..period_frames)
+        .max_by(|&a, &b| {
+            let strength = |phase: usize| -> f32 {
+                envelope.iter().skip(phase).step_by(period_frames).sum()
+            };
+            strength(a).total_cmp(&strength(b))
+        })
+        .unwrap_or(0);
+
+    let tolerance = (period * 0.1).round() as usize;
+    let mut beats = Vec::new();
+    let mut expected = phase as f64;
+
+    while (expected.round() as usize) < envelope.len() {
+        let center = expected.round() as usize;
+        let lo = center.saturating_sub(tolerance);
+        let hi = (center + tolerance + 1).min(envelope.len());
+
+        let snapped = (lo..hi)
+            .max_by(|&a, &b| envelope[a].total_cmp(&envelope[b]))
+            .unwrap_or(center);
+
+        beats.push(snapped);
+        // Follow the snapped beat so slow drifts are tracked, but keep the tempo fixed
+        expected = snapped as f64 + period;
+    }
+
+    beats
+}
+
+/// Local maxima clearly above the envelope's average level
+fn pick_onsets(envelope: &[f32]) -> Vec<usize> {
+    let count = envelope.len() as f32;
+    let mean = envelope.iter().sum::<f32>() / count;
+    let variance = envelope.iter().map(|&v| (v - mean) * (v - mean)).sum::<f32>() / count;
+    let threshold = mean + 1.5 * variance.sqrt();
+
+    (3..envelope.len().saturating_sub(3))
+        .filter(|&i| {
+            let value = envelope[i];
+            value > threshold && envelope[i - 3..=i + 3].iter().all(|&v| v <= value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    /// A click every beat from `offset_ms`, each bar's first click louder
+    fn click_track(bpm: f64, offset_ms: f64, seconds: f64) -> Vec<f32> {
+        let mut samples = vec![0.0f32; (seconds * SAMPLE_RATE as f64) as usize];
+        let beat_samples = 60.0 / bpm * SAMPLE_RATE as f64;
+        let click_len = (0.01 * SAMPLE_RATE as f64) as usize;
+        let mut noise = 0x2545_f491_u32;
+
+        let mut beat = 0;
+        loop {
+            let start = (offset_ms / 1000.0 * SAMPLE_RATE as f64 + beat as f64 * beat_samples) as usize;
+            if start + click_len >= samples.len() {
+                break;
+            }
+            let gain = if beat % BEATS_PER_BAR == 0 { 1.0 } else { 0.5 };
+            for (i, sample) in samples[start..start + click_len].iter_mut().enumerate() {
+                // Decaying white noise, xorshift keeps it deterministic
+                noise ^= noise << 13;
+                noise ^= noise >> 17;
+                noise ^= noise << 5;
+                let white = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+                *sample = white * gain * (1.0 - i as f32 / click_len as f32);
+            }
+            beat += 1;
+        }
+
+        samples
+    }
+
+    #[test]
+    fn detects_tempo_and_beat_grid_of_click_track() {
+        let samples = click_track(120.0, 250.0, 20.0);
+        let grid = detect(&samples, SAMPLE_RATE, None).unwrap();
+
+        assert!((grid.bpm - 120.0).abs() < 1.0, "detected {} bpm", grid.bpm);
+
+        // Every beat sits on a click, within an envelope frame or two
+        assert!(grid.beats_ms.len() >= 35, "only {} beats", grid.beats_ms.len());
+        for &beat in &grid.beats_ms {
+            let from_click = (beat as i64 - 250).rem_euclid(500);
+            let error = from_click.min(500 - from_click);
+            assert!(error <= 25, "beat at {} ms is {} ms off the grid", beat, error);
+        }
+
+        // The loud clicks are the downbeats, 2 s apart from 250 ms
+        for &downbeat in &grid.downbeats_ms {
+            let from_bar = (downbeat as i64 - 250).rem_euclid(2000);
+            let error = from_bar.min(2000 - from_bar);
+            assert!(error <= 25, "downbeat at {} ms is {} ms off the bar", downbeat, error);
+        }
+    }
+
+    #[test]
+    fn override_keeps_the_given_tempo() {
+        let samples = click_track(120.0, 250.0, 20.0);
+        let grid = detect(&samples, SAMPLE_RATE, Some(60.0)).unwrap();
+
+        assert_eq!(grid.bpm, 60.0);
+        for pair in grid.beats_ms.windows(2) {
+            let gap = pair[1] as i64 - pair[0] as i64;
+            assert!((gap - 1000).abs() <= 25, "beats {} ms apart", gap);
+        }
+    }
+
+    #[test]
+    fn too_short_for_analysis() {
+        assert!(detect(&[0.0; FRAME_SIZE], SAMPLE_RATE, None).is_none());
+        assert!(detect(&click_track(120.0, 0.0, 1.0), 0, None).is_none());
+    }
+}
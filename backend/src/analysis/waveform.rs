@@ -0,0 +1,168 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+// Number of min/max buckets in the peak waveform
+pub const WAVEFORM_BUCKETS: usize = 1000;
+
+// Coarse spectrogram resolution
+pub const SPECTROGRAM_FRAMES: usize = 256;
+pub const SPECTROGRAM_BANDS: usize = 48;
+const SPECTROGRAM_FFT_SIZE: usize = 2048;
+const SPECTROGRAM_MIN_HZ: f32 = 40.0;
+const SPECTROGRAM_MAX_HZ: f32 = 16000.0;
+// Magnitudes are mapped from [FLOOR_DB, 0] dBFS to [0, 255]
+const SPECTROGRAM_FLOOR_DB: f32 = -90.0;
+
+/// Precomputed preview of a song, served to the player and the rhythm editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Waveform {
+    pub song_id: i64,
+    pub sample_rate: u32,
+    pub duration_ms: u64,
+    /// `[min, max]` sample values per bucket, in the range -1.0 to 1.0
+    pub peaks: Vec<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spectrogram: Option<Spectrogram>,
+}
+
+/// Log-frequency spectrogram, stored row by row (one row of `bands` values per frame)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spectrogram {
+    pub frames: usize,
+    pub bands: usize,
+    pub min_hz: f32,
+    pub max_hz: f32,
+    /// Band energy scaled to 0-255, `frames * bands` values
+    pub data: Vec<u8>,
+}
+
+/// Splits the samples into `buckets` equal slices and keeps the extremes of each one
+pub fn compute_peaks(samples: &[f32], buckets: usize) -> Vec<[f32; 2]> {
+    if samples.is_empty() || buckets == 0 {
+        return Vec::new();
+    }
+
+    let buckets = buckets.min(samples.len());
+    let mut peaks = Vec::with_capacity(buckets);
+
+    for i in 0..buckets {
+        let start = i * samples.len() / buckets;
+        let end = ((i + 1) * samples.len() / buckets).max(start + 1);
+
+        let (min, max) = samples[start..end]
+            .iter()
+            .fold((0.0f32, 0.0f32), |(min, max), &s| (min.min(s), max.max(s)));
+
+        peaks.push([round(min.max(-1.0)), round(max.min(1.0))]);
+    }
+
+    peaks
+}
+
+pub fn compute_spectrogram(samples: &[f32], sample_rate: u32) -> Option<Spectrogram> {
+    if samples.len() < SPECTROGRAM_FFT_SIZE || sample_rate == 0 {
+        return None;
+    }
+
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(SPECTROGRAM_FFT_SIZE);
+
+    let window: Vec<f32> = (0..SPECTROGRAM_FFT_SIZE)
+        .map(|i| {
+            let x = i as f32 / (SPECTROGRAM_FFT_SIZE - 1) as f32;
+            0.5 - 0.5 * (2.0 * std::f32::consts::PI * x).cos()
+        })
+        .collect();
+
+    // Log-spaced band edges, expressed as FFT bin indices
+    let nyquist = sample_rate as f32 / 2.0;
+    let max_hz = SPECTROGRAM_MAX_HZ.min(nyquist);
+    let bin_hz = sample_rate as f32 / SPECTROGRAM_FFT_SIZE as f32;
+    let edges: Vec<usize> = (0..=SPECTROGRAM_BANDS)
+        .map(|b| {
+            let ratio = b as f32 / SPECTROGRAM_BANDS as f32;
+            let hz = SPECTROGRAM_MIN_HZ * (max_hz / SPECTROGRAM_MIN_HZ).powf(ratio);
+            ((hz / bin_hz).round() as usize).clamp(1, SPECTROGRAM_FFT_SIZE / 2)
+        })
+        .collect();
+
+    let hop = (samples.len() - SPECTROGRAM_FFT_SIZE) as f64 / (SPECTROGRAM_FRAMES - 1) as f64;
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); SPECTROGRAM_FFT_SIZE];
+    let mut data = Vec::with_capacity(SPECTROGRAM_FRAMES * SPECTROGRAM_BANDS);
+
+    for frame in 0..SPECTROGRAM_FRAMES {
+        let start = (frame as f64 * hop) as usize;
+        for (i, slot) in buffer.iter_mut().enumerate() {
+            *slot = Complex::new(samples[start + i] * window[i], 0.0);
+        }
+
+        fft.process(&mut buffer);
+
+        for band in 0..SPECTROGRAM_BANDS {
+            let lo = edges[band];
+            let hi = edges[band + 1].max(lo + 1);
+
+            let energy = buffer[lo..hi].iter().map(|c| c.norm_sqr()).sum::<f32>() / (hi - lo) as f32;
+            // Normalise by the window gain so a full-scale sine sits near 0 dB
+            let magnitude = energy.sqrt() / (SPECTROGRAM_FFT_SIZE as f32 / 4.0);
+            let db = 20.0 * magnitude.max(1e-9).log10();
+
+            let scaled = ((db - SPECTROGRAM_FLOOR_DB) / -SPECTROGRAM_FLOOR_DB).clamp(0.0, 1.0);
+            data.push((scaled * 255.0).round() as u8);
+        }
+    }
+
+    Some(Spectrogram {
+        frames: SPECTROGRAM_FRAMES,
+        bands: SPECTROGRAM_BANDS,
+        min_hz: SPECTROGRAM_MIN_HZ,
+        max_hz,
+        data,
+    })
+}
+
+// Four decimals is plenty for drawing and keeps the JSON small
+fn round(value: f32) -> f32 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peaks_keep_the_extremes_of_each_bucket() {
+        let samples = [0.1, -0.5, 0.3, 0.2, -2.0, 0.9, 0.0, 0.0];
+        assert_eq!(compute_peaks(&samples, 4), vec![[-0.5, 0.1], [0.0, 0.3], [-1.0, 0.9], [0.0, 0.0]]);
+
+        // Never more buckets than samples
+        assert_eq!(compute_peaks(&samples, 100).len(), samples.len());
+        assert!(compute_peaks(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn spectrogram_of_a_sine_peaks_at_its_band() {
+        let sample_rate = 44_100;
+        let hz = 1000.0f32;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| (2.0 * std::f32::consts::PI * hz * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        let spectrogram = compute_spectrogram(&samples, sample_rate as u32).unwrap();
+        assert_eq!(spectrogram.data.len(), SPECTROGRAM_FRAMES * SPECTROGRAM_BANDS);
+
+        // Band edges are log spaced between min_hz and max_hz
+        let ratio = (hz / spectrogram.min_hz).ln() / (spectrogram.max_hz / spectrogram.min_hz).ln();
+        let expected_band = (ratio * SPECTROGRAM_BANDS as f32) as usize;
+
+        let first_frame = &spectrogram.data[..SPECTROGRAM_BANDS];
+        let loudest = (0..SPECTROGRAM_BANDS).max_by_key(|&b| first_frame[b]).unwrap();
+        assert!(loudest.abs_diff(expected_band) <= 1, "loudest band {}, expected {}", loudest, expected_band);
+        assert!(first_frame[loudest] > 200, "a full scale sine should be near 0 dB");
+    }
+
+    #[test]
+    fn too_short_for_a_spectrogram() {
+        assert!(compute_spectrogram(&[0.0; 100], 44_100).is_none());
+    }
+}
@@ -1,26 +1,230 @@
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+
 use axum::{
-    extract::FromRequestParts,
-    http::request::Parts,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
 };
-use axum_extra::extract::cookie::{Key, PrivateCookieJar};
+use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar, SameSite};
+use chrono::{DateTime, Utc};
+use rand::{Rng, RngCore};
+use sha2::{Digest, Sha256};
 
 use crate::state::AppState;
 use crate::error::AppError;
-use crate::orm::users::{models::User, repository};
+use crate::config::{get_trust_proxy_headers, GUEST_SESSION_HOURS, SESSION_LIFETIME_DAYS, SESSION_TOUCH_INTERVAL_SECONDS};
+use crate::orm::users::{models::{Permission, User, UserRole}, repository};
+use crate::orm::sessions::{models::Session, repository as sessions};
+use crate::orm::tokens::{models::{ApiScope, TokenScopes}, repository as tokens};
 
 pub const AUTH_COOKIE_NAME: &str = "auth_session";
+pub const GUEST_COOKIE_NAME: &str = "wavy_guest";
+
+// Longest user agent kept with a session
+const MAX_USER_AGENT_LENGTH: usize = 256;
 
-pub struct AuthUser(pub User);
+/// The logged in user, and the scopes of the API token when the request was made with one
+pub struct AuthUser(pub User, pub Option<TokenScopes>);
 
 impl AuthUser {
+    /// Admin only permissions also need the admin scope when an API token is used
+    pub fn can(&self, permission: Permission) -> bool {
+        let scoped_out = permission.admin_only() && self.1.as_ref().is_some_and(|scopes| !scopes.contains(ApiScope::Admin));
+        !scoped_out && self.0.role.has(permission)
+    }
+}
+
+/// Someone who may listen: a user, or a guest while the station lets guests in.
+/// Guests have negative ids so they never collide with users.
+pub struct AuthListener {
+    pub id: i64,
+    pub name: String,
+    pub is_guest: bool,
+}
+
+/// What the guest cookie holds, it's encrypted so guests can't pick their id
+#[derive(Debug, Clone, Copy)]
+pub struct GuestSession {
+    pub id: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl GuestSession {
+    pub fn new() -> Self {
+        Self {
+            id: -rand::rng().random_range(1..=i64::from(i32::MAX)),
+            expires_at: Utc::now() + chrono::Duration::hours(GUEST_SESSION_HOURS),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("Guest {:04}", self.id.unsigned_abs() % 10_000)
+    }
+
+    fn to_cookie_value(self) -> String {
+        format!("{}:{}", self.id, self.expires_at.timestamp())
+    }
+
+    fn from_cookie_value(value: &str) -> Option<Self> {
+        let (id, expires_at) = value.split_once(':')?;
+        let id = id.parse::<i64>().ok().filter(|id| *id < 0)?;
+        let expires_at = DateTime::from_timestamp(expires_at.parse().ok()?, 0)?;
+        (expires_at > Utc::now()).then_some(Self { id, expires_at })
+    }
+
+    /// The unexpired guest session of the jar's cookie
+    pub fn from_jar(jar: &PrivateCookieJar<Key>) -> Option<Self> {
+        jar.get(GUEST_COOKIE_NAME).and_then(|cookie| Self::from_cookie_value(cookie.value()))
+    }
+
+    pub fn add_to(self, jar: PrivateCookieJar<Key>) -> PrivateCookieJar<Key> {
+        let mut cookie = Cookie::new(GUEST_COOKIE_NAME, self.to_cookie_value());
+        cookie.set_http_only(true);
+        cookie.set_path("/");
+        cookie.set_same_site(SameSite::Lax);
+        cookie.set_max_age(time::Duration::hours(GUEST_SESSION_HOURS));
+        jar.add(cookie)
+    }
+}
+
+/// A user whose role grants the permission `P` stands for, see `perm`
+pub struct Require<P: RequiredPermission>(pub User, pub PhantomData<P>);
+
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+/// Marker types for `Require`, for the permissions endpoints are gated on
+pub mod perm {
+    use super::{Permission, RequiredPermission};
+
+    macro_rules! markers {
+        ($($name:ident),* $(,)?) => {
+            $(
+                pub struct $name;
+
+                impl RequiredPermission for $name {
+                    const PERMISSION: Permission = Permission::$name;
+                }
+            )*
+        };
+    }
+
+    markers!(EditMetadata, ManageLibrary, ManageQueue, ModerateChat, ManageStation, ManageUsers, ViewAuditLog);
+}
+
+/// The logged in user along with the session the request was made with
+pub struct AuthSession {
+    pub user: User,
+    pub session: Session,
+}
+
+impl AuthSession {
     pub fn is_admin(&self) -> bool {
-        matches!(self.0.role.as_str(), "admin")
+        self.user.role == UserRole::Admin
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        self.user.role.has(permission)
     }
 }
 
-pub struct AdminOnly(pub User);
+/// Where a request comes from, as far as it can be told
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
 
-impl FromRequestParts<AppState> for AuthUser {
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts.headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+
+        // Proxy headers can be set by anyone, they only count behind a proxy that overwrites them
+        let forwarded = get_trust_proxy_headers()
+            .then(|| {
+                header("x-real-ip")
+                    .or_else(|| header("x-forwarded-for").and_then(|v| v.split(',').next()).map(str::trim))
+                    .map(str::to_string)
+            })
+            .flatten();
+
+        let ip = forwarded.or_else(|| {
+            parts.extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        });
+
+        let user_agent = header(header::USER_AGENT.as_str())
+            .map(|ua| ua.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Ok(ClientInfo { ip, user_agent })
+    }
+}
+
+/// Random URL safe secret for session and API tokens
+pub fn new_secret_token() -> String {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash a token is stored under, the token itself is only known to the client
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Creates a session for the user and adds its cookie to the jar
+pub async fn start_session(
+    state: &AppState,
+    jar: PrivateCookieJar<Key>,
+    user_id: i64,
+    client: &ClientInfo,
+) -> Result<PrivateCookieJar<Key>, AppError> {
+    let token = new_secret_token();
+
+    let expires_at = Utc::now() + chrono::Duration::days(SESSION_LIFETIME_DAYS);
+    sessions::create(
+        &state.db,
+        user_id,
+        &hash_token(&token),
+        expires_at,
+        client.user_agent.as_deref(),
+        client.ip.as_deref(),
+    )
+    .await
+    .map_err(AppError::InternalServerError)?;
+
+    let mut cookie = Cookie::new(AUTH_COOKIE_NAME, token);
+    cookie.set_http_only(true);
+    cookie.set_path("/");
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_max_age(time::Duration::days(SESSION_LIFETIME_DAYS));
+
+    Ok(jar.add(cookie))
+}
+
+/// Removes the auth cookie from the jar
+pub fn clear_session_cookie(jar: PrivateCookieJar<Key>) -> PrivateCookieJar<Key> {
+    let mut cookie = Cookie::new(AUTH_COOKIE_NAME, "");
+    cookie.set_path("/");
+    jar.remove(cookie)
+}
+
+impl FromRequestParts<AppState> for AuthSession {
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
@@ -32,29 +236,172 @@ impl FromRequestParts<AppState> for AuthUser {
             .get(AUTH_COOKIE_NAME)
             .ok_or(AppError::Unauthorized("Please log in".to_string()))?;
 
-        // Parse ID as i64
-        let user_id = cookie.value().parse::<i64>()
-            .map_err(|_| AppError::Unauthorized("Invalid session".to_string()))?;
+        let session = sessions::find_by_token_hash(&state.db, &hash_token(cookie.value()))
+            .await
+            .map_err(AppError::InternalServerError)?
+            .ok_or(AppError::Unauthorized("Invalid session".to_string()))?;
+
+        let now = Utc::now();
+        if session.expires_at <= now {
+            let _ = sessions::delete_by_token_hash(&state.db, &session.token_hash).await;
+            return Err(AppError::Unauthorized("Session expired".to_string()));
+        }
+
+        // Last-seen doesn't need to be exact, spare a write per request
+        if (now - session.last_seen_at).num_seconds() >= SESSION_TOUCH_INTERVAL_SECONDS {
+            let Ok(client) = ClientInfo::from_request_parts(parts, state).await;
+            if let Err(e) = sessions::touch(&state.db, session.id, client.ip.as_deref()).await {
+                tracing::warn!("Failed to update session #{}: {}", session.id, e);
+            }
+        }
 
-        let user = repository::find_by_id(&state.db, user_id)
+        let user = repository::find_by_id(&state.db, session.user_id)
             .await
-            .map_err(|e| AppError::InternalServerError(e))?
+            .map_err(AppError::InternalServerError)?
             .ok_or(AppError::Unauthorized("User not found".to_string()))?;
 
-        Ok(AuthUser(user))
+        Ok(AuthSession { user, session })
+    }
+}
+
+/// Token of an `Authorization: Bearer` header, if there is one
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts.headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Authenticates with an API token, which has to carry the scope the request needs
+async fn authenticate_api_token(parts: &Parts, state: &AppState, token: &str) -> Result<AuthUser, AppError> {
+    let api_token = tokens::find_by_token_hash(&state.db, &hash_token(token))
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::Unauthorized("Invalid API token".to_string()))?;
+
+    let now = Utc::now();
+    if api_token.expires_at <= now {
+        return Err(AppError::Unauthorized("API token expired".to_string()));
+    }
+
+    let scopes = TokenScopes::from_db(&api_token.scopes);
+    let required = ApiScope::required_for(&parts.method, parts.uri.path());
+    if !scopes.contains(required) {
+        return Err(AppError::CustomForbidden(format!("API token lacks the {} scope", required.as_str())));
+    }
+
+    if api_token.last_used_at.is_none_or(|at| (now - at).num_seconds() >= SESSION_TOUCH_INTERVAL_SECONDS)
+        && let Err(e) = tokens::touch(&state.db, api_token.id).await
+    {
+        tracing::warn!("Failed to update API token #{}: {}", api_token.id, e);
+    }
+
+    let user = repository::find_by_id(&state.db, api_token.user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::Unauthorized("User not found".to_string()))?;
+
+    Ok(AuthUser(user, Some(scopes)))
+}
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(token) = bearer_token(parts).map(str::to_string) {
+            return authenticate_api_token(parts, state, &token).await;
+        }
+
+        let auth = AuthSession::from_request_parts(parts, state).await?;
+        Ok(AuthUser(auth.user, None))
     }
 }
 
-impl FromRequestParts<AppState> for AdminOnly {
+impl<P: RequiredPermission> FromRequestParts<AppState> for Require<P> {
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
         let auth_user = AuthUser::from_request_parts(parts, state).await?;
 
-        if auth_user.is_admin() {
-            Ok(AdminOnly(auth_user.0))
+        if auth_user.can(P::PERMISSION) {
+            Ok(Require(auth_user.0, PhantomData))
         } else {
-            Err(AppError::Unauthorized("Admin permissions required".to_string()))
+            Err(AppError::CustomForbidden("You don't have permission to do this".to_string()))
         }
     }
 }
+
+impl FromRequestParts<AppState> for AuthListener {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let unauthorized = match AuthUser::from_request_parts(parts, state).await {
+            Ok(AuthUser(user, _)) => {
+                return Ok(AuthListener { id: user.id, name: user.username, is_guest: false });
+            }
+            Err(e) => e,
+        };
+
+        let jar = PrivateCookieJar::<Key>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Cookie extractor failed: {:?}", e)))?;
+        let Some(guest) = GuestSession::from_jar(&jar) else {
+            return Err(unauthorized);
+        };
+
+        // Turning guests off locks out the ones that already have a cookie
+        if !state.station.read().await.settings.guests_enabled {
+            return Err(AppError::Unauthorized("Guest listening is turned off, please log in".to_string()));
+        }
+
+        Ok(AuthListener { id: guest.id, name: guest.name(), is_guest: true })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orm::users::models::UserStatus;
+
+    fn user(role: UserRole) -> User {
+        User {
+            id: 1,
+            username: "someone".to_string(),
+            password_hash: String::new(),
+            artist_id: None,
+            role,
+            total_listen_time: 0,
+            leaderboard_opt_out: false,
+            status: UserStatus::Active,
+        }
+    }
+
+    #[test]
+    fn admin_token_without_admin_scope_loses_admin_permissions() {
+        let auth = AuthUser(user(UserRole::Admin), Some(TokenScopes(vec![ApiScope::LibraryWrite])));
+
+        assert!(!auth.can(Permission::ManageUsers));
+        assert!(!auth.can(Permission::ManageStation));
+        // What curators and DJs can do isn't admin only
+        assert!(auth.can(Permission::EditMetadata));
+        assert!(auth.can(Permission::ManageQueue));
+    }
+
+    #[test]
+    fn admin_scope_and_cookie_sessions_keep_admin_permissions() {
+        let scoped = AuthUser(user(UserRole::Admin), Some(TokenScopes(vec![ApiScope::LibraryWrite, ApiScope::Admin])));
+        let session = AuthUser(user(UserRole::Admin), None);
+
+        assert!(scoped.can(Permission::ManageUsers));
+        assert!(session.can(Permission::ManageUsers));
+    }
+
+    #[test]
+    fn admin_scope_grants_nothing_the_role_lacks() {
+        let auth = AuthUser(user(UserRole::User), Some(TokenScopes(vec![ApiScope::Admin])));
+
+        assert!(!auth.can(Permission::ManageUsers));
+        assert!(!auth.can(Permission::EditMetadata));
+    }
+}
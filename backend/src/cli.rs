@@ -0,0 +1,93 @@
+use crate::rhythm::{compiler, validate};
+
+const RHYTHM_USAGE: &str = "Usage:
+  Wavy rhythm compile <input.json> [output.dat]
+  Wavy rhythm decompile <input.dat> [output.json]
+  Wavy rhythm validate <input.json> [--duration-ms <ms>]";
+
+/// Runs a subcommand if one was given, returning the process exit code.
+/// `None` means no subcommand, so the server should start.
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("rhythm") => Some(rhythm(&args[1..])),
+        _ => None,
+    }
+}
+
+fn rhythm(args: &[String]) -> i32 {
+    let command = args.first().map(String::as_str);
+    let input = args.get(1).map(String::as_str);
+
+    let (command, input) = match (command, input) {
+        (Some(command), Some(input)) => (command, input),
+        _ => {
+            eprintln!("{}", RHYTHM_USAGE);
+            return 2;
+        }
+    };
+
+    match command {
+        "compile" => match compiler::compile(input, args.get(2).map(String::as_str)) {
+            Ok(output) => {
+                println!("Compiled {} -> {}", input, output);
+                0
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                1
+            }
+        },
+        "decompile" => match compiler::decompile(input, args.get(2).map(String::as_str)) {
+            Ok(output) => {
+                println!("Decompiled {} -> {}", input, output);
+                0
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                1
+            }
+        },
+        "validate" => {
+            let duration_ms = match args.get(2).map(String::as_str) {
+                None => None,
+                Some("--duration-ms") => match args.get(3).and_then(|v| v.parse::<u64>().ok()) {
+                    Some(ms) => Some(ms),
+                    None => {
+                        eprintln!("--duration-ms expects a number of milliseconds");
+                        return 2;
+                    }
+                },
+                Some(other) => {
+                    eprintln!("Unknown option '{}'\n{}", other, RHYTHM_USAGE);
+                    return 2;
+                }
+            };
+
+            let source = match std::fs::read_to_string(input) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("Could not read '{}': {}", input, e);
+                    return 1;
+                }
+            };
+
+            match validate::parse(&source, duration_ms) {
+                Ok(track) => {
+                    println!("{}: OK, version {}, {} events", input, track.version, track.events.len());
+                    0
+                }
+                Err(errors) => {
+                    for error in &errors {
+                        eprintln!("{}:{}", input, error);
+                    }
+                    eprintln!("{} error(s)", errors.len());
+                    1
+                }
+            }
+        }
+        _ => {
+            eprintln!("{}", RHYTHM_USAGE);
+            2
+        }
+    }
+}
@@ -28,13 +28,171 @@ pub fn get_temporal_dir() -> PathBuf {
     ensure_exists(get_data_dir().join("temporal"))
 }
 
-pub fn get_save_dat_path() -> PathBuf {
-    get_data_dir().join("save.dat")
+pub fn get_rhythm_dir() -> PathBuf {
+    ensure_exists(get_data_dir().join("rhythm"))
+}
+
+/// Rhythm JSON of a song, as authored or generated
+pub fn get_rhythm_source_path(song_id: i64) -> PathBuf {
+    get_rhythm_dir().join(format!("{}.json", song_id))
+}
+
+/// Compiled rhythm data of a song, attached to its `SongStart`
+pub fn get_rhythm_data_path(song_id: i64) -> PathBuf {
+    get_rhythm_dir().join(format!("{}.dat", song_id))
+}
+
+pub fn get_waveform_path(song_id: i64) -> PathBuf {
+    get_music_dir().join(format!("{}.waveform.json", song_id))
+}
+
+/// Known breached passwords, one per line, refused as new passwords
+pub fn get_breached_passwords_path() -> PathBuf {
+    env::var("BREACHED_PASSWORDS_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| get_data_dir().join("breached_passwords.txt"))
+}
+
+/// BPM range the station picks songs from, songs without a detected BPM are always eligible
+pub fn get_station_bpm_range() -> (Option<f64>, Option<f64>) {
+    let read = |key: &str| env::var(key).ok().and_then(|v| v.parse::<f64>().ok());
+    (read("STATION_MIN_BPM"), read("STATION_MAX_BPM"))
+}
+
+/// How strongly dislikes lower a song's chance of being picked, 0 (the default) ignores them
+pub fn get_station_dislike_weight() -> f64 {
+    env::var("STATION_DISLIKE_WEIGHT")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|w| w.is_finite() && *w >= 0.0)
+        .unwrap_or(0.0)
+}
+
+/// Whether guests can listen without an account, off unless enabled
+pub fn get_guests_enabled() -> bool {
+    env::var("GUESTS_ENABLED")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+pub fn get_max_guests() -> usize {
+    env::var("MAX_GUESTS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_GUESTS)
+}
+
+/// Whether every user can play songs on demand, not only curators and admins. Off unless enabled.
+pub fn get_on_demand_enabled() -> bool {
+    env::var("ON_DEMAND_ENABLED")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Whether client IPs are read from the X-Real-IP / X-Forwarded-For headers of a reverse proxy
+pub fn get_trust_proxy_headers() -> bool {
+    env::var("TRUST_PROXY_HEADERS")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
 }
 
 // How many seconds of audio to buffer for burst (catch-up buffer for new clients)
 pub const BURST_BUFFER_SECONDS: f64 = 3.0;
 
+// Song request queue limits, overall and pending per listener
+pub const MAX_SONG_REQUESTS: usize = 50;
+pub const MAX_SONG_REQUESTS_PER_USER: usize = 3;
+
+// Longest reaction accepted over the WebSocket (in characters) and the time between two of them
+pub const MAX_REACTION_LENGTH: usize = 16;
+pub const REACTION_COOLDOWN_MS: u64 = 500;
+
+// Longest chat message (in characters) and the time between two of them
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 500;
+pub const CHAT_COOLDOWN_MS: u64 = 1000;
+
+// Page size of the chat history endpoint
+pub const CHAT_HISTORY_DEFAULT_LIMIT: i64 = 50;
+pub const CHAT_HISTORY_MAX_LIMIT: i64 = 200;
+
+// Page size of the listen history endpoint
+pub const LISTEN_HISTORY_DEFAULT_LIMIT: i64 = 50;
+pub const LISTEN_HISTORY_MAX_LIMIT: i64 = 200;
+
+// Length of the top songs/artists/tags lists in listening stats
+pub const STATS_TOP_DEFAULT_LIMIT: i64 = 10;
+pub const STATS_TOP_MAX_LIMIT: i64 = 50;
+pub const WRAPPED_TOP_LIMIT: i64 = 5;
+
+// Login sessions last this long, last-seen is written at most once per interval
+pub const SESSION_LIFETIME_DAYS: i64 = 30;
+pub const SESSION_TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+// API token lifetime when none is asked for, the longest one allowed and how many a user can have
+pub const API_TOKEN_DEFAULT_DAYS: i64 = 90;
+pub const API_TOKEN_MAX_DAYS: i64 = 365;
+pub const MAX_API_TOKENS_PER_USER: i64 = 20;
+
+// Accepted lengths of usernames and passwords, in characters
+pub const USERNAME_MIN_LENGTH: usize = 3;
+pub const USERNAME_MAX_LENGTH: usize = 32;
+pub const PASSWORD_MIN_LENGTH: usize = 8;
+pub const PASSWORD_MAX_LENGTH: usize = 128;
+
+// How long a password reset code made by an admin works
+pub const PASSWORD_RESET_HOURS: i64 = 24;
+
+// How long a guest can listen before getting a new guest session, and how many guests listen at once by default
+pub const GUEST_SESSION_HOURS: i64 = 12;
+pub const DEFAULT_MAX_GUESTS: usize = 10;
+
+// Invite lifetime when none is asked for, the longest one allowed and the most registrations one invite can allow
+pub const INVITE_DEFAULT_DAYS: i64 = 7;
+pub const INVITE_MAX_DAYS: i64 = 90;
+pub const INVITE_MAX_USES: i64 = 100;
+
+// Failed logins allowed before each further one is delayed, and the count that locks out, per account and per address.
+// Delays start at the base and double up to the max, failures are forgotten a window after the last one.
+pub const LOGIN_ACCOUNT_FREE_ATTEMPTS: u32 = 3;
+pub const LOGIN_ACCOUNT_LOCKOUT_FAILURES: u32 = 10;
+pub const LOGIN_IP_FREE_ATTEMPTS: u32 = 10;
+pub const LOGIN_IP_LOCKOUT_FAILURES: u32 = 50;
+pub const LOGIN_BACKOFF_BASE_SECONDS: u64 = 1;
+pub const LOGIN_BACKOFF_MAX_SECONDS: u64 = 300;
+pub const LOGIN_LOCKOUT_MINUTES: u64 = 15;
+pub const LOGIN_FAILURE_WINDOW_MINUTES: u64 = 60;
+// Addresses and accounts tracked at once, the least recently failed unblocked ones make way past it
+pub const LOGIN_THROTTLE_MAX_ENTRIES: usize = 100_000;
+
+// Longest playlist name and description in characters, songs a playlist can hold and playlists per user
+pub const PLAYLIST_NAME_MAX_LENGTH: usize = 100;
+pub const PLAYLIST_DESCRIPTION_MAX_LENGTH: usize = 1000;
+pub const PLAYLIST_MAX_SONGS: i64 = 1000;
+pub const MAX_PLAYLISTS_PER_USER: i64 = 100;
+
+// Length of the leaderboards
+pub const LEADERBOARD_DEFAULT_LIMIT: i64 = 10;
+pub const LEADERBOARD_MAX_LIMIT: i64 = 50;
+
+// Audit log page size
+pub const AUDIT_LOG_DEFAULT_LIMIT: i64 = 50;
+pub const AUDIT_LOG_MAX_LIMIT: i64 = 200;
+
+// Songs analysed at once because a request needed their waveform
+pub const MAX_CONCURRENT_ANALYSES: usize = 2;
+
+// Interval between progress events
+pub const PROGRESS_TICK_SECONDS: f64 = 1.0;
+
+// A stream connection that neither took audio nor sent a heartbeat for this long is ended
+pub const LISTENER_IDLE_TIMEOUT_SECONDS: i64 = 120;
+
+// Desync samples kept per listener
+pub const DESYNC_HISTORY_LEN: usize = 120;
+
+// How far ahead of the playback position rhythm events are pushed to WebSocket clients
+pub const RHYTHM_LOOKAHEAD_SECONDS: f64 = 3.0;
+
 // MP3 Frame constants
 // Most MP3s are 44.1kHz, 1152 samples per frame = ~26ms per frame
 pub const SAMPLES_PER_FRAME: u32 = 1152;
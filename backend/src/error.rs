@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -14,6 +14,10 @@ pub enum AppError {
     WrongCredentials,
     NotFound(String),
     Conflict(String),
+    ValidationFailed(Vec<String>),
+    /// Seconds until the client may retry
+    TooManyRequests(u64),
+    ServiceUnavailable(String),
 }
 
 impl IntoResponse for AppError {
@@ -26,6 +30,21 @@ impl IntoResponse for AppError {
             AppError::WrongCredentials => (StatusCode::UNAUTHORIZED, "Wrong credentials".to_string()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+            AppError::ValidationFailed(details) => {
+                let body = Json(json!({
+                    "error": "Validation failed",
+                    "details": details,
+                }));
+                return (StatusCode::UNPROCESSABLE_ENTITY, body).into_response();
+            }
+            AppError::TooManyRequests(retry_after) => {
+                let body = Json(json!({
+                    "error": "Too many attempts, try again later",
+                    "retry_after": retry_after,
+                }));
+                return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after.to_string())], body).into_response();
+            }
         };
 
         let body = Json(json!({
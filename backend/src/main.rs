@@ -1,13 +1,14 @@
 mod config;
 mod auth;
+mod cli;
 mod state;
 mod orm;
 
 use crate::config::{BROADCAST_BUFFER_FRAMES, DISK_BUFFER_FRAMES};
-use crate::state::{AppState, AudioFrame, StationData, StreamMessage, StationEvent};
-use chrono::{Utc, Duration};
+use crate::state::{AppState, AudioFrame, StationData, StationSettings, StreamMessage, StationEvent};
+use chrono::Utc;
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use std::collections::VecDeque;
@@ -21,14 +22,21 @@ use tower_http::services::ServeFile;
 use tower_http::cors::CorsLayer;
 use axum::http::{Method, HeaderValue};
 use axum_extra::extract::cookie::Key;
-use streaming::{broadcaster, handlers, loader};
+use streaming::{broadcaster, handlers, loader, presence};
 
 mod error;
 mod streaming;
 mod rhythm;
+mod analysis;
+mod throttle;
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     dotenv().ok();
 
     tracing_subscriber::fmt::init();
@@ -39,7 +47,7 @@ async fn main() {
     // DB initialization
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
-    let db_options = SqliteConnectOptions::from_str(&*db_url)
+    let db_options = SqliteConnectOptions::from_str(&db_url)
         .expect("Invalid connection string")
         .create_if_missing(true)
         .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
@@ -67,12 +75,25 @@ async fn main() {
 
     // Shared state
     let buffer_history = Arc::new(RwLock::new(VecDeque::<AudioFrame>::new()));
-    let station_data = Arc::new(RwLock::new(StationData::default()));
+    let station_data = Arc::new(RwLock::new(StationData {
+        settings: StationSettings::from_env(),
+        ..Default::default()
+    }));
 
     // Load signing key from environment variable
     let cookie_key_str = env::var("COOKIE_KEY").expect("COOKIE_KEY must be set in .env");
     let cookie_key = Key::from(cookie_key_str.as_bytes());
 
+    // Without an admin, whoever has this token (from the server log) registers the first one
+    let admins = orm::users::repository::count_admins(&pool)
+        .await
+        .expect("Failed to count admins");
+    let setup_token_hash = (admins == 0).then(|| {
+        let token = auth::new_secret_token();
+        tracing::warn!("No admin account yet. Register the first one with the setup token: {}", token);
+        auth::hash_token(&token)
+    });
+
     let app_state = AppState {
         tx: radio_tx,
         event_tx,
@@ -80,6 +101,9 @@ async fn main() {
         station: station_data.clone(),
         db: pool,
         cookie_key: cookie_key.clone(),
+        login_throttle: Default::default(),
+        setup_token_hash: Arc::new(tokio::sync::Mutex::new(setup_token_hash)),
+        analysis_queue: Default::default(),
     };
 
     // Start the loader (reads MP3 files and sends frames)
@@ -89,75 +113,45 @@ async fn main() {
     let history_clone = buffer_history.clone();
     let station_clone = station_data.clone();
     let event_tx_clone = app_state.event_tx.clone();
+    let db_broadcaster = app_state.db.clone();
     tokio::spawn(async move {
-        broadcaster::start(radio_tx_for_broadcaster, event_tx_clone, disk_rx, history_clone, station_clone).await;
+        broadcaster::start(radio_tx_for_broadcaster, event_tx_clone, disk_rx, history_clone, station_clone, db_broadcaster).await;
     });
 
-    // Start listener cleanup task (removes stale listeners)
-    // Start listener cleanup & Leaderboard update task
-    let station_cleanup = station_data.clone();
-    let db_update = app_state.db.clone();
+    // Save listen time of the connected listeners every 10 seconds.
+    // Disconnects are handled when the stream body is dropped, idle connections are ended here.
+    let state_presence = app_state.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
         loop {
             interval.tick().await;
-            
-            let mut updates = Vec::new();
-
-            // Scope for Write Lock
-            {
-                let mut guard = station_cleanup.write().await;
-                let now = Utc::now();
-                
-                // Collect IDs to remove
-                let stale_ids: Vec<i64> = guard.listeners.iter()
-                    .filter(|(_, l)| l.is_stale(20))
-                    .map(|(&id, _)| id)
-                    .collect();
-                
-                if !stale_ids.is_empty() {
-                    tracing::info!("Removing {} stale listeners", stale_ids.len());
-                    for id in stale_ids {
-                        if let Some(removed) = guard.listeners.remove(&id) {
-                            tracing::debug!("Removed stale listener: {} (ID: {})", removed.username, removed.user_id);
-                        }
-                    }
-                }
-
-                // Accumulate listen time for remaining active listeners
-                for listener in guard.listeners.values_mut() {
-                    let elapsed_ms = now.signed_duration_since(listener.last_saved_at).num_milliseconds();
-                    
-                    // Only update if we have at least 1 second to add
-                    if elapsed_ms >= 1000 {
-                        let seconds_to_add = elapsed_ms / 1000;
-                        if seconds_to_add > 0 {
-                            updates.push((listener.user_id, seconds_to_add));
-                            // Advance last_saved_at by the exact amount we're saving
-                            // This preserves the remainder milliseconds for the next update
-                            listener.last_saved_at = listener.last_saved_at + Duration::seconds(seconds_to_add);
-                        }
-                    } else if elapsed_ms < 0 {
-                        // Handle potential clock skew
-                        listener.last_saved_at = now;
-                    }
-                }
-            } // Drop lock
-
-            // Update DB (outside lock)
-            for (user_id, seconds) in updates {
-                let result = sqlx::query!(
-                    "UPDATE users SET total_listen_time = total_listen_time + ? WHERE id = ?",
-                    seconds,
-                    user_id
-                )
-                .execute(&db_update)
-                .await;
-
-                if let Err(e) = result {
-                    tracing::error!("Failed to update listen time for user {}: {}", user_id, e);
-                }
+
+            presence::end_idle_connections(&state_presence).await;
+
+            let (earned, current_play) = {
+                let mut guard = state_presence.station.write().await;
+                // Song changes save the time before them, what's left is the current song's
+                let earned = presence::take_listen_time(&mut guard, Utc::now());
+                presence::attribute_listen_time(&mut guard, earned)
+            };
+
+            presence::save_listen_time(&state_presence.db, earned, current_play).await;
+        }
+    });
+
+    // Purge expired login sessions and forgotten login failures
+    let db_sessions = app_state.db.clone();
+    let login_throttle = app_state.login_throttle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match orm::sessions::repository::delete_expired(&db_sessions).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Removed {} expired sessions", count),
+                Err(e) => tracing::error!("Failed to remove expired sessions: {}", e),
             }
+            login_throttle.lock().await.purge();
         }
     });
 
@@ -168,10 +162,22 @@ async fn main() {
         .merge(orm::albums::router())
         .merge(orm::songs::router())
         .merge(orm::tags::router())
+        .merge(orm::chat::router())
+        .merge(orm::ratings::router())
+        .merge(orm::history::router())
+        .merge(orm::leaderboard::router())
+        .merge(orm::sessions::router())
+        .merge(orm::tokens::router())
+        .merge(orm::audit::router())
+        .merge(orm::invites::router())
+        .merge(orm::playlists::router())
         .route("/stream", get(handlers::stream_audio))
         .route("/heartbeat", post(handlers::heartbeat))
         .route("/listeners", get(handlers::get_active_listeners))
+        .route("/listeners/desync", get(handlers::get_listener_desync))
         .route("/song/current", get(handlers::get_current_song))
+        .route("/station/settings", get(handlers::get_station_settings).post(handlers::update_station_settings))
+        .route("/station/queue/{song_id}", delete(handlers::remove_song_request))
         .route("/ws", get(handlers::ws_handler));
 
     let cors = CorsLayer::new()
@@ -186,6 +192,7 @@ async fn main() {
             axum::http::header::ACCEPT,
             axum::http::header::COOKIE,
         ])
+        .expose_headers([axum::http::HeaderName::from_static("x-wavy-connection-id")])
         .allow_credentials(true);
 
     let app = Router::new()
@@ -201,5 +208,6 @@ async fn main() {
     tracing::info!("Server ready at http://0.0.0.0:3000");
     tracing::info!("Playing MP3s from: {}", config::get_music_dir().display());
 
-    axum::serve(listener, app).await.unwrap();
+    // Client addresses are kept with login sessions
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await.unwrap();
 }
\ No newline at end of file
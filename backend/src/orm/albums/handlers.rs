@@ -8,7 +8,8 @@ use crate::state::AppState;
 use crate::error::AppError;
 use super::models::{CreateAlbumDto, UpdateAlbumDto, Album};
 use super::repository;
-use crate::auth::AdminOnly;
+use crate::auth::{perm, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
 
 pub async fn list_albums(
     State(state): State<AppState>,
@@ -32,34 +33,47 @@ pub async fn get_album(
 
 pub async fn create_album(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Json(payload): Json<CreateAlbumDto>,
 ) -> Result<Json<Album>, AppError> {
     let album = repository::create(&state.db, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Create, AuditEntity::Album, Some(album.id), audit::NOTHING, Some(&album)).await;
     Ok(Json(album))
 }
 
 pub async fn update_album(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Path(id): Path<i64>,
     Json(payload): Json<UpdateAlbumDto>,
 ) -> Result<Json<Album>, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Album not found".to_string()))?;
+
     let album = repository::update(&state.db, id, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::Album, Some(id), Some(&before), Some(&album)).await;
     Ok(Json(album))
 }
 
 pub async fn delete_album(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::ManageLibrary>,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Album not found".to_string()))?;
+
     repository::delete(&state.db, id)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::Album, Some(id), Some(&before), audit::NOTHING).await;
     Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
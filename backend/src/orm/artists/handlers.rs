@@ -8,7 +8,8 @@ use crate::state::AppState;
 use crate::error::AppError;
 use super::models::{CreateArtistDto, UpdateArtistDto, Artist};
 use super::repository;
-use crate::auth::AdminOnly;
+use crate::auth::{perm, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
 
 pub async fn list_artists(
     State(state): State<AppState>,
@@ -32,34 +33,47 @@ pub async fn get_artist(
 
 pub async fn create_artist(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Json(payload): Json<CreateArtistDto>,
 ) -> Result<Json<Artist>, AppError> {
     let artist = repository::create(&state.db, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Create, AuditEntity::Artist, Some(artist.id), audit::NOTHING, Some(&artist)).await;
     Ok(Json(artist))
 }
 
 pub async fn update_artist(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Path(id): Path<i64>,
     Json(payload): Json<UpdateArtistDto>,
 ) -> Result<Json<Artist>, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Artist not found".to_string()))?;
+
     let artist = repository::update(&state.db, id, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::Artist, Some(id), Some(&before), Some(&artist)).await;
     Ok(Json(artist))
 }
 
 pub async fn delete_artist(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::ManageLibrary>,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Artist not found".to_string()))?;
+
     repository::delete(&state.db, id)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::Artist, Some(id), Some(&before), audit::NOTHING).await;
     Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
@@ -0,0 +1,24 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{AuditEntry, AuditQuery};
+use super::repository;
+use crate::auth::{perm, Require};
+use crate::config::{AUDIT_LOG_DEFAULT_LIMIT, AUDIT_LOG_MAX_LIMIT};
+
+pub async fn list_entries(
+    State(state): State<AppState>,
+    _: Require<perm::ViewAuditLog>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    let limit = query.limit.unwrap_or(AUDIT_LOG_DEFAULT_LIMIT).clamp(1, AUDIT_LOG_MAX_LIMIT);
+
+    let entries = repository::find(&state.db, &query, limit)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(entries.into_iter().map(AuditEntry::from).collect()))
+}
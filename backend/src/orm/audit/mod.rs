@@ -0,0 +1,56 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::get;
+use serde::Serialize;
+use sqlx::{SqliteExecutor, SqlitePool};
+
+use crate::state::AppState;
+use crate::orm::users::models::User;
+use crate::auth::ClientInfo;
+use crate::throttle::Lockout;
+use models::{AuditAction, AuditEntity, FailedLoginDetails};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/audit", get(handlers::list_entries))
+}
+
+/// Records a change made by `actor`. `before` and `after` are the entity around the change,
+/// `None` where it didn't exist. Failing to record is logged and doesn't fail the request.
+pub async fn record<'e, B: Serialize, A: Serialize>(
+    db: impl SqliteExecutor<'e>,
+    actor: &User,
+    action: AuditAction,
+    entity: AuditEntity,
+    entity_id: Option<i64>,
+    before: Option<&B>,
+    after: Option<&A>,
+) {
+    let before = before.and_then(|v| serde_json::to_string(v).ok());
+    let after = after.and_then(|v| serde_json::to_string(v).ok());
+
+    if let Err(e) = repository::create(db, Some(actor.id), &actor.username, action, entity, entity_id, before, after).await {
+        tracing::error!("Failed to record {:?} of {:?} #{:?} by {}: {}", action, entity, entity_id, actor.username, e);
+    }
+}
+
+/// Records a failed login for `identity`, under the account if there is one by that name
+pub async fn record_failed_login(pool: &SqlitePool, identity: &str, user: Option<&User>, client: &ClientInfo, lockout: Lockout) {
+    let details = FailedLoginDetails {
+        ip: client.ip.as_deref(),
+        user_agent: client.user_agent.as_deref(),
+        locked_ip: lockout.ip,
+        locked_account: lockout.account,
+    };
+    let after = serde_json::to_string(&details).ok();
+
+    if let Err(e) = repository::create(pool, None, identity, AuditAction::FailedLogin, AuditEntity::User, user.map(|u| u.id), None, after).await {
+        tracing::error!("Failed to record failed login for {}: {}", identity, e);
+    }
+}
+
+/// For the `before` or `after` of `record` when there is nothing to show
+pub const NOTHING: Option<&()> = None;
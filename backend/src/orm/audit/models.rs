@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    /// A wrong password or unknown username, the actor is whoever tried
+    FailedLogin,
+}
+
+/// Kind of thing an audit entry is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum AuditEntity {
+    Artist,
+    Album,
+    Song,
+    /// A tag on a song, `entity_id` is the song
+    SongTag,
+    /// Rhythm data of a song, `entity_id` is the song
+    Rhythm,
+    Tag,
+    User,
+    ChatMessage,
+    /// `entity_id` is the muted user
+    ChatMute,
+    /// `entity_id` is the requested song
+    SongRequest,
+    StationSettings,
+    Invite,
+    /// A reset code made for the user `entity_id`
+    PasswordReset,
+    /// A password changed or reset, `entity_id` is the user
+    Password,
+    Playlist,
+    /// `entity_id` is the rated song
+    SongRating,
+    ApiToken,
+    /// A logged in device, no `entity_id` when every session of the actor was revoked
+    Session,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AuditEntryRow {
+    pub id: i64,
+    pub actor_id: Option<i64>,
+    pub actor_name: String,
+    pub action: AuditAction,
+    pub entity: AuditEntity,
+    pub entity_id: Option<i64>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_id: Option<i64>,
+    pub actor_name: String,
+    pub action: AuditAction,
+    pub entity: AuditEntity,
+    pub entity_id: Option<i64>,
+    pub before: Option<Box<RawValue>>,
+    pub after: Option<Box<RawValue>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AuditEntryRow> for AuditEntry {
+    fn from(row: AuditEntryRow) -> Self {
+        // Stored by `audit::record` from serde_json, so always valid
+        let json = |value: Option<String>| value.and_then(|v| RawValue::from_string(v).ok());
+
+        Self {
+            id: row.id,
+            actor_id: row.actor_id,
+            actor_name: row.actor_name,
+            action: row.action,
+            entity: row.entity,
+            entity_id: row.entity_id,
+            before: json(row.before),
+            after: json(row.after),
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub actor_id: Option<i64>,
+    pub entity: Option<AuditEntity>,
+    pub entity_id: Option<i64>,
+    pub action: Option<AuditAction>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only entries older than this id, to page backwards
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// What is recorded about a failed login
+#[derive(Debug, Serialize)]
+pub struct FailedLoginDetails<'a> {
+    pub ip: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub locked_ip: bool,
+    pub locked_account: bool,
+}
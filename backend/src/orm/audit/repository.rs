@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use sqlx::{SqliteExecutor, SqlitePool};
+use super::models::{AuditAction, AuditEntity, AuditEntryRow, AuditQuery};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create<'e>(
+    db: impl SqliteExecutor<'e>,
+    actor_id: Option<i64>,
+    actor_name: &str,
+    action: AuditAction,
+    entity: AuditEntity,
+    entity_id: Option<i64>,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<(), String> {
+    let now = Utc::now();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (actor_id, actor_name, action, entity, entity_id, before, after, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        actor_id,
+        actor_name,
+        action,
+        entity,
+        entity_id,
+        before,
+        after,
+        now
+    )
+    .execute(db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Entries matching every filter that is set, newest first
+pub async fn find(pool: &SqlitePool, query: &AuditQuery, limit: i64) -> Result<Vec<AuditEntryRow>, String> {
+    let before = query.before.unwrap_or(i64::MAX);
+
+    sqlx::query_as!(
+        AuditEntryRow,
+        r#"
+        SELECT
+            id as "id!",
+            actor_id,
+            actor_name,
+            action as "action: AuditAction",
+            entity as "entity: AuditEntity",
+            entity_id,
+            before,
+            after,
+            created_at as "created_at: DateTime<Utc>"
+        FROM audit_log
+        WHERE id < ?1
+            AND (?2 IS NULL OR actor_id = ?2)
+            AND (?3 IS NULL OR entity = ?3)
+            AND (?4 IS NULL OR entity_id = ?4)
+            AND (?5 IS NULL OR action = ?5)
+            AND (?6 IS NULL OR created_at >= ?6)
+            AND (?7 IS NULL OR created_at < ?7)
+        ORDER BY id DESC
+        LIMIT ?8
+        "#,
+        before,
+        query.actor_id,
+        query.entity,
+        query.entity_id,
+        query.action,
+        query.since,
+        query.until,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,130 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+
+use crate::state::{AppState, StationEvent};
+use crate::error::AppError;
+use super::models::{ChatHistoryQuery, ChatMessage, ChatMute, CreateMuteDto, SongReaction};
+use super::repository;
+use crate::auth::{perm, AuthUser, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::config::{CHAT_HISTORY_DEFAULT_LIMIT, CHAT_HISTORY_MAX_LIMIT};
+
+pub async fn get_history(
+    State(state): State<AppState>,
+    _user: AuthUser,
+    Query(query): Query<ChatHistoryQuery>,
+) -> Result<Json<Vec<ChatMessage>>, AppError> {
+    let limit = query.limit.unwrap_or(CHAT_HISTORY_DEFAULT_LIMIT).clamp(1, CHAT_HISTORY_MAX_LIMIT);
+
+    let messages = repository::find_messages(&state.db, query.before, limit)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(messages))
+}
+
+pub async fn delete_message(
+    State(state): State<AppState>,
+    Require(moderator, _): Require<perm::ModerateChat>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let message = repository::find_message(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Message not found".to_string()))?;
+
+    let deleted = repository::delete_message(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    if !deleted {
+        return Err(AppError::NotFound("Message not found".to_string()));
+    }
+    audit::record(&state.db, &moderator, AuditAction::Delete, AuditEntity::ChatMessage, Some(id), Some(&message), audit::NOTHING).await;
+
+    // Lets connected clients drop it from their view
+    let _ = state.event_tx.send(StationEvent::ChatMessageDeleted(id));
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_mutes(
+    State(state): State<AppState>,
+    _: Require<perm::ModerateChat>,
+) -> Result<Json<Vec<ChatMute>>, AppError> {
+    let mutes = repository::find_active_mutes(&state.db)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(mutes))
+}
+
+pub async fn mute_user(
+    State(state): State<AppState>,
+    Require(admin, _): Require<perm::ModerateChat>,
+    Json(payload): Json<CreateMuteDto>,
+) -> Result<Json<ChatMute>, AppError> {
+    if payload.duration_seconds <= 0 {
+        return Err(AppError::BadRequest("duration_seconds must be positive".to_string()));
+    }
+
+    let user = crate::orm::users::repository::find_by_id(&state.db, payload.user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    let muted_until = Duration::try_seconds(payload.duration_seconds)
+        .and_then(|duration| Utc::now().checked_add_signed(duration))
+        .ok_or(AppError::BadRequest("duration_seconds is too large".to_string()))?;
+
+    repository::mute(&state.db, user.id, muted_until, payload.reason.clone(), admin.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    let mute = ChatMute {
+        user_id: user.id,
+        username: user.username,
+        muted_until,
+        reason: payload.reason,
+        muted_by: Some(admin.id),
+    };
+    audit::record(&state.db, &admin, AuditAction::Create, AuditEntity::ChatMute, Some(mute.user_id), audit::NOTHING, Some(&mute)).await;
+
+    Ok(Json(mute))
+}
+
+pub async fn unmute_user(
+    State(state): State<AppState>,
+    Require(moderator, _): Require<perm::ModerateChat>,
+    Path(user_id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let before = repository::find_active_mutes(&state.db)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .into_iter()
+        .find(|mute| mute.user_id == user_id);
+
+    let unmuted = repository::unmute(&state.db, user_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    if !unmuted {
+        return Err(AppError::NotFound("User is not muted".to_string()));
+    }
+    audit::record(&state.db, &moderator, AuditAction::Delete, AuditEntity::ChatMute, Some(user_id), before.as_ref(), audit::NOTHING).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_song_reactions(
+    State(state): State<AppState>,
+    _user: AuthUser,
+    Path(song_id): Path<i64>,
+) -> Result<Json<Vec<SongReaction>>, AppError> {
+    let reactions = repository::find_reactions_by_song(&state.db, song_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(reactions))
+}
@@ -0,0 +1,17 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::{get, delete};
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/chat/messages", get(handlers::get_history))
+        .route("/chat/messages/{id}", delete(handlers::delete_message))
+        .route("/chat/mutes", get(handlers::list_mutes).post(handlers::mute_user))
+        .route("/chat/mutes/{user_id}", delete(handlers::unmute_user))
+        .route("/songs/{song_id}/reactions", get(handlers::get_song_reactions))
+}
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ChatMessage {
+    pub id: i64,
+    pub user_id: i64,
+    pub username: String,
+    /// Song playing when the message was sent
+    pub song_id: Option<i64>,
+    pub song_title: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SongReaction {
+    pub id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub song_id: i64,
+    pub emoji: String,
+    /// Position in the song the reaction was sent at
+    pub position_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ChatMute {
+    pub user_id: i64,
+    pub username: String,
+    pub muted_until: DateTime<Utc>,
+    pub reason: Option<String>,
+    pub muted_by: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatHistoryQuery {
+    /// Only messages older than this id, to page backwards
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMuteDto {
+    pub user_id: i64,
+    pub duration_seconds: i64,
+    pub reason: Option<String>,
+}
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use super::models::{ChatMessage, ChatMute, SongReaction};
+
+pub async fn create_message(pool: &SqlitePool, user_id: i64, song_id: Option<i64>, body: &str) -> Result<ChatMessage, String> {
+    let now = Utc::now();
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO chat_messages (user_id, song_id, body, created_at)
+        VALUES (?, ?, ?, ?)
+        RETURNING id as "id!"
+        "#,
+        user_id,
+        song_id,
+        body,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    find_message(pool, id)
+        .await?
+        .ok_or_else(|| "Chat message not found after insert".to_string())
+}
+
+pub async fn find_message(pool: &SqlitePool, id: i64) -> Result<Option<ChatMessage>, String> {
+    sqlx::query_as!(
+        ChatMessage,
+        r#"
+        SELECT
+            m.id as "id!",
+            m.user_id,
+            u.username as "username!",
+            m.song_id,
+            s.title as "song_title?",
+            m.body,
+            m.created_at as "created_at: DateTime<Utc>"
+        FROM chat_messages m
+        JOIN users u ON m.user_id = u.id
+        LEFT JOIN songs s ON m.song_id = s.id
+        WHERE m.id = ?
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// A page of messages older than `before`, oldest first
+pub async fn find_messages(pool: &SqlitePool, before: Option<i64>, limit: i64) -> Result<Vec<ChatMessage>, String> {
+    let before = before.unwrap_or(i64::MAX);
+
+    let mut messages = sqlx::query_as!(
+        ChatMessage,
+        r#"
+        SELECT
+            m.id as "id!",
+            m.user_id,
+            u.username as "username!",
+            m.song_id,
+            s.title as "song_title?",
+            m.body,
+            m.created_at as "created_at: DateTime<Utc>"
+        FROM chat_messages m
+        JOIN users u ON m.user_id = u.id
+        LEFT JOIN songs s ON m.song_id = s.id
+        WHERE m.id < ?
+        ORDER BY m.id DESC
+        LIMIT ?
+        "#,
+        before,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Returns whether a message was deleted
+pub async fn delete_message(pool: &SqlitePool, id: i64) -> Result<bool, String> {
+    let result = sqlx::query!("DELETE FROM chat_messages WHERE id = ?", id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_reaction(
+    pool: &SqlitePool,
+    user_id: i64,
+    song_id: i64,
+    emoji: &str,
+    position_ms: i64,
+) -> Result<SongReaction, String> {
+    let now = Utc::now();
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO song_reactions (user_id, song_id, emoji, position_ms, created_at)
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING id as "id!"
+        "#,
+        user_id,
+        song_id,
+        emoji,
+        position_ms,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    sqlx::query_as!(
+        SongReaction,
+        r#"
+        SELECT
+            r.id as "id!",
+            r.user_id,
+            u.username as "username!",
+            r.song_id,
+            r.emoji,
+            r.position_ms,
+            r.created_at as "created_at: DateTime<Utc>"
+        FROM song_reactions r
+        JOIN users u ON r.user_id = u.id
+        WHERE r.id = ?
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Reactions of a song in timeline order
+pub async fn find_reactions_by_song(pool: &SqlitePool, song_id: i64) -> Result<Vec<SongReaction>, String> {
+    sqlx::query_as!(
+        SongReaction,
+        r#"
+        SELECT
+            r.id as "id!",
+            r.user_id,
+            u.username as "username!",
+            r.song_id,
+            r.emoji,
+            r.position_ms,
+            r.created_at as "created_at: DateTime<Utc>"
+        FROM song_reactions r
+        JOIN users u ON r.user_id = u.id
+        WHERE r.song_id = ?
+        ORDER BY r.position_ms
+        "#,
+        song_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Mutes a user until the given time, replacing any previous mute
+pub async fn mute(
+    pool: &SqlitePool,
+    user_id: i64,
+    muted_until: DateTime<Utc>,
+    reason: Option<String>,
+    muted_by: i64,
+) -> Result<(), String> {
+    sqlx::query!(
+        r#"
+        INSERT INTO chat_mutes (user_id, muted_until, reason, muted_by)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET
+            muted_until = excluded.muted_until,
+            reason = excluded.reason,
+            muted_by = excluded.muted_by
+        "#,
+        user_id,
+        muted_until,
+        reason,
+        muted_by
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Returns whether the user was muted
+pub async fn unmute(pool: &SqlitePool, user_id: i64) -> Result<bool, String> {
+    let result = sqlx::query!("DELETE FROM chat_mutes WHERE user_id = ?", user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Mutes that haven't expired yet
+pub async fn find_active_mutes(pool: &SqlitePool) -> Result<Vec<ChatMute>, String> {
+    let mutes = sqlx::query_as!(
+        ChatMute,
+        r#"
+        SELECT
+            m.user_id,
+            u.username as "username!",
+            m.muted_until as "muted_until: DateTime<Utc>",
+            m.reason,
+            m.muted_by
+        FROM chat_mutes m
+        JOIN users u ON m.user_id = u.id
+        ORDER BY m.muted_until
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let now = Utc::now();
+    Ok(mutes.into_iter().filter(|m| m.muted_until > now).collect())
+}
+
+/// End of the user's mute, if they are muted right now
+pub async fn find_active_mute(pool: &SqlitePool, user_id: i64) -> Result<Option<DateTime<Utc>>, String> {
+    let muted_until = sqlx::query_scalar!(
+        r#"SELECT muted_until as "muted_until: DateTime<Utc>" FROM chat_mutes WHERE user_id = ?"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(muted_until.filter(|until| *until > Utc::now()))
+}
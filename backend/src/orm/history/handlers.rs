@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{self, HistoryQuery, ListenEntry, PeriodQuery, UserStats, WrappedQuery, WrappedSummary};
+use super::repository;
+use crate::auth::AuthUser;
+use crate::config::{
+    LISTEN_HISTORY_DEFAULT_LIMIT, LISTEN_HISTORY_MAX_LIMIT, STATS_TOP_DEFAULT_LIMIT, STATS_TOP_MAX_LIMIT,
+    WRAPPED_TOP_LIMIT,
+};
+
+pub async fn get_history(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<ListenEntry>>, AppError> {
+    let limit = query.limit.unwrap_or(LISTEN_HISTORY_DEFAULT_LIMIT).clamp(1, LISTEN_HISTORY_MAX_LIMIT);
+
+    let history = repository::find_history(&state.db, user.0.id, query.before, limit)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(history))
+}
+
+pub async fn get_stats(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<PeriodQuery>,
+) -> Result<Json<UserStats>, AppError> {
+    let limit = query.limit.unwrap_or(STATS_TOP_DEFAULT_LIMIT).clamp(1, STATS_TOP_MAX_LIMIT);
+    let since = query.period.since();
+    let user_id = user.0.id;
+
+    let totals = repository::find_totals(&state.db, user_id, since, None)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let top_songs = repository::find_top_songs(&state.db, user_id, since, None, limit)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let top_artists = repository::find_top_artists(&state.db, user_id, since, None, limit)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let top_tags = repository::find_top_tags(&state.db, user_id, since, None, limit)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(UserStats {
+        period: query.period,
+        since,
+        totals,
+        top_songs,
+        top_artists,
+        top_tags,
+    }))
+}
+
+pub async fn get_wrapped(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<WrappedQuery>,
+) -> Result<Json<WrappedSummary>, AppError> {
+    let year = query.year.unwrap_or_else(models::current_year);
+    let (start, end) = models::year_range(year)
+        .ok_or(AppError::BadRequest("Invalid year".to_string()))?;
+    let (since, until) = (Some(start), Some(end));
+    let user_id = user.0.id;
+
+    let totals = repository::find_totals(&state.db, user_id, since, until)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let top_songs = repository::find_top_songs(&state.db, user_id, since, until, WRAPPED_TOP_LIMIT)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let top_artists = repository::find_top_artists(&state.db, user_id, since, until, WRAPPED_TOP_LIMIT)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let top_tags = repository::find_top_tags(&state.db, user_id, since, until, WRAPPED_TOP_LIMIT)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let busiest_month = repository::find_busiest_month(&state.db, user_id, since, until)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(WrappedSummary {
+        year,
+        totals,
+        top_songs,
+        top_artists,
+        top_tags,
+        busiest_month,
+    }))
+}
@@ -0,0 +1,15 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::get;
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/users/me/history", get(handlers::get_history))
+        .route("/users/me/stats", get(handlers::get_stats))
+        .route("/users/me/wrapped", get(handlers::get_wrapped))
+}
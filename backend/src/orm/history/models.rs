@@ -0,0 +1,129 @@
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Window statistics are computed over, counted back from now
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsPeriod {
+    Day,
+    Week,
+    Month,
+    Year,
+    #[default]
+    All,
+}
+
+impl StatsPeriod {
+    /// Start of the window, `None` for all time
+    pub fn since(self) -> Option<DateTime<Utc>> {
+        let days = match self {
+            StatsPeriod::Day => 1,
+            StatsPeriod::Week => 7,
+            StatsPeriod::Month => 30,
+            StatsPeriod::Year => 365,
+            StatsPeriod::All => return None,
+        };
+        Some(Utc::now() - Duration::days(days))
+    }
+}
+
+/// Bounds of a calendar year, for the wrapped summary
+pub fn year_range(year: i32) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).single()?;
+    let end = Utc.with_ymd_and_hms(year + 1, 1, 1, 0, 0, 0).single()?;
+    Some((start, end))
+}
+
+pub fn current_year() -> i32 {
+    Utc::now().year()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PeriodQuery {
+    #[serde(default)]
+    pub period: StatsPeriod,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// Only plays that started before this play id, to page backwards
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WrappedQuery {
+    pub year: Option<i32>,
+}
+
+/// A song the user heard, and how much of it
+#[derive(Debug, Serialize, FromRow)]
+pub struct ListenEntry {
+    pub play_id: i64,
+    pub song_id: i64,
+    pub title: String,
+    pub artist_names: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub listened_ms: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TopSong {
+    pub song_id: i64,
+    pub title: String,
+    pub artist_names: Option<String>,
+    pub listened_ms: i64,
+    pub plays: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TopArtist {
+    pub artist_id: i64,
+    pub name: String,
+    pub listened_ms: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TopTag {
+    pub tag_id: i64,
+    pub name: String,
+    /// Listening time weighted by how strongly each song has the tag
+    pub listened_ms: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListenTotals {
+    pub listened_ms: i64,
+    pub songs_heard: i64,
+    pub artists_heard: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserStats {
+    pub period: StatsPeriod,
+    pub since: Option<DateTime<Utc>>,
+    pub totals: ListenTotals,
+    pub top_songs: Vec<TopSong>,
+    pub top_artists: Vec<TopArtist>,
+    pub top_tags: Vec<TopTag>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthListen {
+    /// `YYYY-MM`
+    pub month: String,
+    pub listened_ms: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WrappedSummary {
+    pub year: i32,
+    pub totals: ListenTotals,
+    pub top_songs: Vec<TopSong>,
+    pub top_artists: Vec<TopArtist>,
+    pub top_tags: Vec<TopTag>,
+    pub busiest_month: Option<MonthListen>,
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use super::models::{ListenEntry, ListenTotals, MonthListen, TopArtist, TopSong, TopTag};
+
+// Range filters take `since` and `until` as optional bounds on `play_history.started_at`,
+// `None` leaves that side open.
+
+/// Records that the station started playing a song, returns the play id
+pub async fn record_play(pool: &SqlitePool, song_id: i64, started_at: DateTime<Utc>, duration_ms: i64) -> Result<i64, String> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO play_history (song_id, started_at, duration_ms)
+        VALUES (?, ?, ?)
+        RETURNING id as "id!"
+        "#,
+        song_id,
+        started_at,
+        duration_ms
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Adds listening time of a user to a play
+pub async fn log_listen(pool: &SqlitePool, user_id: i64, play_id: i64, song_id: i64, listened_ms: i64) -> Result<(), String> {
+    sqlx::query!(
+        r#"
+        INSERT INTO listen_log (user_id, play_id, song_id, listened_ms)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(user_id, play_id) DO UPDATE SET
+            listened_ms = listened_ms + excluded.listened_ms
+        "#,
+        user_id,
+        play_id,
+        song_id,
+        listened_ms
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Plays the user listened to, newest first
+pub async fn find_history(pool: &SqlitePool, user_id: i64, before: Option<i64>, limit: i64) -> Result<Vec<ListenEntry>, String> {
+    let before = before.unwrap_or(i64::MAX);
+
+    sqlx::query_as!(
+        ListenEntry,
+        r#"
+        SELECT
+            p.id as "play_id!",
+            s.id as "song_id!",
+            s.title,
+            (SELECT GROUP_CONCAT(a.name, ', ') FROM song_artists sa JOIN artists a ON sa.artist_id = a.id WHERE sa.song_id = s.id) as "artist_names?: String",
+            p.started_at as "started_at: DateTime<Utc>",
+            p.duration_ms,
+            l.listened_ms
+        FROM listen_log l
+        JOIN play_history p ON l.play_id = p.id
+        JOIN songs s ON l.song_id = s.id
+        WHERE l.user_id = ? AND p.id < ?
+        ORDER BY p.id DESC
+        LIMIT ?
+        "#,
+        user_id,
+        before,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn find_totals(
+    pool: &SqlitePool,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<ListenTotals, String> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(l.listened_ms), 0) as "listened_ms!: i64",
+            COUNT(DISTINCT l.song_id) as "songs_heard!: i64",
+            (
+                SELECT COUNT(DISTINCT sa.artist_id)
+                FROM listen_log l2
+                JOIN play_history p2 ON l2.play_id = p2.id
+                JOIN song_artists sa ON l2.song_id = sa.song_id
+                WHERE l2.user_id = ?1
+                    AND (?2 IS NULL OR p2.started_at >= ?2)
+                    AND (?3 IS NULL OR p2.started_at < ?3)
+            ) as "artists_heard!: i64"
+        FROM listen_log l
+        JOIN play_history p ON l.play_id = p.id
+        WHERE l.user_id = ?1
+            AND (?2 IS NULL OR p.started_at >= ?2)
+            AND (?3 IS NULL OR p.started_at < ?3)
+        "#,
+        user_id,
+        since,
+        until
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(ListenTotals {
+        listened_ms: row.listened_ms,
+        songs_heard: row.songs_heard,
+        artists_heard: row.artists_heard,
+    })
+}
+
+pub async fn find_top_songs(
+    pool: &SqlitePool,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<Vec<TopSong>, String> {
+    sqlx::query_as!(
+        TopSong,
+        r#"
+        SELECT
+            s.id as "song_id!",
+            s.title,
+            (SELECT GROUP_CONCAT(a.name, ', ') FROM song_artists sa JOIN artists a ON sa.artist_id = a.id WHERE sa.song_id = s.id) as "artist_names?: String",
+            SUM(l.listened_ms) as "listened_ms!: i64",
+            COUNT(*) as "plays!: i64"
+        FROM listen_log l
+        JOIN play_history p ON l.play_id = p.id
+        JOIN songs s ON l.song_id = s.id
+        WHERE l.user_id = ?1
+            AND (?2 IS NULL OR p.started_at >= ?2)
+            AND (?3 IS NULL OR p.started_at < ?3)
+        GROUP BY s.id
+        ORDER BY 4 DESC
+        LIMIT ?4
+        "#,
+        user_id,
+        since,
+        until,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn find_top_artists(
+    pool: &SqlitePool,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<Vec<TopArtist>, String> {
+    sqlx::query_as!(
+        TopArtist,
+        r#"
+        SELECT
+            a.id as "artist_id!",
+            a.name,
+            SUM(l.listened_ms) as "listened_ms!: i64"
+        FROM listen_log l
+        JOIN play_history p ON l.play_id = p.id
+        JOIN song_artists sa ON l.song_id = sa.song_id
+        JOIN artists a ON sa.artist_id = a.id
+        WHERE l.user_id = ?1
+            AND (?2 IS NULL OR p.started_at >= ?2)
+            AND (?3 IS NULL OR p.started_at < ?3)
+        GROUP BY a.id
+        ORDER BY 3 DESC
+        LIMIT ?4
+        "#,
+        user_id,
+        since,
+        until,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Tags ranked by listening time weighted with the song's tag score
+pub async fn find_top_tags(
+    pool: &SqlitePool,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<Vec<TopTag>, String> {
+    sqlx::query_as!(
+        TopTag,
+        r#"
+        SELECT
+            t.id as "tag_id!",
+            t.name,
+            CAST(SUM(l.listened_ms * COALESCE(st.score, 1.0)) AS INTEGER) as "listened_ms!: i64"
+        FROM listen_log l
+        JOIN play_history p ON l.play_id = p.id
+        JOIN song_tags st ON l.song_id = st.song_id
+        JOIN tags t ON st.tag_id = t.id
+        WHERE l.user_id = ?1
+            AND (?2 IS NULL OR p.started_at >= ?2)
+            AND (?3 IS NULL OR p.started_at < ?3)
+        GROUP BY t.id
+        ORDER BY 3 DESC
+        LIMIT ?4
+        "#,
+        user_id,
+        since,
+        until,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// The calendar month the user listened the most in, within the range
+pub async fn find_busiest_month(
+    pool: &SqlitePool,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<Option<MonthListen>, String> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            substr(p.started_at, 1, 7) as "month!: String",
+            SUM(l.listened_ms) as "listened_ms!: i64"
+        FROM listen_log l
+        JOIN play_history p ON l.play_id = p.id
+        WHERE l.user_id = ?1
+            AND (?2 IS NULL OR p.started_at >= ?2)
+            AND (?3 IS NULL OR p.started_at < ?3)
+        GROUP BY 1
+        ORDER BY 2 DESC
+        LIMIT 1
+        "#,
+        user_id,
+        since,
+        until
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(row.map(|r| MonthListen { month: r.month, listened_ms: r.listened_ms }))
+}
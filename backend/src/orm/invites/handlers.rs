@@ -0,0 +1,90 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{CreateInviteDto, CreatedInviteDto, Invite};
+use super::repository;
+use crate::auth::{hash_token, new_secret_token, perm, Require};
+use crate::config::{INVITE_DEFAULT_DAYS, INVITE_MAX_DAYS, INVITE_MAX_USES};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+
+// Characters of the code shown in listings
+const SHOWN_PREFIX_LENGTH: usize = 6;
+
+pub async fn list_invites(
+    State(state): State<AppState>,
+    _: Require<perm::ManageUsers>,
+) -> Result<Json<Vec<Invite>>, AppError> {
+    let invites = repository::find_all(&state.db)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(invites))
+}
+
+pub async fn create_invite(
+    State(state): State<AppState>,
+    Require(admin, _): Require<perm::ManageUsers>,
+    Json(payload): Json<CreateInviteDto>,
+) -> Result<(StatusCode, Json<CreatedInviteDto>), AppError> {
+    let mut errors = Vec::new();
+
+    let note = payload.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > 200) {
+        errors.push("note must be at most 200 characters".to_string());
+    }
+
+    let max_uses = payload.max_uses.unwrap_or(1);
+    if !(1..=INVITE_MAX_USES).contains(&max_uses) {
+        errors.push(format!("max_uses must be between 1 and {}", INVITE_MAX_USES));
+    }
+
+    let days = payload.expires_in_days.unwrap_or(INVITE_DEFAULT_DAYS);
+    if !(1..=INVITE_MAX_DAYS).contains(&days) {
+        errors.push(format!("expires_in_days must be between 1 and {}", INVITE_MAX_DAYS));
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::ValidationFailed(errors));
+    }
+
+    let code = new_secret_token();
+    let expires_at = Utc::now() + Duration::days(days);
+
+    let invite = repository::create(
+        &state.db,
+        &hash_token(&code),
+        &code[..SHOWN_PREFIX_LENGTH],
+        note,
+        admin.id,
+        max_uses,
+        expires_at,
+    )
+    .await
+    .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &admin, AuditAction::Create, AuditEntity::Invite, Some(invite.id), audit::NOTHING, Some(&invite)).await;
+
+    Ok((StatusCode::CREATED, Json(CreatedInviteDto { code, info: invite })))
+}
+
+pub async fn delete_invite(
+    State(state): State<AppState>,
+    Require(admin, _): Require<perm::ManageUsers>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Invite not found".to_string()))?;
+
+    repository::delete(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &admin, AuditAction::Delete, AuditEntity::Invite, Some(id), Some(&before), audit::NOTHING).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,14 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::{delete, get};
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/invites", get(handlers::list_invites).post(handlers::create_invite))
+        .route("/admin/invites/{id}", delete(handlers::delete_invite))
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Invite {
+    pub id: i64,
+    pub code_prefix: String,
+    pub note: Option<String>,
+    pub created_by: Option<i64>,
+    pub max_uses: i64,
+    pub uses: i64,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInviteDto {
+    pub note: Option<String>,
+    pub max_uses: Option<i64>,
+    pub expires_in_days: Option<i64>,
+}
+
+/// A freshly created invite, the only time its code is shown
+#[derive(Debug, Serialize)]
+pub struct CreatedInviteDto {
+    pub code: String,
+    #[serde(flatten)]
+    pub info: Invite,
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use super::models::Invite;
+
+pub async fn create(
+    pool: &SqlitePool,
+    code_hash: &str,
+    code_prefix: &str,
+    note: Option<&str>,
+    created_by: i64,
+    max_uses: i64,
+    expires_at: DateTime<Utc>,
+) -> Result<Invite, String> {
+    let now = Utc::now();
+
+    sqlx::query_as!(
+        Invite,
+        r#"
+        INSERT INTO invites (code_hash, code_prefix, note, created_by, max_uses, created_at, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id as "id!",
+            code_prefix,
+            note,
+            created_by,
+            max_uses,
+            uses,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>"
+        "#,
+        code_hash,
+        code_prefix,
+        note,
+        created_by,
+        max_uses,
+        now,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Every invite, newest first, used up and expired ones included
+pub async fn find_all(pool: &SqlitePool) -> Result<Vec<Invite>, String> {
+    sqlx::query_as!(
+        Invite,
+        r#"
+        SELECT
+            id as "id!",
+            code_prefix,
+            note,
+            created_by,
+            max_uses,
+            uses,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>"
+        FROM invites
+        ORDER BY id DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn find_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Invite>, String> {
+    sqlx::query_as!(
+        Invite,
+        r#"
+        SELECT
+            id as "id!",
+            code_prefix,
+            note,
+            created_by,
+            max_uses,
+            uses,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>"
+        FROM invites
+        WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Takes one use of a valid invite, returns its id. In one statement so concurrent
+/// registrations can't use it more often than allowed.
+pub async fn redeem(pool: &SqlitePool, code_hash: &str) -> Result<Option<i64>, String> {
+    let now = Utc::now();
+
+    sqlx::query_scalar!(
+        r#"
+        UPDATE invites SET uses = uses + 1
+        WHERE code_hash = ? AND uses < max_uses AND expires_at > ?
+        RETURNING id as "id!"
+        "#,
+        code_hash,
+        now
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Gives back a use taken by `redeem` when the registration failed after all
+pub async fn release(pool: &SqlitePool, id: i64) -> Result<(), String> {
+    sqlx::query!("UPDATE invites SET uses = uses - 1 WHERE id = ? AND uses > 0", id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub async fn delete(pool: &SqlitePool, id: i64) -> Result<bool, String> {
+    let result = sqlx::query!("DELETE FROM invites WHERE id = ?", id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{LeaderboardDto, LeaderboardEntry, LeaderboardMetric, LeaderboardQuery, LeaderboardRow, ListeningDay};
+use super::repository;
+use crate::config::{LEADERBOARD_DEFAULT_LIMIT, LEADERBOARD_MAX_LIMIT};
+
+pub async fn get_leaderboard(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<LeaderboardDto>, AppError> {
+    let limit = query.limit.unwrap_or(LEADERBOARD_DEFAULT_LIMIT).clamp(1, LEADERBOARD_MAX_LIMIT);
+    let since = query.period.since();
+
+    let rows = match query.metric {
+        LeaderboardMetric::ListenTime => repository::find_top_listen_time(&state.db, since, limit).await,
+        LeaderboardMetric::SongsRequested => repository::find_top_requesters(&state.db, since, limit).await,
+        LeaderboardMetric::Streak => repository::find_listening_days(&state.db)
+            .await
+            .map(|days| current_streaks(days, limit)),
+    }
+    .map_err(AppError::InternalServerError)?;
+
+    let entries = rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| LeaderboardEntry {
+            rank: index + 1,
+            user_id: row.user_id,
+            username: row.username,
+            value: row.value,
+        })
+        .collect();
+
+    Ok(Json(LeaderboardDto {
+        metric: query.metric,
+        period: query.period,
+        entries,
+    }))
+}
+
+/// Length of each user's run of consecutive listening days. A run still counts
+/// when the user hasn't listened yet today.
+fn current_streaks(days: Vec<ListeningDay>, limit: i64) -> Vec<LeaderboardRow> {
+    let today = Utc::now().date_naive();
+    let mut rows: Vec<LeaderboardRow> = Vec::new();
+    let mut expected: Option<NaiveDate> = None;
+
+    // Days arrive grouped by user, newest first
+    for day in days {
+        let Ok(date) = NaiveDate::parse_from_str(&day.day, "%Y-%m-%d") else {
+            continue;
+        };
+
+        if rows.last().is_none_or(|row| row.user_id != day.user_id) {
+            let alive = date == today || date == today - Duration::days(1);
+            rows.push(LeaderboardRow { user_id: day.user_id, username: day.username, value: 0 });
+            if !alive {
+                expected = None;
+                continue;
+            }
+            expected = Some(date);
+        }
+
+        if expected == Some(date)
+            && let Some(row) = rows.last_mut()
+        {
+            row.value += 1;
+            expected = Some(date - Duration::days(1));
+        } else {
+            expected = None;
+        }
+    }
+
+    rows.retain(|row| row.value > 0);
+    rows.sort_by_key(|row| std::cmp::Reverse(row.value));
+    rows.truncate(limit as usize);
+    rows
+}
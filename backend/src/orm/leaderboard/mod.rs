@@ -0,0 +1,13 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::get;
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/users/leaderboard", get(handlers::get_leaderboard))
+}
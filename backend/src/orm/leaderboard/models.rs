@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use crate::orm::history::models::StatsPeriod;
+
+/// What users are ranked by. There is no comparisons voted metric, Wavy has no comparisons to vote on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardMetric {
+    /// Seconds listened
+    #[default]
+    ListenTime,
+    /// Songs requested through the WebSocket
+    SongsRequested,
+    /// Consecutive days listened up to today, ignores the period
+    Streak,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    #[serde(default)]
+    pub metric: LeaderboardMetric,
+    #[serde(default)]
+    pub period: StatsPeriod,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct LeaderboardRow {
+    pub user_id: i64,
+    pub username: String,
+    pub value: i64,
+}
+
+/// A day a user listened on
+#[derive(Debug, FromRow)]
+pub struct ListeningDay {
+    pub user_id: i64,
+    pub username: String,
+    /// `YYYY-MM-DD`
+    pub day: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub user_id: i64,
+    pub username: String,
+    pub value: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardDto {
+    pub metric: LeaderboardMetric,
+    pub period: StatsPeriod,
+    pub entries: Vec<LeaderboardEntry>,
+}
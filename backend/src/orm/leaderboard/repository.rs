@@ -0,0 +1,112 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use sqlx::SqlitePool;
+use super::models::{LeaderboardRow, ListeningDay};
+
+/// Adds listening time to the hour `at` falls in
+pub async fn log_listen_hour(pool: &SqlitePool, user_id: i64, at: DateTime<Utc>, listened_ms: i64) -> Result<(), String> {
+    let hour_start = at.duration_trunc(TimeDelta::hours(1)).map_err(|e| e.to_string())?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO listen_time_hourly (user_id, hour_start, listened_ms)
+        VALUES (?, ?, ?)
+        ON CONFLICT(user_id, hour_start) DO UPDATE SET
+            listened_ms = listened_ms + excluded.listened_ms
+        "#,
+        user_id,
+        hour_start,
+        listened_ms
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub async fn log_request(pool: &SqlitePool, user_id: i64, song_id: i64, requested_at: DateTime<Utc>) -> Result<(), String> {
+    sqlx::query!(
+        "INSERT INTO song_request_log (user_id, song_id, requested_at) VALUES (?, ?, ?)",
+        user_id,
+        song_id,
+        requested_at
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Seconds listened since `since`, counted by the hour
+pub async fn find_top_listen_time(pool: &SqlitePool, since: Option<DateTime<Utc>>, limit: i64) -> Result<Vec<LeaderboardRow>, String> {
+    // The hour `since` falls in is counted whole
+    let since = since
+        .map(|since| since.duration_trunc(TimeDelta::hours(1)))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
+    sqlx::query_as!(
+        LeaderboardRow,
+        r#"
+        SELECT
+            u.id as "user_id!",
+            u.username,
+            SUM(h.listened_ms) / 1000 as "value!: i64"
+        FROM listen_time_hourly h
+        JOIN users u ON h.user_id = u.id
+        WHERE u.leaderboard_opt_out = 0
+            AND (?1 IS NULL OR h.hour_start >= ?1)
+        GROUP BY u.id
+        HAVING SUM(h.listened_ms) >= 1000
+        ORDER BY 3 DESC
+        LIMIT ?2
+        "#,
+        since,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn find_top_requesters(pool: &SqlitePool, since: Option<DateTime<Utc>>, limit: i64) -> Result<Vec<LeaderboardRow>, String> {
+    sqlx::query_as!(
+        LeaderboardRow,
+        r#"
+        SELECT
+            u.id as "user_id!",
+            u.username,
+            COUNT(*) as "value!: i64"
+        FROM song_request_log r
+        JOIN users u ON r.user_id = u.id
+        WHERE u.leaderboard_opt_out = 0
+            AND (?1 IS NULL OR r.requested_at >= ?1)
+        GROUP BY u.id
+        ORDER BY 3 DESC
+        LIMIT ?2
+        "#,
+        since,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Every day each listed user listened on, newest first per user
+pub async fn find_listening_days(pool: &SqlitePool) -> Result<Vec<ListeningDay>, String> {
+    sqlx::query_as!(
+        ListeningDay,
+        r#"
+        SELECT DISTINCT h.user_id as "user_id!", u.username as "username!", substr(h.hour_start, 1, 10) as "day!: String"
+        FROM listen_time_hourly h
+        JOIN users u ON h.user_id = u.id
+        WHERE u.leaderboard_opt_out = 0 AND h.listened_ms > 0
+        ORDER BY 1, 3 DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
@@ -2,4 +2,26 @@ pub mod users;
 pub mod artists;
 pub mod songs;
 pub mod albums;
-pub mod tags;
\ No newline at end of file
+pub mod tags;
+pub mod chat;
+pub mod ratings;
+pub mod history;
+pub mod leaderboard;
+pub mod sessions;
+pub mod tokens;
+pub mod audit;
+pub mod invites;
+pub mod playlists;
+
+// Distinguishes a missing field from an explicit null
+pub mod double_option {
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::Write;
+
+use super::models::{Playlist, PlaylistEntry};
+
+/// Where a song is found, relative to the music directory
+fn location(entry: &PlaylistEntry) -> String {
+    format!("{}.mp3", entry.song_id)
+}
+
+/// "Artist - Title", or just the title
+fn display_name(entry: &PlaylistEntry) -> String {
+    match &entry.artist_names {
+        Some(artists) => format!("{} - {}", artists, entry.title),
+        None => entry.title.clone(),
+    }
+}
+
+/// Extended M3U, one `#EXTINF` line per song
+pub fn to_m3u(playlist: &Playlist, entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    // Line breaks would end the directive early
+    let _ = writeln!(out, "#PLAYLIST:{}", playlist.name.replace(['\r', '\n'], " "));
+
+    for entry in entries {
+        let seconds = entry.duration_ms.map_or(-1, |ms| ms / 1000);
+        let _ = writeln!(out, "#EXTINF:{},{}", seconds, display_name(entry).replace(['\r', '\n'], " "));
+        let _ = writeln!(out, "{}", location(entry));
+    }
+
+    out
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Not allowed in XML 1.0 at all
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// XSPF 1 (https://xspf.org)
+pub fn to_xspf(playlist: &Playlist, entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    let _ = writeln!(out, "  <title>{}</title>", escape_xml(&playlist.name));
+    let _ = writeln!(out, "  <creator>{}</creator>", escape_xml(&playlist.owner_name));
+    if let Some(description) = &playlist.description {
+        let _ = writeln!(out, "  <annotation>{}</annotation>", escape_xml(description));
+    }
+    let _ = writeln!(out, "  <date>{}</date>", playlist.updated_at.to_rfc3339());
+
+    out.push_str("  <trackList>\n");
+    for entry in entries {
+        out.push_str("    <track>\n");
+        let _ = writeln!(out, "      <location>{}</location>", escape_xml(&location(entry)));
+        let _ = writeln!(out, "      <title>{}</title>", escape_xml(&entry.title));
+        if let Some(artists) = &entry.artist_names {
+            let _ = writeln!(out, "      <creator>{}</creator>", escape_xml(artists));
+        }
+        if let Some(album) = &entry.album_title {
+            let _ = writeln!(out, "      <album>{}</album>", escape_xml(album));
+        }
+        if let Some(duration_ms) = entry.duration_ms {
+            let _ = writeln!(out, "      <duration>{}</duration>", duration_ms);
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n");
+    out.push_str("</playlist>\n");
+
+    out
+}
+
+/// A download name made of the playlist's name, without anything a header or file system could choke on
+pub fn file_name(playlist: &Playlist, extension: &str) -> String {
+    let name: String = playlist.name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ' ') { c } else { '_' })
+        .collect();
+    let name = name.trim();
+
+    if name.is_empty() {
+        format!("playlist-{}.{}", playlist.id, extension)
+    } else {
+        format!("{}.{}", name, extension)
+    }
+}
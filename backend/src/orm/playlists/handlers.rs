@@ -0,0 +1,230 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::export;
+use super::models::{
+    AddPlaylistSongDto, CreatePlaylistDto, ExportFormat, ExportQuery, MovePlaylistSongDto, Playlist, PlaylistDetailDto,
+    UpdatePlaylistDto, VersionQuery,
+};
+use super::repository;
+use crate::auth::AuthUser;
+use crate::config::{MAX_PLAYLISTS_PER_USER, PLAYLIST_DESCRIPTION_MAX_LENGTH, PLAYLIST_NAME_MAX_LENGTH};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::orm::users::models::User;
+
+/// The playlist if the user may see it, hidden ones look missing
+async fn visible_playlist(state: &AppState, id: i64, user: &AuthUser) -> Result<Playlist, AppError> {
+    repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .filter(|p| p.can_view(user))
+        .ok_or(AppError::NotFound("Playlist not found".to_string()))
+}
+
+async fn detail(state: &AppState, id: i64) -> Result<PlaylistDetailDto, AppError> {
+    let playlist = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Playlist not found".to_string()))?;
+    let songs = repository::find_entries(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(PlaylistDetailDto { playlist, songs })
+}
+
+/// Checks the trimmed name and description
+fn validate(name: Option<&str>, description: Option<&str>) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    if let Some(name) = name {
+        if name.is_empty() {
+            errors.push("name can't be empty".to_string());
+        } else if name.chars().count() > PLAYLIST_NAME_MAX_LENGTH {
+            errors.push(format!("name must be at most {} characters", PLAYLIST_NAME_MAX_LENGTH));
+        }
+    }
+    if description.is_some_and(|d| d.chars().count() > PLAYLIST_DESCRIPTION_MAX_LENGTH) {
+        errors.push(format!("description must be at most {} characters", PLAYLIST_DESCRIPTION_MAX_LENGTH));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(AppError::ValidationFailed(errors)) }
+}
+
+/// Records a change to a playlist, whoever made it
+async fn record_change(state: &AppState, actor: &User, action: AuditAction, before: &Playlist) {
+    let after = repository::find_by_id(&state.db, before.id).await.ok().flatten();
+    audit::record(&state.db, actor, action, AuditEntity::Playlist, Some(before.id), Some(before), after.as_ref()).await;
+}
+
+pub async fn list_playlists(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<Playlist>>, AppError> {
+    let playlists = repository::find_visible(&state.db, user.0.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(playlists))
+}
+
+pub async fn create_playlist(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreatePlaylistDto>,
+) -> Result<(StatusCode, Json<Playlist>), AppError> {
+    let name = payload.name.trim();
+    let description = payload.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+    validate(Some(name), description)?;
+
+    let owned = repository::count_by_owner(&state.db, user.0.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    if owned >= MAX_PLAYLISTS_PER_USER {
+        return Err(AppError::CustomForbidden(format!("You can have at most {} playlists", MAX_PLAYLISTS_PER_USER)));
+    }
+
+    let playlist = repository::create(&state.db, user.0.id, name, description, payload.visibility.unwrap_or_default())
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &user.0, AuditAction::Create, AuditEntity::Playlist, Some(playlist.id), audit::NOTHING, Some(&playlist)).await;
+
+    Ok((StatusCode::CREATED, Json(playlist)))
+}
+
+pub async fn get_playlist(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<PlaylistDetailDto>, AppError> {
+    visible_playlist(&state, id, &user).await?;
+    Ok(Json(detail(&state, id).await?))
+}
+
+pub async fn update_playlist(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdatePlaylistDto>,
+) -> Result<Json<PlaylistDetailDto>, AppError> {
+    let before = visible_playlist(&state, id, &user).await?;
+    if !before.can_manage(&user) {
+        return Err(AppError::CustomForbidden("Only the owner can change this playlist".to_string()));
+    }
+
+    let name = payload.name.as_deref().map(str::trim);
+    let description = payload.description.as_deref().map(|d| Some(d.trim()).filter(|d| !d.is_empty()));
+    validate(name, description.flatten())?;
+
+    repository::update(&state.db, id, name, description, payload.visibility, payload.version).await?;
+    record_change(&state, &user.0, AuditAction::Update, &before).await;
+
+    Ok(Json(detail(&state, id).await?))
+}
+
+pub async fn delete_playlist(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let before = visible_playlist(&state, id, &user).await?;
+    if !before.can_manage(&user) {
+        return Err(AppError::CustomForbidden("Only the owner can delete this playlist".to_string()));
+    }
+
+    repository::delete(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    record_change(&state, &user.0, AuditAction::Delete, &before).await;
+
+    // The station goes back to the whole library
+    let mut station_guard = state.station.write().await;
+    if station_guard.settings.playlist_id == Some(id) {
+        station_guard.settings.playlist_id = None;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The playlist if the user may change its songs
+async fn editable_playlist(state: &AppState, id: i64, user: &AuthUser) -> Result<Playlist, AppError> {
+    let playlist = visible_playlist(state, id, user).await?;
+    if !playlist.can_edit_songs(user) {
+        return Err(AppError::CustomForbidden("Only the owner can change the songs of this playlist".to_string()));
+    }
+    Ok(playlist)
+}
+
+pub async fn add_song(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<AddPlaylistSongDto>,
+) -> Result<(StatusCode, Json<PlaylistDetailDto>), AppError> {
+    let before = editable_playlist(&state, id, &user).await?;
+
+    crate::orm::songs::repository::find_by_id(&state.db, payload.song_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
+    repository::insert_song(&state.db, id, payload.song_id, payload.position, user.0.id, payload.version).await?;
+    record_change(&state, &user.0, AuditAction::Update, &before).await;
+
+    Ok((StatusCode::CREATED, Json(detail(&state, id).await?)))
+}
+
+pub async fn move_song(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((id, entry_id)): Path<(i64, i64)>,
+    Json(payload): Json<MovePlaylistSongDto>,
+) -> Result<Json<PlaylistDetailDto>, AppError> {
+    let before = editable_playlist(&state, id, &user).await?;
+
+    repository::move_song(&state.db, id, entry_id, payload.position, payload.version).await?;
+    record_change(&state, &user.0, AuditAction::Update, &before).await;
+
+    Ok(Json(detail(&state, id).await?))
+}
+
+pub async fn remove_song(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((id, entry_id)): Path<(i64, i64)>,
+    Query(query): Query<VersionQuery>,
+) -> Result<Json<PlaylistDetailDto>, AppError> {
+    let before = editable_playlist(&state, id, &user).await?;
+
+    repository::remove_song(&state.db, id, entry_id, query.version).await?;
+    record_change(&state, &user.0, AuditAction::Update, &before).await;
+
+    Ok(Json(detail(&state, id).await?))
+}
+
+pub async fn export_playlist(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let playlist = visible_playlist(&state, id, &user).await?;
+    let entries = repository::find_entries(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    let (body, content_type, extension) = match query.format {
+        ExportFormat::M3u => (export::to_m3u(&playlist, &entries), "audio/x-mpegurl; charset=utf-8", "m3u8"),
+        ExportFormat::Xspf => (export::to_xspf(&playlist, &entries), "application/xspf+xml; charset=utf-8", "xspf"),
+    };
+    let disposition = format!("attachment; filename=\"{}\"", export::file_name(&playlist, extension));
+
+    Ok((
+        [(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        body,
+    ))
+}
@@ -0,0 +1,21 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+pub mod export;
+
+use axum::Router;
+use axum::routing::{get, post};
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/playlists", get(handlers::list_playlists).post(handlers::create_playlist))
+        .route(
+            "/playlists/{id}",
+            get(handlers::get_playlist).post(handlers::update_playlist).delete(handlers::delete_playlist),
+        )
+        .route("/playlists/{id}/songs", post(handlers::add_song))
+        .route("/playlists/{id}/songs/{entry_id}", post(handlers::move_song).delete(handlers::remove_song))
+        .route("/playlists/{id}/export", get(handlers::export_playlist))
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::auth::AuthUser;
+use crate::error::AppError;
+use crate::orm::users::models::Permission;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum PlaylistVisibility {
+    /// Only the owner sees it
+    #[default]
+    Private,
+    /// Everyone sees it, only the owner edits it
+    Public,
+    /// Everyone sees it and can add, move and remove songs
+    Collaborative,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Playlist {
+    pub id: i64,
+    pub owner_id: i64,
+    pub owner_name: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: PlaylistVisibility,
+    /// Bumped by every change, send it back with edits to detect concurrent ones
+    pub version: i64,
+    pub song_count: i64,
+    pub duration_ms: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Playlist {
+    /// Users who can manage every playlist count as owners
+    fn is_owned_by(&self, user: &AuthUser) -> bool {
+        self.owner_id == user.0.id || user.can(Permission::ManagePlaylists)
+    }
+
+    pub fn can_view(&self, user: &AuthUser) -> bool {
+        self.visibility != PlaylistVisibility::Private || self.is_owned_by(user)
+    }
+
+    /// Adding, moving and removing songs
+    pub fn can_edit_songs(&self, user: &AuthUser) -> bool {
+        self.visibility == PlaylistVisibility::Collaborative || self.is_owned_by(user)
+    }
+
+    /// Renaming, changing the visibility and deleting
+    pub fn can_manage(&self, user: &AuthUser) -> bool {
+        self.is_owned_by(user)
+    }
+}
+
+/// A song at a position of a playlist
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PlaylistEntry {
+    /// Names the entry in edits, the same song can be in a playlist more than once
+    pub id: i64,
+    pub position: i64,
+    pub song_id: i64,
+    pub title: String,
+    pub artist_names: Option<String>,
+    pub album_title: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub added_by: Option<i64>,
+    pub added_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistDetailDto {
+    #[serde(flatten)]
+    pub playlist: Playlist,
+    pub songs: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePlaylistDto {
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: Option<PlaylistVisibility>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePlaylistDto {
+    pub name: Option<String>,
+    /// An empty description clears it
+    pub description: Option<String>,
+    pub visibility: Option<PlaylistVisibility>,
+    /// The version the edit was based on, checked when given
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddPlaylistSongDto {
+    pub song_id: i64,
+    /// Where to insert it, at the end when omitted
+    pub position: Option<i64>,
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MovePlaylistSongDto {
+    pub position: i64,
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionQuery {
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    M3u,
+    Xspf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+}
+
+/// Why a change to a playlist wasn't made
+#[derive(Debug)]
+pub enum PlaylistEditError {
+    /// Someone else changed the playlist since the version the edit was based on
+    VersionMismatch,
+    EntryNotFound,
+    Full,
+    Database(String),
+}
+
+impl From<sqlx::Error> for PlaylistEditError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err.to_string())
+    }
+}
+
+impl From<PlaylistEditError> for AppError {
+    fn from(err: PlaylistEditError) -> Self {
+        match err {
+            PlaylistEditError::VersionMismatch => {
+                AppError::Conflict("The playlist was changed by someone else, reload it and try again".to_string())
+            }
+            PlaylistEditError::EntryNotFound => AppError::NotFound("The song is not in the playlist".to_string()),
+            PlaylistEditError::Full => {
+                AppError::BadRequest(format!("A playlist can hold at most {} songs", crate::config::PLAYLIST_MAX_SONGS))
+            }
+            PlaylistEditError::Database(e) => AppError::InternalServerError(e),
+        }
+    }
+}
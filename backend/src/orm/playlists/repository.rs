@@ -0,0 +1,387 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use super::models::{Playlist, PlaylistEditError, PlaylistEntry, PlaylistVisibility};
+use crate::config::PLAYLIST_MAX_SONGS;
+use crate::orm::songs::models::Song;
+
+pub async fn create(
+    pool: &SqlitePool,
+    owner_id: i64,
+    name: &str,
+    description: Option<&str>,
+    visibility: PlaylistVisibility,
+) -> Result<Playlist, String> {
+    let now = Utc::now();
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO playlists (owner_id, name, description, visibility, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+        owner_id,
+        name,
+        description,
+        visibility,
+        now,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .last_insert_rowid();
+
+    find_by_id(pool, id)
+        .await?
+        .ok_or("Playlist not found after creation".to_string())
+}
+
+pub async fn find_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Playlist>, String> {
+    sqlx::query_as!(
+        Playlist,
+        r#"
+        SELECT
+            p.id as "id!",
+            p.owner_id,
+            u.username as owner_name,
+            p.name,
+            p.description,
+            p.visibility as "visibility: PlaylistVisibility",
+            p.version,
+            (SELECT COUNT(*) FROM playlist_songs ps WHERE ps.playlist_id = p.id) as "song_count!: i64",
+            (SELECT COALESCE(SUM(s.duration_ms), 0) FROM playlist_songs ps JOIN songs s ON s.id = ps.song_id
+                WHERE ps.playlist_id = p.id) as "duration_ms!: i64",
+            p.created_at as "created_at: DateTime<Utc>",
+            p.updated_at as "updated_at: DateTime<Utc>"
+        FROM playlists p
+        JOIN users u ON u.id = p.owner_id
+        WHERE p.id = ?
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// The user's own playlists and everyone's public and collaborative ones, last changed first
+pub async fn find_visible(pool: &SqlitePool, user_id: i64) -> Result<Vec<Playlist>, String> {
+    sqlx::query_as!(
+        Playlist,
+        r#"
+        SELECT
+            p.id as "id!",
+            p.owner_id,
+            u.username as owner_name,
+            p.name,
+            p.description,
+            p.visibility as "visibility: PlaylistVisibility",
+            p.version,
+            (SELECT COUNT(*) FROM playlist_songs ps WHERE ps.playlist_id = p.id) as "song_count!: i64",
+            (SELECT COALESCE(SUM(s.duration_ms), 0) FROM playlist_songs ps JOIN songs s ON s.id = ps.song_id
+                WHERE ps.playlist_id = p.id) as "duration_ms!: i64",
+            p.created_at as "created_at: DateTime<Utc>",
+            p.updated_at as "updated_at: DateTime<Utc>"
+        FROM playlists p
+        JOIN users u ON u.id = p.owner_id
+        WHERE p.owner_id = ? OR p.visibility != 'private'
+        ORDER BY p.updated_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn count_by_owner(pool: &SqlitePool, owner_id: i64) -> Result<i64, String> {
+    sqlx::query_scalar!("SELECT COUNT(*) FROM playlists WHERE owner_id = ?", owner_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Changes the fields that are `Some`. `description` is `Some(None)` to clear it.
+pub async fn update(
+    pool: &SqlitePool,
+    id: i64,
+    name: Option<&str>,
+    description: Option<Option<&str>>,
+    visibility: Option<PlaylistVisibility>,
+    version: Option<i64>,
+) -> Result<(), PlaylistEditError> {
+    let now = Utc::now();
+    let set_description = description.is_some();
+    let description = description.flatten();
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE playlists SET
+            name = COALESCE(?1, name),
+            description = CASE WHEN ?2 THEN ?3 ELSE description END,
+            visibility = COALESCE(?4, visibility),
+            version = version + 1,
+            updated_at = ?5
+        WHERE id = ?6 AND (?7 IS NULL OR version = ?7)
+        "#,
+        name,
+        set_description,
+        description,
+        visibility,
+        now,
+        id,
+        version
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(PlaylistEditError::VersionMismatch);
+    }
+    Ok(())
+}
+
+pub async fn delete(pool: &SqlitePool, id: i64) -> Result<(), String> {
+    sqlx::query!("DELETE FROM playlists WHERE id = ?", id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub async fn find_entries(pool: &SqlitePool, playlist_id: i64) -> Result<Vec<PlaylistEntry>, String> {
+    sqlx::query_as!(
+        PlaylistEntry,
+        r#"
+        SELECT
+            ps.id as "id!",
+            ps.position,
+            ps.song_id,
+            s.title,
+            GROUP_CONCAT(a.name, ', ') as "artist_names?: String",
+            al.title as album_title,
+            s.duration_ms,
+            ps.added_by,
+            ps.added_at as "added_at: DateTime<Utc>"
+        FROM playlist_songs ps
+        JOIN songs s ON s.id = ps.song_id
+        LEFT JOIN song_artists sa ON s.id = sa.song_id
+        LEFT JOIN artists a ON sa.artist_id = a.id
+        LEFT JOIN albums al ON s.album_id = al.id
+        WHERE ps.playlist_id = ?
+        GROUP BY ps.id
+        ORDER BY ps.position, ps.id
+        "#,
+        playlist_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// The playlist's songs in order, for the station loader
+pub async fn find_songs(pool: &SqlitePool, playlist_id: i64) -> Result<Vec<Song>, String> {
+    sqlx::query_as!(
+        Song,
+        r#"
+        SELECT
+            s.id as "id!",
+            s.title,
+            s.album_id,
+            al.title as album_title,
+            GROUP_CONCAT(a.name, ', ') as "artist_names?: String",
+            s.duration_ms,
+            s.loudness_db,
+            s.bpm,
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = 1) as "likes!: i64",
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = -1) as "dislikes!: i64"
+        FROM playlist_songs ps
+        JOIN songs s ON s.id = ps.song_id
+        LEFT JOIN song_artists sa ON s.id = sa.song_id
+        LEFT JOIN artists a ON sa.artist_id = a.id
+        LEFT JOIN albums al ON s.album_id = al.id
+        WHERE ps.playlist_id = ?
+        GROUP BY ps.id
+        ORDER BY ps.position, ps.id
+        "#,
+        playlist_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Starts a change to the playlist's songs: bumps the version, failing if it isn't `version` when given,
+/// and numbers the positions from 0 again. The bump takes SQLite's write lock, so concurrent edits
+/// run one after another and each sees the positions the previous one left.
+async fn begin_songs_edit(pool: &SqlitePool, playlist_id: i64, version: Option<i64>) -> Result<Transaction<'static, Sqlite>, PlaylistEditError> {
+    let mut tx = pool.begin().await?;
+    let now = Utc::now();
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE playlists SET version = version + 1, updated_at = ?1
+        WHERE id = ?2 AND (?3 IS NULL OR version = ?3)
+        "#,
+        now,
+        playlist_id,
+        version
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(PlaylistEditError::VersionMismatch);
+    }
+
+    // Deleted songs leave gaps
+    sqlx::query!(
+        r#"
+        UPDATE playlist_songs SET position = ranked.position
+        FROM (
+            SELECT id, ROW_NUMBER() OVER (ORDER BY position, id) - 1 as position
+            FROM playlist_songs
+            WHERE playlist_id = ?
+        ) as ranked
+        WHERE playlist_songs.id = ranked.id
+        "#,
+        playlist_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(tx)
+}
+
+async fn count_entries(tx: &mut Transaction<'static, Sqlite>, playlist_id: i64) -> Result<i64, PlaylistEditError> {
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM playlist_songs WHERE playlist_id = ?", playlist_id)
+        .fetch_one(&mut **tx)
+        .await?;
+    Ok(count)
+}
+
+/// Inserts the song at `position`, or at the end. Returns the new entry's id.
+pub async fn insert_song(
+    pool: &SqlitePool,
+    playlist_id: i64,
+    song_id: i64,
+    position: Option<i64>,
+    added_by: i64,
+    version: Option<i64>,
+) -> Result<i64, PlaylistEditError> {
+    let mut tx = begin_songs_edit(pool, playlist_id, version).await?;
+
+    let count = count_entries(&mut tx, playlist_id).await?;
+    if count >= PLAYLIST_MAX_SONGS {
+        return Err(PlaylistEditError::Full);
+    }
+    let position = position.map_or(count, |p| p.clamp(0, count));
+
+    sqlx::query!(
+        "UPDATE playlist_songs SET position = position + 1 WHERE playlist_id = ? AND position >= ?",
+        playlist_id,
+        position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let now = Utc::now();
+    let entry_id = sqlx::query!(
+        r#"
+        INSERT INTO playlist_songs (playlist_id, song_id, position, added_by, added_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        playlist_id,
+        song_id,
+        position,
+        added_by,
+        now
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    tx.commit().await?;
+    Ok(entry_id)
+}
+
+/// Moves an entry to `position`, shifting the ones in between
+pub async fn move_song(
+    pool: &SqlitePool,
+    playlist_id: i64,
+    entry_id: i64,
+    position: i64,
+    version: Option<i64>,
+) -> Result<(), PlaylistEditError> {
+    let mut tx = begin_songs_edit(pool, playlist_id, version).await?;
+
+    let current = sqlx::query_scalar!(
+        "SELECT position FROM playlist_songs WHERE id = ? AND playlist_id = ?",
+        entry_id,
+        playlist_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(PlaylistEditError::EntryNotFound)?;
+
+    let count = count_entries(&mut tx, playlist_id).await?;
+    let target = position.clamp(0, count - 1);
+
+    if target > current {
+        sqlx::query!(
+            "UPDATE playlist_songs SET position = position - 1 WHERE playlist_id = ? AND position > ? AND position <= ?",
+            playlist_id,
+            current,
+            target
+        )
+        .execute(&mut *tx)
+        .await?;
+    } else if target < current {
+        sqlx::query!(
+            "UPDATE playlist_songs SET position = position + 1 WHERE playlist_id = ? AND position >= ? AND position < ?",
+            playlist_id,
+            target,
+            current
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!("UPDATE playlist_songs SET position = ? WHERE id = ?", target, entry_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn remove_song(
+    pool: &SqlitePool,
+    playlist_id: i64,
+    entry_id: i64,
+    version: Option<i64>,
+) -> Result<(), PlaylistEditError> {
+    let mut tx = begin_songs_edit(pool, playlist_id, version).await?;
+
+    let removed = sqlx::query_scalar!(
+        "DELETE FROM playlist_songs WHERE id = ? AND playlist_id = ? RETURNING position",
+        entry_id,
+        playlist_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(PlaylistEditError::EntryNotFound)?;
+
+    sqlx::query!(
+        "UPDATE playlist_songs SET position = position - 1 WHERE playlist_id = ? AND position > ?",
+        playlist_id,
+        removed
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
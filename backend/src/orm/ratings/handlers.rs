@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{RateSongDto, SongRatingDto};
+use super::repository;
+use crate::auth::AuthUser;
+use crate::orm::songs::models::Song;
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+
+async fn ensure_song_exists(state: &AppState, song_id: i64) -> Result<(), AppError> {
+    crate::orm::songs::repository::find_by_id(&state.db, song_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+    Ok(())
+}
+
+pub async fn get_rating(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<SongRatingDto>, AppError> {
+    ensure_song_exists(&state, id).await?;
+
+    let rating = repository::find_for_song(&state.db, user.0.id, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(rating))
+}
+
+pub async fn rate_song(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<RateSongDto>,
+) -> Result<Json<SongRatingDto>, AppError> {
+    ensure_song_exists(&state, id).await?;
+
+    repository::rate(&state.db, user.0.id, id, payload.rating)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &user.0, AuditAction::Update, AuditEntity::SongRating, Some(id), audit::NOTHING, Some(&payload.rating)).await;
+
+    let rating = repository::find_for_song(&state.db, user.0.id, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(rating))
+}
+
+pub async fn rate_current_song(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<RateSongDto>,
+) -> Result<Json<SongRatingDto>, AppError> {
+    let song_id = state.station.read().await
+        .current_song
+        .as_ref()
+        .map(|s| s.id)
+        .ok_or(AppError::NotFound("Nothing is playing".to_string()))?;
+
+    rate_song(State(state), user, Path(song_id), Json(payload)).await
+}
+
+pub async fn clear_rating(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<SongRatingDto>, AppError> {
+    repository::clear(&state.db, user.0.id, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &user.0, AuditAction::Delete, AuditEntity::SongRating, Some(id), audit::NOTHING, audit::NOTHING).await;
+
+    let rating = repository::find_for_song(&state.db, user.0.id, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(rating))
+}
+
+pub async fn list_favourites(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<Song>>, AppError> {
+    let songs = repository::find_favourites(&state.db, user.0.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    Ok(Json(songs))
+}
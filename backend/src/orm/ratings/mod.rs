@@ -0,0 +1,15 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::{get, post};
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/songs/{id}/rating", get(handlers::get_rating).post(handlers::rate_song).delete(handlers::clear_rating))
+        .route("/song/current/rating", post(handlers::rate_current_song))
+        .route("/users/me/favourites", get(handlers::list_favourites))
+}
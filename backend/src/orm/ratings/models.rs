@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rating {
+    Like,
+    Dislike,
+}
+
+impl Rating {
+    /// Value stored in `song_ratings.rating`
+    pub fn to_db(self) -> i64 {
+        match self {
+            Rating::Like => 1,
+            Rating::Dislike => -1,
+        }
+    }
+
+    pub fn from_db(value: i64) -> Option<Self> {
+        match value {
+            1 => Some(Rating::Like),
+            -1 => Some(Rating::Dislike),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateSongDto {
+    pub rating: Rating,
+}
+
+/// A user's rating of a song next to the song's totals
+#[derive(Debug, Serialize)]
+pub struct SongRatingDto {
+    pub song_id: i64,
+    pub rating: Option<Rating>,
+    pub likes: i64,
+    pub dislikes: i64,
+}
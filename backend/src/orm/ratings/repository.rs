@@ -0,0 +1,95 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+use super::models::{Rating, SongRatingDto};
+use crate::orm::songs::models::Song;
+
+/// Sets the user's rating of a song, replacing the previous one
+pub async fn rate(pool: &SqlitePool, user_id: i64, song_id: i64, rating: Rating) -> Result<(), String> {
+    let value = rating.to_db();
+    let now = Utc::now();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO song_ratings (user_id, song_id, rating, rated_at)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(user_id, song_id) DO UPDATE SET
+            rating = excluded.rating,
+            rated_at = excluded.rated_at
+        "#,
+        user_id,
+        song_id,
+        value,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub async fn clear(pool: &SqlitePool, user_id: i64, song_id: i64) -> Result<(), String> {
+    sqlx::query!("DELETE FROM song_ratings WHERE user_id = ? AND song_id = ?", user_id, song_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub async fn find_for_song(pool: &SqlitePool, user_id: i64, song_id: i64) -> Result<SongRatingDto, String> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT rating FROM song_ratings WHERE user_id = ? AND song_id = ?) as "rating?: i64",
+            (SELECT COUNT(*) FROM song_ratings WHERE song_id = ? AND rating = 1) as "likes!: i64",
+            (SELECT COUNT(*) FROM song_ratings WHERE song_id = ? AND rating = -1) as "dislikes!: i64"
+        "#,
+        user_id,
+        song_id,
+        song_id,
+        song_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(SongRatingDto {
+        song_id,
+        rating: row.rating.and_then(Rating::from_db),
+        likes: row.likes,
+        dislikes: row.dislikes,
+    })
+}
+
+/// Songs the user liked, most recent first
+pub async fn find_favourites(pool: &SqlitePool, user_id: i64) -> Result<Vec<Song>, String> {
+    sqlx::query_as!(
+        Song,
+        r#"
+        SELECT 
+            s.id as "id!", 
+            s.title, 
+            s.album_id,
+            al.title as "album_title?",
+            GROUP_CONCAT(a.name, ', ') as "artist_names?: String",
+            s.duration_ms,
+            s.loudness_db,
+            s.bpm,
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = 1) as "likes!: i64",
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = -1) as "dislikes!: i64"
+        FROM song_ratings fav
+        JOIN songs s ON fav.song_id = s.id
+        LEFT JOIN song_artists sa ON s.id = sa.song_id
+        LEFT JOIN artists a ON sa.artist_id = a.id
+        LEFT JOIN albums al ON s.album_id = al.id
+        WHERE fav.user_id = ? AND fav.rating = 1
+        GROUP BY s.id
+        ORDER BY fav.rated_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
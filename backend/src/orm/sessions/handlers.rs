@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use axum_extra::extract::cookie::{Key, PrivateCookieJar};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::SessionDto;
+use super::repository;
+use crate::auth::{clear_session_cookie, AuthSession};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    auth: AuthSession,
+) -> Result<Json<Vec<SessionDto>>, AppError> {
+    let sessions = repository::find_by_user(&state.db, auth.user.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(sessions.into_iter().map(|s| SessionDto::new(s, auth.session.id)).collect()))
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let deleted = repository::delete(&state.db, id, auth.user.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    if !deleted {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+    audit::record(&state.db, &auth.user, AuditAction::Delete, AuditEntity::Session, Some(id), audit::NOTHING, audit::NOTHING).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Logs out everywhere, this device included
+pub async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    jar: PrivateCookieJar<Key>,
+) -> Result<(StatusCode, PrivateCookieJar<Key>), AppError> {
+    repository::delete_for_user(&state.db, auth.user.id, None)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &auth.user, AuditAction::Delete, AuditEntity::Session, None, audit::NOTHING, audit::NOTHING).await;
+
+    Ok((StatusCode::NO_CONTENT, clear_session_cookie(jar)))
+}
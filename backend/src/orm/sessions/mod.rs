@@ -0,0 +1,14 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::{delete, get};
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/auth/sessions", get(handlers::list_sessions).delete(handlers::revoke_all_sessions))
+        .route("/auth/sessions/{id}", delete(handlers::revoke_session))
+}
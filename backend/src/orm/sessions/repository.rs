@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use super::models::Session;
+
+pub async fn create(
+    pool: &SqlitePool,
+    user_id: i64,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+    user_agent: Option<&str>,
+    ip: Option<&str>,
+) -> Result<Session, String> {
+    let now = Utc::now();
+
+    sqlx::query_as!(
+        Session,
+        r#"
+        INSERT INTO sessions (user_id, token_hash, created_at, expires_at, last_seen_at, user_agent, ip)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id as "id!",
+            user_id,
+            token_hash,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_seen_at as "last_seen_at: DateTime<Utc>",
+            user_agent,
+            ip
+        "#,
+        user_id,
+        token_hash,
+        now,
+        expires_at,
+        now,
+        user_agent,
+        ip
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn find_by_token_hash(pool: &SqlitePool, token_hash: &str) -> Result<Option<Session>, String> {
+    sqlx::query_as!(
+        Session,
+        r#"
+        SELECT
+            id as "id!",
+            user_id,
+            token_hash,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_seen_at as "last_seen_at: DateTime<Utc>",
+            user_agent,
+            ip
+        FROM sessions
+        WHERE token_hash = ?
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Sessions of a user that haven't expired, most recently used first
+pub async fn find_by_user(pool: &SqlitePool, user_id: i64) -> Result<Vec<Session>, String> {
+    let now = Utc::now();
+
+    sqlx::query_as!(
+        Session,
+        r#"
+        SELECT
+            id as "id!",
+            user_id,
+            token_hash,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_seen_at as "last_seen_at: DateTime<Utc>",
+            user_agent,
+            ip
+        FROM sessions
+        WHERE user_id = ? AND expires_at > ?
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+        now
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn touch(pool: &SqlitePool, id: i64, ip: Option<&str>) -> Result<(), String> {
+    let now = Utc::now();
+
+    sqlx::query!(
+        "UPDATE sessions SET last_seen_at = ?, ip = COALESCE(?, ip) WHERE id = ?",
+        now,
+        ip,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Returns whether a session of this user was deleted
+pub async fn delete(pool: &SqlitePool, id: i64, user_id: i64) -> Result<bool, String> {
+    let result = sqlx::query!("DELETE FROM sessions WHERE id = ? AND user_id = ?", id, user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_by_token_hash(pool: &SqlitePool, token_hash: &str) -> Result<(), String> {
+    sqlx::query!("DELETE FROM sessions WHERE token_hash = ?", token_hash)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Logs a user out everywhere, except for the `keep` session if given
+pub async fn delete_for_user(pool: &SqlitePool, user_id: i64, keep: Option<i64>) -> Result<u64, String> {
+    let result = sqlx::query!(
+        "DELETE FROM sessions WHERE user_id = ? AND (? IS NULL OR id != ?)",
+        user_id,
+        keep,
+        keep
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn delete_expired(pool: &SqlitePool) -> Result<u64, String> {
+    let now = Utc::now();
+
+    let result = sqlx::query!("DELETE FROM sessions WHERE expires_at <= ?", now)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,254 @@
+use std::io::SeekFrom;
+use std::time::UNIX_EPOCH;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use bytes::Bytes;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_stream::Stream;
+
+use crate::state::AppState;
+use crate::error::AppError;
+use crate::auth::AuthUser;
+use crate::orm::users::models::Permission;
+
+// Bytes read from the file at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What part of the file a `Range` header asks for
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    /// First and last byte, inclusive
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Reads a single `bytes=` range. Anything else, several ranges included, gets the whole file,
+/// which is always a valid answer to a range request.
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    match (start.trim(), end.trim()) {
+        // The last `suffix` bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(len - suffix.min(len), len - 1),
+            Err(_) => ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) if start >= len => ByteRange::Unsatisfiable,
+            Ok(start) => ByteRange::Partial(start, len - 1),
+            Err(_) => ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start > end => ByteRange::Full,
+            (Ok(start), Ok(_)) if start >= len => ByteRange::Unsatisfiable,
+            (Ok(start), Ok(end)) => ByteRange::Partial(start, end.min(len - 1)),
+            _ => ByteRange::Full,
+        },
+    }
+}
+
+/// The range asked for by `Range`, the whole file when `If-Range` names another version
+fn requested_range(headers: &HeaderMap, etag: &str, len: u64) -> ByteRange {
+    // A range is only for the file the client already has part of, `If-Range` dates aren't trusted
+    let if_range_ok = headers.get(header::IF_RANGE)
+        .is_none_or(|value| value.to_str().is_ok_and(|value| value.trim() == etag));
+
+    match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) if if_range_ok => parse_range(value, len),
+        _ => ByteRange::Full,
+    }
+}
+
+/// Strong validator made of the file's size and modification time, both change when a song is replaced
+fn etag(metadata: &std::fs::Metadata) -> String {
+    let modified = metadata.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    format!("\"{:x}-{:x}\"", metadata.len(), modified)
+}
+
+/// Whether an `If-None-Match` list names `etag`, compared weakly as the RFC asks
+fn none_match(value: &str, etag: &str) -> bool {
+    value.split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Up to `remaining` bytes of the file from where it is positioned
+fn file_stream(file: File, remaining: u64) -> impl Stream<Item = std::io::Result<Bytes>> {
+    futures::stream::try_unfold((file, remaining), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+
+        let mut buffer = vec![0u8; CHUNK_SIZE.min(remaining as usize)];
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.truncate(read);
+
+        Ok(Some((Bytes::from(buffer), (file, remaining - read as u64))))
+    })
+}
+
+/// Serves a song's file for playing it on demand, with range requests so players can seek.
+/// Curators and admins can always, everyone else when the station allows it.
+pub async fn get_song_audio(
+    State(state): State<AppState>,
+    AuthUser(user, _): AuthUser,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let allowed = user.role.has(Permission::EditMetadata) || state.station.read().await.settings.on_demand_enabled;
+    if !allowed {
+        return Err(AppError::CustomForbidden("Playing songs on demand is turned off".to_string()));
+    }
+
+    let path = crate::config::get_music_dir().join(format!("{}.mp3", id));
+    let mut file = match File::open(&path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::NotFound("Song audio not found".to_string()));
+        }
+        Err(e) => return Err(AppError::InternalServerError(format!("Failed to open song audio: {}", e))),
+    };
+    let metadata = file.metadata().await
+        .map_err(|e| AppError::InternalServerError(format!("Failed to read song audio: {}", e)))?;
+    let len = metadata.len();
+    let etag = etag(&metadata);
+
+    let response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::ACCEPT_RANGES, "bytes")
+        // The ETag is checked again every time
+        .header(header::CACHE_CONTROL, "private, no-cache");
+
+    let if_none_match = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|value| none_match(value, &etag)) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap());
+    }
+
+    let range = requested_range(&headers, &etag, len);
+
+    let response = response.header(header::CONTENT_TYPE, "audio/mpeg");
+    match range {
+        ByteRange::Full => Ok(response
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, len)
+            .body(Body::from_stream(file_stream(file, len)))
+            .unwrap()),
+        ByteRange::Partial(start, end) => {
+            file.seek(SeekFrom::Start(start)).await
+                .map_err(|e| AppError::InternalServerError(format!("Failed to read song audio: {}", e)))?;
+            let length = end - start + 1;
+
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+                .header(header::CONTENT_LENGTH, length)
+                .body(Body::from_stream(file_stream(file, length)))
+                .unwrap())
+        }
+        ByteRange::Unsatisfiable => Ok(Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+            .header(header::ACCEPT_RANGES, "bytes")
+            .body(Body::empty())
+            .unwrap()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const ETAG: &str = "\"2a-17\"";
+
+    fn headers(range: &str, if_range: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_str(range).unwrap());
+        if let Some(if_range) = if_range {
+            headers.insert(header::IF_RANGE, HeaderValue::from_str(if_range).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range("bytes=500-", 1000), ByteRange::Partial(500, 999));
+        // The end is clamped to the file
+        assert_eq!(parse_range("bytes=900-5000", 1000), ByteRange::Partial(900, 999));
+        assert_eq!(parse_range("bytes=999-999", 1000), ByteRange::Partial(999, 999));
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=1000-1010", 1000), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), ByteRange::Partial(900, 999));
+        // Longer than the file is all of it
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Partial(0, 999));
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn empty_file() {
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-10", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn ranges_served_whole() {
+        assert_eq!(parse_range("bytes=500-100", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-10", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=abc-", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=10", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn if_range() {
+        assert_eq!(requested_range(&headers("bytes=0-9", None), ETAG, 1000), ByteRange::Partial(0, 9));
+        assert_eq!(requested_range(&headers("bytes=0-9", Some(ETAG)), ETAG, 1000), ByteRange::Partial(0, 9));
+        // Another version, a weak tag or a date all get the whole file
+        assert_eq!(requested_range(&headers("bytes=0-9", Some("\"2b-17\"")), ETAG, 1000), ByteRange::Full);
+        assert_eq!(requested_range(&headers("bytes=0-9", Some("W/\"2a-17\"")), ETAG, 1000), ByteRange::Full);
+        assert_eq!(
+            requested_range(&headers("bytes=0-9", Some("Sun, 18 Oct 2026 22:09:31 GMT")), ETAG, 1000),
+            ByteRange::Full,
+        );
+        assert_eq!(requested_range(&HeaderMap::new(), ETAG, 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn if_none_match() {
+        assert!(none_match(ETAG, ETAG));
+        assert!(none_match("*", ETAG));
+        assert!(none_match("W/\"2a-17\"", ETAG));
+        assert!(none_match("\"old\", \"2a-17\"", ETAG));
+        assert!(!none_match("\"2b-17\"", ETAG));
+        assert!(!none_match("", ETAG));
+    }
+}
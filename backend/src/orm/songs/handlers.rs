@@ -6,9 +6,11 @@ use axum::{
 
 use crate::state::AppState;
 use crate::error::AppError;
-use super::models::{CreateSongDto, UpdateSongDto, Song};
+use super::models::{CreateSongDto, UpdateSongDto, Song, WaveformQuery, GenerateRhythmDto, GenerateRhythmResponse};
 use super::repository;
-use crate::auth::AdminOnly;
+use crate::auth::{perm, AuthUser, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::analysis::waveform::Waveform;
 
 pub async fn list_songs(
     State(state): State<AppState>,
@@ -56,38 +58,140 @@ pub async fn get_song_image(
     ))
 }
 
+async fn read_waveform(id: i64) -> Result<Option<Waveform>, AppError> {
+    match tokio::fs::read(crate::config::get_waveform_path(id)).await {
+        Ok(data) => serde_json::from_slice::<Waveform>(&data)
+            .map(Some)
+            .map_err(|e| AppError::InternalServerError(format!("Corrupted waveform: {}", e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(AppError::InternalServerError(format!("Failed to read waveform: {}", e))),
+    }
+}
+
+pub async fn get_song_waveform(
+    State(state): State<AppState>,
+    _: AuthUser,
+    Path(id): Path<i64>,
+    axum::extract::Query(query): axum::extract::Query<WaveformQuery>,
+) -> Result<Json<Waveform>, AppError> {
+    let mut waveform = match read_waveform(id).await? {
+        Some(waveform) => waveform,
+        None => {
+            // Songs uploaded before analysis existed get their waveform generated on first request
+            repository::find_by_id(&state.db, id)
+                .await
+                .map_err(AppError::InternalServerError)?
+                .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
+            state.analysis_queue.run(&state.db, id)
+                .await
+                .map_err(|e| AppError::InternalServerError(format!("Waveform not available: {}", e)))?;
+            read_waveform(id).await?
+                .ok_or(AppError::InternalServerError("Waveform not available".to_string()))?
+        }
+    };
+
+    if !query.spectrogram.unwrap_or(false) {
+        waveform.spectrogram = None;
+    }
+
+    Ok(Json(waveform))
+}
+
+pub async fn generate_song_rhythm(
+    State(state): State<AppState>,
+    Require(actor, _): Require<perm::EditMetadata>,
+    Path(id): Path<i64>,
+    payload: Option<Json<GenerateRhythmDto>>,
+) -> Result<Json<GenerateRhythmResponse>, AppError> {
+    repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
+    // Without a tempo in the body the one set by hand, if any, still applies
+    let bpm_override = match payload.and_then(|Json(dto)| dto.bpm) {
+        Some(Some(bpm)) => Some(bpm),
+        Some(None) => {
+            repository::clear_bpm_override(&state.db, id)
+                .await
+                .map_err(AppError::InternalServerError)?;
+            None
+        }
+        None => repository::find_bpm_override(&state.db, id)
+            .await
+            .map_err(AppError::InternalServerError)?,
+    };
+    let options = crate::analysis::AnalysisOptions {
+        bpm_override,
+        overwrite_rhythm: true,
+    };
+
+    let grid = crate::analysis::run_for_song(&state.db, id, options)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .beat_grid
+        .ok_or(AppError::BadRequest("Could not detect a beat grid for this song".to_string()))?;
+
+    let response = GenerateRhythmResponse {
+        bpm: grid.bpm,
+        beats: grid.beats_ms.len(),
+        downbeats: grid.downbeats_ms.len(),
+        onsets: grid.onsets_ms.len(),
+    };
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::Rhythm, Some(id), audit::NOTHING, Some(&response)).await;
+
+    Ok(Json(response))
+}
+
 pub async fn create_song(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::ManageLibrary>,
     Json(payload): Json<CreateSongDto>,
 ) -> Result<Json<Song>, AppError> {
     let song = repository::create(&state.db, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Create, AuditEntity::Song, Some(song.id), audit::NOTHING, Some(&song)).await;
     Ok(Json(song))
 }
 
 pub async fn update_song(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Path(id): Path<i64>,
     Json(payload): Json<UpdateSongDto>,
 ) -> Result<Json<Song>, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
     let song = repository::update(&state.db, id, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::Song, Some(id), Some(&before), Some(&song)).await;
+
+    crate::streaming::events::song_updated(&state, &song).await;
+
     Ok(Json(song))
 }
 
 pub async fn delete_song(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::ManageLibrary>,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
     // Delete from DB first
     repository::delete(&state.db, id)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::Song, Some(id), Some(&before), audit::NOTHING).await;
 
     // Delete the file if it exists
     let file_path = std::path::PathBuf::from("data").join(format!("{}.mp3", id));
@@ -100,5 +204,12 @@ pub async fn delete_song(
         let _ = tokio::fs::remove_file(image_path).await;
     }
 
+    let waveform_path = crate::config::get_waveform_path(id);
+    if waveform_path.exists() {
+        let _ = tokio::fs::remove_file(waveform_path).await;
+    }
+
+    crate::rhythm::store::delete(id).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
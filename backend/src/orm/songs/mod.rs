@@ -2,6 +2,8 @@ pub mod models;
 pub mod repository;
 pub mod handlers;
 pub mod upload;
+pub mod rhythm;
+pub mod audio;
 
 use axum::extract::DefaultBodyLimit;
 use axum::Router;
@@ -14,5 +16,9 @@ pub fn router() -> Router<AppState> {
         .route("/songs/upload", post(upload::upload_song))
         .route("/songs/{id}", get(handlers::get_song).post(handlers::update_song).delete(handlers::delete_song))
         .route("/songs/{id}/image", get(handlers::get_song_image))
+        .route("/songs/{id}/audio", get(audio::get_song_audio))
+        .route("/songs/{id}/waveform", get(handlers::get_song_waveform))
+        .route("/songs/{id}/rhythm", get(rhythm::get_rhythm).post(rhythm::upload_rhythm).delete(rhythm::delete_rhythm))
+        .route("/songs/{id}/rhythm/generate", post(handlers::generate_song_rhythm))
         .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
 }
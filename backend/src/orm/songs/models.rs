@@ -8,6 +8,12 @@ pub struct Song {
     pub album_id: Option<i64>,
     pub album_title: Option<String>,
     pub artist_names: Option<String>, // Aggregated from the song_artists join table
+    pub duration_ms: Option<i64>,
+    pub loudness_db: Option<f64>,
+    pub bpm: Option<f64>,
+    // Aggregated from song_ratings
+    pub likes: i64,
+    pub dislikes: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,4 +28,27 @@ pub struct UpdateSongDto {
     pub title: Option<String>,
     pub album_id: Option<i64>,
     pub artist_ids: Option<Vec<i64>>, // Support multiple artists
+    /// Manual override of the detected tempo, `Some(None)` (explicit null) goes back to the detected one
+    #[serde(default, with = "crate::orm::double_option")]
+    pub bpm: Option<Option<f64>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaveformQuery {
+    pub spectrogram: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GenerateRhythmDto {
+    /// Fit the grid to this tempo instead of detecting it, `Some(None)` (explicit null) clears the override and detects it again
+    #[serde(default, with = "crate::orm::double_option")]
+    pub bpm: Option<Option<f64>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GenerateRhythmResponse {
+    pub bpm: f64,
+    pub beats: usize,
+    pub downbeats: usize,
+    pub onsets: usize,
 }
@@ -10,7 +10,12 @@ pub async fn find_all(pool: &SqlitePool) -> Result<Vec<Song>, String> {
             s.title, 
             s.album_id,
             al.title as album_title,
-            GROUP_CONCAT(a.name, ', ') as artist_names
+            GROUP_CONCAT(a.name, ', ') as artist_names,
+            s.duration_ms,
+            s.loudness_db,
+            s.bpm,
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = 1) as "likes!: i64",
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = -1) as "dislikes!: i64"
         FROM songs s
         LEFT JOIN song_artists sa ON s.id = sa.song_id
         LEFT JOIN artists a ON sa.artist_id = a.id
@@ -34,7 +39,12 @@ pub async fn search(pool: &SqlitePool, query: &str) -> Result<Vec<Song>, String>
             s.title, 
             s.album_id,
             al.title as album_title,
-            GROUP_CONCAT(a.name, ', ') as artist_names
+            GROUP_CONCAT(a.name, ', ') as artist_names,
+            s.duration_ms,
+            s.loudness_db,
+            s.bpm,
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = 1) as "likes!: i64",
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = -1) as "dislikes!: i64"
         FROM songs s
         LEFT JOIN song_artists sa ON s.id = sa.song_id
         LEFT JOIN artists a ON sa.artist_id = a.id
@@ -60,7 +70,12 @@ pub async fn find_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Song>, Stri
             s.title, 
             s.album_id,
             al.title as album_title,
-            GROUP_CONCAT(a.name, ', ') as artist_names
+            GROUP_CONCAT(a.name, ', ') as artist_names,
+            s.duration_ms,
+            s.loudness_db,
+            s.bpm,
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = 1) as "likes!: i64",
+            (SELECT COUNT(*) FROM song_ratings r WHERE r.song_id = s.id AND r.rating = -1) as "dislikes!: i64"
         FROM songs s
         LEFT JOIN song_artists sa ON s.id = sa.song_id
         LEFT JOIN artists a ON sa.artist_id = a.id
@@ -124,6 +139,21 @@ pub async fn update(pool: &SqlitePool, id: i64, dto: UpdateSongDto) -> Result<So
         separated.push_bind_unseparated(album_id);
         has_updates = true;
     }
+    match dto.bpm {
+        Some(Some(bpm)) => {
+            separated.push("bpm = ");
+            separated.push_bind_unseparated(bpm);
+            separated.push("bpm_override = ");
+            separated.push_bind_unseparated(bpm);
+            has_updates = true;
+        }
+        Some(None) => {
+            separated.push("bpm = bpm_detected");
+            separated.push("bpm_override = NULL");
+            has_updates = true;
+        }
+        None => {}
+    }
 
     if has_updates {
         qb.push(" WHERE id = ");
@@ -172,3 +202,56 @@ pub async fn delete(pool: &SqlitePool, id: i64) -> Result<(), String> {
 
     Ok(())
 }
+
+/// Goes back to the detected tempo, so the next analysis detects it again
+pub async fn clear_bpm_override(pool: &SqlitePool, id: i64) -> Result<(), String> {
+    sqlx::query!("UPDATE songs SET bpm = bpm_detected, bpm_override = NULL WHERE id = ?", id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Tempo set by hand, if any
+pub async fn find_bpm_override(pool: &SqlitePool, id: i64) -> Result<Option<f64>, String> {
+    let row = sqlx::query!("SELECT bpm_override FROM songs WHERE id = ?", id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(row.and_then(|r| r.bpm_override))
+}
+
+/// Saves analysis results. A `detected_bpm` is kept for when the override is cleared but never replaces
+/// one set by hand, a new `bpm_override` does.
+pub async fn set_analysis(
+    pool: &SqlitePool,
+    id: i64,
+    duration_ms: i64,
+    loudness_db: f64,
+    detected_bpm: Option<f64>,
+    bpm_override: Option<f64>,
+) -> Result<(), String> {
+    sqlx::query!(
+        r#"
+        UPDATE songs SET
+            duration_ms = ?1,
+            loudness_db = ?2,
+            bpm_detected = COALESCE(?3, bpm_detected),
+            bpm_override = COALESCE(?4, bpm_override),
+            bpm = COALESCE(?4, bpm_override, ?3, bpm)
+        WHERE id = ?5
+        "#,
+        duration_ms,
+        loudness_db,
+        detected_bpm,
+        bpm_override,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
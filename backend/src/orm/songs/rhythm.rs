@@ -0,0 +1,72 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use crate::auth::{perm, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::rhythm::{compiler::{self, CompileError}, model::RhythmTrack, store};
+use super::repository;
+
+#[derive(serde::Serialize)]
+pub struct RhythmUploadResponse {
+    pub song_id: i64,
+    pub events: usize,
+}
+
+pub async fn get_rhythm(
+    Path(id): Path<i64>,
+) -> Result<Json<RhythmTrack>, AppError> {
+    let track = store::load(id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song has no rhythm data".to_string()))?;
+    Ok(Json(track))
+}
+
+// Takes the raw body so validation errors can point at lines of what the author sent
+pub async fn upload_rhythm(
+    State(state): State<AppState>,
+    Require(actor, _): Require<perm::EditMetadata>,
+    Path(id): Path<i64>,
+    body: String,
+) -> Result<Json<RhythmUploadResponse>, AppError> {
+    let song = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
+    let duration_ms = song.duration_ms.map(|d| d as u64);
+    let compiled = compiler::compile_bytes(body.as_bytes(), duration_ms).map_err(|e| match e {
+        CompileError::Invalid(errors) => AppError::ValidationFailed(errors.iter().map(|e| e.to_string()).collect()),
+        other => AppError::InternalServerError(other.to_string()),
+    })?;
+
+    // Decompiling our own output doubles as a check of what clients will receive
+    let track = compiler::decompile_bytes(&compiled)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    store::save(id, body.as_bytes(), &compiled)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    let response = RhythmUploadResponse { song_id: id, events: track.events.len() };
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::Rhythm, Some(id), audit::NOTHING, Some(&response)).await;
+
+    Ok(Json(response))
+}
+
+pub async fn delete_rhythm(
+    State(state): State<AppState>,
+    Require(actor, _): Require<perm::EditMetadata>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    if !store::delete(id).await {
+        return Err(AppError::NotFound("Song has no rhythm data".to_string()));
+    }
+    audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::Rhythm, Some(id), audit::NOTHING, audit::NOTHING).await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -8,7 +8,8 @@ use tokio::process::Command;
 use axum_extra::extract::Multipart;
 use crate::state::AppState;
 use crate::error::AppError;
-use crate::auth::AdminOnly;
+use crate::auth::{perm, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
 use super::models::CreateSongDto;
 use super::repository;
 
@@ -20,7 +21,7 @@ pub struct UploadResponse {
 
 pub async fn upload_song(
     State(state): State<AppState>,
-    _admin: AdminOnly,
+    Require(actor, _): Require<perm::ManageLibrary>,
     mut multipart: Multipart,
 ) -> Result<(StatusCode, Json<UploadResponse>), AppError> {
     let mut file_data: Option<Vec<u8>> = None;
@@ -119,10 +120,18 @@ pub async fn upload_song(
     // Clean up raw file after successful conversion
     let _ = fs::remove_file(&raw_path).await;
 
+    // Duration, loudness, waveform preview and beat grid. A failure here shouldn't reject the upload,
+    // the waveform endpoint will retry on demand.
+    if let Err(e) = crate::analysis::run_for_song(&state.db, song_id, Default::default()).await {
+        tracing::warn!("Analysis failed for song #{}: {}", song_id, e);
+    }
+
+    audit::record(&state.db, &actor, AuditAction::Create, AuditEntity::Song, Some(song_id), audit::NOTHING, Some(&song)).await;
+
     // Handle image if provided
     if let Some(img_data) = image_data {
         let img_path = crate::config::get_covers_dir().join(format!("{}.png", song_id)); // Defaulting to png for now, or we could detect
-        if let Ok(_) = fs::write(&img_path, &img_data).await {
+        if fs::write(&img_path, &img_data).await.is_ok() {
             //let _ = repository::set_has_image(&state.db, song_id, true).await;
         }
     }
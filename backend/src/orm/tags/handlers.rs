@@ -6,9 +6,10 @@ use axum::{
 
 use crate::state::AppState;
 use crate::error::AppError;
-use super::models::{CreateTagDto, UpdateTagDto, Tag, AssignTagDto, TagInfo, TagRequest, SongSearchResult};
+use super::models::{CreateTagDto, UpdateTagDto, Tag, AssignTagDto, SongTag, TagInfo, TagRequest, SongSearchResult};
 use super::repository;
-use crate::auth::AdminOnly;
+use crate::auth::{perm, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
 
 pub async fn list_tags(
     State(state): State<AppState>,
@@ -32,47 +33,69 @@ pub async fn get_tag(
 
 pub async fn create_tag(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Json(payload): Json<CreateTagDto>,
 ) -> Result<Json<Tag>, AppError> {
     let tag = repository::create(&state.db, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Create, AuditEntity::Tag, Some(tag.id), audit::NOTHING, Some(&tag)).await;
     Ok(Json(tag))
 }
 
 pub async fn update_tag(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Path(id): Path<i64>,
     Json(payload): Json<UpdateTagDto>,
 ) -> Result<Json<Tag>, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Tag not found".to_string()))?;
+
     let tag = repository::update(&state.db, id, payload)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::Tag, Some(id), Some(&before), Some(&tag)).await;
     Ok(Json(tag))
 }
 
 pub async fn delete_tag(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::ManageLibrary>,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Tag not found".to_string()))?;
+
     repository::delete(&state.db, id)
         .await
         .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::Tag, Some(id), Some(&before), audit::NOTHING).await;
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn assign_tag(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Path(song_id): Path<i64>,
     Json(payload): Json<AssignTagDto>,
 ) -> Result<StatusCode, AppError> {
+    let before = song_tag(&state, song_id, payload.tag_id).await?;
+
     repository::assign_to_song(&state.db, song_id, payload.tag_id, payload.score)
         .await
         .map_err(AppError::InternalServerError)?;
+
+    let after = SongTag { song_id, tag_id: payload.tag_id, score: payload.score };
+    let action = if before.is_some() { AuditAction::Update } else { AuditAction::Create };
+    audit::record(&state.db, &actor, action, AuditEntity::SongTag, Some(song_id), before.as_ref(), Some(&after)).await;
+
+    crate::streaming::events::song_tags_changed(&state, song_id).await;
+
     Ok(StatusCode::OK)
 }
 
@@ -98,11 +121,30 @@ pub async fn search_songs(
 
 pub async fn remove_tag(
     State(state): State<AppState>,
-    _: AdminOnly,
+    Require(actor, _): Require<perm::EditMetadata>,
     Path((song_id, tag_id)): Path<(i64, i64)>,
 ) -> Result<StatusCode, AppError> {
+    let before = song_tag(&state, song_id, tag_id).await?;
+
     repository::remove_from_song(&state.db, song_id, tag_id)
         .await
         .map_err(AppError::InternalServerError)?;
+    if before.is_some() {
+        audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::SongTag, Some(song_id), before.as_ref(), audit::NOTHING).await;
+    }
+
+    crate::streaming::events::song_tags_changed(&state, song_id).await;
+
     Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+}
+// Current score of a tag on a song, for the audit log
+async fn song_tag(state: &AppState, song_id: i64, tag_id: i64) -> Result<Option<SongTag>, AppError> {
+    let tags = repository::find_by_song(&state.db, song_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(tags
+        .into_iter()
+        .find(|t| t.tag_id == tag_id)
+        .map(|t| SongTag { song_id, tag_id, score: t.score }))
+}
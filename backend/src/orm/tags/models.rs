@@ -32,7 +32,7 @@ pub struct SongTag {
     pub score: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagInfo {
     pub tag_id: i64,
     pub name: String,
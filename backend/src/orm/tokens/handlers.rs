@@ -0,0 +1,105 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{ApiScope, ApiTokenDto, CreateApiTokenDto, CreatedApiTokenDto, TokenScopes};
+use super::repository;
+use crate::auth::{hash_token, new_secret_token, AuthSession};
+use crate::config::{API_TOKEN_DEFAULT_DAYS, API_TOKEN_MAX_DAYS, MAX_API_TOKENS_PER_USER};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+
+// Every API token starts with this, so leaked ones are easy to spot
+const TOKEN_PREFIX: &str = "wavy_";
+// Characters of the token shown in listings
+const SHOWN_PREFIX_LENGTH: usize = 12;
+
+// Token management takes a cookie session, an API token can't mint more tokens
+
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    auth: AuthSession,
+) -> Result<Json<Vec<ApiTokenDto>>, AppError> {
+    let tokens = repository::find_by_user(&state.db, auth.user.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(tokens.into_iter().map(ApiTokenDto::from).collect()))
+}
+
+pub async fn create_token(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    Json(payload): Json<CreateApiTokenDto>,
+) -> Result<(StatusCode, Json<CreatedApiTokenDto>), AppError> {
+    let mut errors = Vec::new();
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        errors.push("name must be between 1 and 64 characters".to_string());
+    }
+
+    let mut scopes = payload.scopes;
+    scopes.sort_by_key(|s| s.as_str());
+    scopes.dedup();
+    if scopes.is_empty() {
+        errors.push("at least one scope is required".to_string());
+    }
+    if scopes.contains(&ApiScope::Admin) && !auth.is_admin() {
+        errors.push("only admins can grant the admin scope".to_string());
+    }
+
+    let days = payload.expires_in_days.unwrap_or(API_TOKEN_DEFAULT_DAYS);
+    if !(1..=API_TOKEN_MAX_DAYS).contains(&days) {
+        errors.push(format!("expires_in_days must be between 1 and {}", API_TOKEN_MAX_DAYS));
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::ValidationFailed(errors));
+    }
+
+    let count = repository::count_by_user(&state.db, auth.user.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    if count >= MAX_API_TOKENS_PER_USER {
+        return Err(AppError::Conflict(format!("You can have at most {} API tokens", MAX_API_TOKENS_PER_USER)));
+    }
+
+    let token = format!("{}{}", TOKEN_PREFIX, new_secret_token());
+    let expires_at = Utc::now() + Duration::days(days);
+
+    let created = repository::create(
+        &state.db,
+        auth.user.id,
+        name,
+        &hash_token(&token),
+        &token[..SHOWN_PREFIX_LENGTH],
+        &TokenScopes(scopes).to_db(),
+        expires_at,
+    )
+    .await
+    .map_err(AppError::InternalServerError)?;
+    let info = ApiTokenDto::from(created);
+    audit::record(&state.db, &auth.user, AuditAction::Create, AuditEntity::ApiToken, Some(info.id), audit::NOTHING, Some(&info)).await;
+
+    Ok((StatusCode::CREATED, Json(CreatedApiTokenDto { token, info })))
+}
+
+pub async fn delete_token(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let deleted = repository::delete(&state.db, id, auth.user.id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("API token not found".to_string()))?;
+    let before = ApiTokenDto::from(deleted);
+    audit::record(&state.db, &auth.user, AuditAction::Delete, AuditEntity::ApiToken, Some(id), Some(&before), audit::NOTHING).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,14 @@
+pub mod models;
+pub mod repository;
+pub mod handlers;
+
+use axum::Router;
+use axum::routing::{delete, get};
+
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/auth/tokens", get(handlers::list_tokens).post(handlers::create_token))
+        .route("/auth/tokens/{id}", delete(handlers::delete_token))
+}
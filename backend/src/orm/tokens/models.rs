@@ -0,0 +1,133 @@
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// What an API token is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ApiScope {
+    /// Stream, heartbeats, the WebSocket and the current song
+    #[serde(rename = "stream:listen")]
+    StreamListen,
+    /// Every other GET request
+    #[serde(rename = "library:read")]
+    LibraryRead,
+    /// Every other write, songs, tags, ratings...
+    #[serde(rename = "library:write")]
+    LibraryWrite,
+    /// Sending chat messages
+    #[serde(rename = "chat:write")]
+    ChatWrite,
+    /// Admin only endpoints, on top of the scope of the request
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 5] = [
+        ApiScope::StreamListen,
+        ApiScope::LibraryRead,
+        ApiScope::LibraryWrite,
+        ApiScope::ChatWrite,
+        ApiScope::Admin,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::StreamListen => "stream:listen",
+            ApiScope::LibraryRead => "library:read",
+            ApiScope::LibraryWrite => "library:write",
+            ApiScope::ChatWrite => "chat:write",
+            ApiScope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// Scope a token needs for a request, `path` is relative to `/api`
+    pub fn required_for(method: &Method, path: &str) -> ApiScope {
+        let read = method == Method::GET || method == Method::HEAD;
+        let path = path.strip_prefix("/api").unwrap_or(path);
+        let first = path.trim_start_matches('/').split('/').next().unwrap_or_default();
+
+        match first {
+            "stream" | "heartbeat" | "ws" | "listeners" => ApiScope::StreamListen,
+            "song" if read => ApiScope::StreamListen,
+            "chat" if !read => ApiScope::ChatWrite,
+            _ if read => ApiScope::LibraryRead,
+            _ => ApiScope::LibraryWrite,
+        }
+    }
+}
+
+/// Scopes of the token a request was authenticated with, kept in the request extensions
+#[derive(Debug, Clone)]
+pub struct TokenScopes(pub Vec<ApiScope>);
+
+impl TokenScopes {
+    pub fn from_db(value: &str) -> Self {
+        Self(value.split_whitespace().filter_map(ApiScope::parse).collect())
+    }
+
+    pub fn to_db(&self) -> String {
+        self.0.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn contains(&self, scope: ApiScope) -> bool {
+        self.0.contains(&scope)
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenDto {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTokenDto {
+    pub id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for ApiTokenDto {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: TokenScopes::from_db(&token.scopes).0,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+        }
+    }
+}
+
+/// A freshly minted token, the only time its value is shown
+#[derive(Debug, Serialize)]
+pub struct CreatedApiTokenDto {
+    pub token: String,
+    #[serde(flatten)]
+    pub info: ApiTokenDto,
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use super::models::ApiToken;
+
+pub async fn create(
+    pool: &SqlitePool,
+    user_id: i64,
+    name: &str,
+    token_hash: &str,
+    token_prefix: &str,
+    scopes: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<ApiToken, String> {
+    let now = Utc::now();
+
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, created_at, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id as "id!",
+            user_id,
+            name,
+            token_prefix,
+            scopes,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_used_at as "last_used_at: DateTime<Utc>"
+        "#,
+        user_id,
+        name,
+        token_hash,
+        token_prefix,
+        scopes,
+        now,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn find_by_token_hash(pool: &SqlitePool, token_hash: &str) -> Result<Option<ApiToken>, String> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT
+            id as "id!",
+            user_id,
+            name,
+            token_prefix,
+            scopes,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_used_at as "last_used_at: DateTime<Utc>"
+        FROM api_tokens
+        WHERE token_hash = ?
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Tokens of a user, newest first, expired ones included
+pub async fn find_by_user(pool: &SqlitePool, user_id: i64) -> Result<Vec<ApiToken>, String> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT
+            id as "id!",
+            user_id,
+            name,
+            token_prefix,
+            scopes,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_used_at as "last_used_at: DateTime<Utc>"
+        FROM api_tokens
+        WHERE user_id = ?
+        ORDER BY id DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn count_by_user(pool: &SqlitePool, user_id: i64) -> Result<i64, String> {
+    sqlx::query_scalar!("SELECT COUNT(*) FROM api_tokens WHERE user_id = ?", user_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn touch(pool: &SqlitePool, id: i64) -> Result<(), String> {
+    let now = Utc::now();
+
+    sqlx::query!("UPDATE api_tokens SET last_used_at = ? WHERE id = ?", now, id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// The deleted token, `None` if this user has no token with that id
+pub async fn delete(pool: &SqlitePool, id: i64, user_id: i64) -> Result<Option<ApiToken>, String> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        DELETE FROM api_tokens
+        WHERE id = ? AND user_id = ?
+        RETURNING
+            id as "id!",
+            user_id,
+            name,
+            token_prefix,
+            scopes,
+            created_at as "created_at: DateTime<Utc>",
+            expires_at as "expires_at: DateTime<Utc>",
+            last_used_at as "last_used_at: DateTime<Utc>"
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn delete_for_user(pool: &SqlitePool, user_id: i64) -> Result<(), String> {
+    sqlx::query!("DELETE FROM api_tokens WHERE user_id = ?", user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
@@ -1,85 +1,191 @@
 use axum::{
     extract::State,
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json as AxumJson,
 };
-use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar, SameSite};
+use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar};
 
 use crate::state::AppState;
 use crate::error::AppError;
-use super::{models::{CreateUserDto, LoginPayload, User}, repository};
-use crate::auth::{AUTH_COOKIE_NAME, AuthUser};
-use argon2::{
-    password_hash::{
-        PasswordHash, PasswordVerifier
-    },
-    Argon2
+use super::{
+    models::{CreateUserDto, GuestDto, LoginPayload, Permission, RegistrationInfoDto, RegistrationMode, User, UserRole, UserStatus},
+    repository,
 };
+use crate::auth::{
+    clear_session_cookie, hash_token, perm, start_session, AuthSession, AuthUser, ClientInfo, GuestSession, Require,
+    AUTH_COOKIE_NAME, GUEST_COOKIE_NAME,
+};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::throttle::LoginThrottle;
+use crate::orm::invites;
+use super::repository::DUMMY_PASSWORD_HASH;
+use super::{passwords, validation};
+
+pub async fn get_registration_info(
+    State(state): State<AppState>,
+) -> AxumJson<RegistrationInfoDto> {
+    AxumJson(RegistrationInfoDto {
+        mode: RegistrationMode::from_env(),
+        needs_setup: state.setup_token_hash.lock().await.is_some(),
+        guests_enabled: state.station.read().await.settings.guests_enabled,
+    })
+}
+
+/// Starts a guest session, or resumes the one in the cookie
+pub async fn start_guest_session(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar<Key>,
+) -> Result<(PrivateCookieJar<Key>, AxumJson<GuestDto>), AppError> {
+    if !state.station.read().await.settings.guests_enabled {
+        return Err(AppError::CustomForbidden("Guest listening is turned off".to_string()));
+    }
 
-use time::Duration;
+    let guest = GuestSession::from_jar(&jar).unwrap_or_else(GuestSession::new);
+    let dto = GuestDto { id: guest.id, name: guest.name(), expires_at: guest.expires_at };
+
+    Ok((guest.add_to(jar), AxumJson(dto)))
+}
 
 pub async fn register(
     State(state): State<AppState>,
     jar: PrivateCookieJar<Key>,
+    client: ClientInfo,
     AxumJson(payload): AxumJson<CreateUserDto>,
-) -> Result<(StatusCode, PrivateCookieJar<Key>, AxumJson<User>), AppError> {
+) -> Result<Response, AppError> {
+    let mut errors = validation::username_errors(&payload.username);
+    errors.extend(validation::password_errors(&payload.password, &payload.username));
+    if !errors.is_empty() {
+        return Err(AppError::ValidationFailed(errors));
+    }
+
     // Check if user exists
     if let Ok(Some(_)) = repository::find_by_username(&state.db, &payload.username).await {
          return Err(AppError::Conflict("Username already taken".to_string()));
     }
 
-    let user = repository::create(&state.db, payload).await.map_err(AppError::InternalServerError)?;
+    // The first admin, whatever the registration mode
+    if let Some(token) = payload.setup_token.as_deref() {
+        let mut setup_token_hash = state.setup_token_hash.lock().await;
+        if setup_token_hash.as_deref() != Some(hash_token(token.trim()).as_str()) {
+            return Err(AppError::CustomForbidden("Invalid setup token".to_string()));
+        }
+
+        let user = repository::create(&state.db, &payload, UserRole::Admin, UserStatus::Active, None)
+            .await
+            .map_err(AppError::InternalServerError)?;
+        *setup_token_hash = None;
+        tracing::info!("Registered the first admin '{}', the setup token is used up", user.username);
+
+        let jar = start_session(&state, jar, user.id, &client).await?;
+        return Ok((StatusCode::CREATED, jar, AxumJson(user)).into_response());
+    }
+
+    let mode = RegistrationMode::from_env();
+    if mode == RegistrationMode::Closed {
+        return Err(AppError::CustomForbidden("Registration is closed".to_string()));
+    }
+
+    let invite_id = match payload.invite_code.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) if mode.accepts_invites() => Some(
+            invites::repository::redeem(&state.db, &hash_token(code))
+                .await
+                .map_err(AppError::InternalServerError)?
+                .ok_or(AppError::CustomForbidden("Invalid or expired invite code".to_string()))?,
+        ),
+        _ => None,
+    };
+
+    let status = match (mode, invite_id) {
+        (RegistrationMode::Invite, None) => {
+            return Err(AppError::CustomForbidden("Registration needs an invite code".to_string()));
+        }
+        (RegistrationMode::Approval, None) => UserStatus::Pending,
+        _ => UserStatus::Active,
+    };
+
+    let user = match repository::create(&state.db, &payload, UserRole::User, status, invite_id).await {
+        Ok(user) => user,
+        Err(e) => {
+            if let Some(invite_id) = invite_id {
+                let _ = invites::repository::release(&state.db, invite_id).await;
+            }
+            return Err(if e.contains("UNIQUE constraint") {
+                AppError::Conflict("Username already taken".to_string())
+            } else {
+                AppError::InternalServerError(e)
+            });
+        }
+    };
+
+    // Logged in once an admin approves
+    if user.status == UserStatus::Pending {
+        return Ok((StatusCode::ACCEPTED, AxumJson(user)).into_response());
+    }
 
     // Auto login
-    let mut cookie = Cookie::new(AUTH_COOKIE_NAME, user.id.to_string());
-    cookie.set_http_only(true);
-    cookie.set_path("/");
-    cookie.set_same_site(SameSite::Lax);
-    cookie.set_max_age(Duration::days(30));
-    
-    let jar = jar.add(cookie);
-
-    Ok((StatusCode::CREATED, jar, AxumJson(user)))
+    let jar = start_session(&state, jar, user.id, &client).await?;
+
+    Ok((StatusCode::CREATED, jar, AxumJson(user)).into_response())
 }
 
 pub async fn login(
     State(state): State<AppState>,
     jar: PrivateCookieJar<Key>,
+    client: ClientInfo,
     AxumJson(payload): AxumJson<LoginPayload>,
 ) -> Result<(PrivateCookieJar<Key>, AxumJson<User>), AppError> {
+    let keys = LoginThrottle::keys(client.ip.as_deref(), &payload.identity);
+    let attempt = state.login_throttle.lock().await
+        .begin_attempt(keys)
+        .map_err(AppError::TooManyRequests)?;
+
     let user = repository::find_by_username(&state.db, &payload.identity).await
-        .map_err(AppError::InternalServerError)?
-        .ok_or(AppError::WrongCredentials)?;
+        .map_err(AppError::InternalServerError)?;
 
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    // Unknown usernames are checked against a dummy hash so they take as long as a wrong password
+    let password_hash = user.as_ref().map_or(DUMMY_PASSWORD_HASH.as_str(), |u| u.password_hash.as_str());
+    let verified = passwords::verify(&payload.password, password_hash)?;
 
-    Argon2::default()
-        .verify_password(payload.password.as_bytes(), &parsed_hash)
-        .map_err(|_| AppError::WrongCredentials)?;
+    let user = match user {
+        Some(user) if verified => user,
+        user => {
+            audit::record_failed_login(&state.db, &payload.identity, user.as_ref(), &client, attempt.lockout).await;
+            return Err(AppError::WrongCredentials);
+        }
+    };
+    state.login_throttle.lock().await.record_success(attempt);
+
+    if user.status == UserStatus::Pending {
+        return Err(AppError::CustomForbidden("Your account is waiting for approval".to_string()));
+    }
 
-    let mut cookie = Cookie::new(AUTH_COOKIE_NAME, user.id.to_string());
-    cookie.set_http_only(true);
-    cookie.set_path("/");
-    cookie.set_same_site(SameSite::Lax);
-    cookie.set_max_age(Duration::days(30));
-    
-    let jar = jar.add(cookie);
+    let jar = start_session(&state, jar, user.id, &client).await?;
 
     Ok((jar, AxumJson(user)))
 }
 
 pub async fn logout(
+    State(state): State<AppState>,
     jar: PrivateCookieJar<Key>,
 ) -> Result<(StatusCode, PrivateCookieJar<Key>), AppError> {
-    let mut cookie = Cookie::new(AUTH_COOKIE_NAME, "");
-    cookie.set_path("/");
-    let jar = jar.remove(cookie);
-    Ok((StatusCode::OK, jar))
+    // Ends the session server side too, so a copied cookie stops working
+    if let Some(cookie) = jar.get(AUTH_COOKIE_NAME) {
+        crate::orm::sessions::repository::delete_by_token_hash(&state.db, &hash_token(cookie.value()))
+            .await
+            .map_err(AppError::InternalServerError)?;
+    }
+
+    // Ends a guest session too
+    let mut guest_cookie = Cookie::new(GUEST_COOKIE_NAME, "");
+    guest_cookie.set_path("/");
+    let jar = jar.remove(guest_cookie);
+
+    Ok((StatusCode::OK, clear_session_cookie(jar)))
 }
 
 pub async fn get_me(
-    AuthUser(user): AuthUser,
+    AuthUser(user, _): AuthUser,
 ) -> AxumJson<User> {
     AxumJson(user)
 }
@@ -89,28 +195,48 @@ use super::models::UpdateUserDto;
 
 pub async fn update_user(
     State(state): State<AppState>,
-    requester: AuthUser,
+    requester: AuthSession,
     Path(id): Path<i64>,
     AxumJson(payload): AxumJson<UpdateUserDto>,
 ) -> Result<AxumJson<User>, AppError> {
     // Check permission: Admin or Self
-    if !requester.is_admin() && requester.0.id != id {
+    if !requester.can(Permission::ManageUsers) && requester.user.id != id {
         return Err(AppError::Unauthorized("You can only modify your own account".to_string()));
     }
 
     // Role change is Admin Only
-    if payload.role.is_some() && !requester.is_admin() {
+    if payload.role.is_some() && !requester.can(Permission::ManageUsers) {
         return Err(AppError::Unauthorized("Only admins can change roles".to_string()));
     }
 
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    if let Some(username) = payload.username.as_deref() {
+        let errors = validation::username_errors(username);
+        if !errors.is_empty() {
+            return Err(AppError::ValidationFailed(errors));
+        }
+    }
+
+    // Keep at least one admin around, the update is refused when nothing else is left
     let user = repository::update(&state.db, id, payload)
         .await
         .map_err(|e| if e.contains("UNIQUE constraint") {
              AppError::Conflict("Username taken".to_string())
         } else {
              AppError::InternalServerError(e)
+        })?
+        .ok_or_else(|| if before.role == UserRole::Admin {
+            AppError::Conflict("The last admin can't change role".to_string())
+        } else {
+            AppError::NotFound("User not found".to_string())
         })?;
 
+    audit::record(&state.db, &requester.user, AuditAction::Update, AuditEntity::User, Some(id), Some(&before), Some(&user)).await;
+
     Ok(AxumJson(user))
 }
 
@@ -120,23 +246,73 @@ pub async fn delete_user(
     Path(id): Path<i64>,
 ) -> Result<StatusCode, AppError> {
     // Check permission: Admin or Self
-    if !requester.is_admin() && requester.0.id != id {
+    if !requester.can(Permission::ManageUsers) && requester.0.id != id {
         return Err(AppError::Unauthorized("You can only delete your own account".to_string()));
     }
 
-    repository::delete(&state.db, id)
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    // Recorded with the delete so a refused one leaves no entry, and before it since an
+    // account removing itself would otherwise not be a valid actor
+    let mut tx = state.db.begin().await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    audit::record(&mut *tx, &requester.0, AuditAction::Delete, AuditEntity::User, Some(id), Some(&before), audit::NOTHING).await;
+
+    // Keep at least one admin around, the setup token is only made when there are none at startup
+    let deleted = repository::delete(&mut *tx, id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    if !deleted && before.role == UserRole::Admin {
+        return Err(AppError::Conflict("The last admin can't be deleted".to_string()));
+    }
+    if !deleted {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+    tx.commit().await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    // Cascades as well, but don't rely on the foreign key pragma to log the user out
+    crate::orm::sessions::repository::delete_for_user(&state.db, id, None)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    crate::orm::tokens::repository::delete_for_user(&state.db, id)
         .await
         .map_err(AppError::InternalServerError)?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
-pub async fn get_leaderboard(
+pub async fn list_pending_users(
     State(state): State<AppState>,
+    _: Require<perm::ManageUsers>,
 ) -> Result<AxumJson<Vec<User>>, AppError> {
-    let top_users = repository::get_top_listeners(&state.db, 10)
+    let users = repository::find_by_status(&state.db, UserStatus::Pending)
         .await
         .map_err(AppError::InternalServerError)?;
+    Ok(AxumJson(users))
+}
+
+/// Lets a pending account log in, rejecting one is deleting it
+pub async fn approve_user(
+    State(state): State<AppState>,
+    Require(admin, _): Require<perm::ManageUsers>,
+    Path(id): Path<i64>,
+) -> Result<AxumJson<User>, AppError> {
+    let before = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    if before.status != UserStatus::Pending {
+        return Err(AppError::Conflict("User is not waiting for approval".to_string()));
+    }
 
-    Ok(AxumJson(top_users))
+    let user = repository::set_status(&state.db, id, UserStatus::Active)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+    audit::record(&state.db, &admin, AuditAction::Update, AuditEntity::User, Some(id), Some(&before), Some(&user)).await;
+
+    Ok(AxumJson(user))
 }
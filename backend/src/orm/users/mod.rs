@@ -1,6 +1,8 @@
 pub mod models;
 pub mod repository;
 pub mod handlers;
+pub mod passwords;
+pub mod validation;
 
 use axum::Router;
 use axum::routing::{get, post};
@@ -9,10 +11,16 @@ use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
     Router::new()
+        .route("/auth/registration", get(handlers::get_registration_info))
         .route("/auth/register", post(handlers::register))
         .route("/auth/login", post(handlers::login))
+        .route("/auth/guest", post(handlers::start_guest_session))
         .route("/auth/logout", post(handlers::logout))
         .route("/auth/me", get(handlers::get_me))
-        .route("/users/leaderboard", get(handlers::get_leaderboard))
+        .route("/auth/password", post(passwords::change_password))
+        .route("/auth/password/reset", post(passwords::reset_password))
         .route("/users/{id}", post(handlers::update_user).delete(handlers::delete_user))
+        .route("/admin/users/pending", get(handlers::list_pending_users))
+        .route("/admin/users/{id}/approve", post(handlers::approve_user))
+        .route("/admin/users/{id}/password-reset", post(passwords::create_password_reset))
 }
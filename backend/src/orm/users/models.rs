@@ -1,13 +1,100 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[serde(rename_all = "snake_case")]
-pub enum UserRole { User, Admin, }
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum UserRole {
+    #[default]
+    User,
+    /// Tags songs and edits metadata
+    Curator,
+    /// Manages the request queue
+    Dj,
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum UserStatus {
+    #[default]
+    Active,
+    /// Registered while approval was required, can't log in yet
+    Pending,
+}
 
-impl Default for UserRole {
-    fn default() -> Self {
-        Self::User
+/// Who may create an account, from `REGISTRATION_MODE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// Anyone
+    Open,
+    /// Only with an invite code
+    Invite,
+    /// Anyone, but an admin has to approve the account unless an invite code was given
+    Approval,
+    /// Nobody
+    Closed,
+}
+
+impl RegistrationMode {
+    /// Invite only unless configured otherwise, the station is meant for people the admins know
+    pub fn from_env() -> Self {
+        match std::env::var("REGISTRATION_MODE").map(|v| v.trim().to_lowercase()).as_deref() {
+            Ok("open") => RegistrationMode::Open,
+            Ok("approval") => RegistrationMode::Approval,
+            Ok("closed") => RegistrationMode::Closed,
+            Ok("invite") | Err(_) => RegistrationMode::Invite,
+            Ok(other) => {
+                tracing::warn!("Unknown REGISTRATION_MODE '{}', using invite", other);
+                RegistrationMode::Invite
+            }
+        }
+    }
+
+    pub fn accepts_invites(self) -> bool {
+        matches!(self, RegistrationMode::Invite | RegistrationMode::Approval)
+    }
+}
+
+/// Something a role allows, checked by the `Require` extractor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Edit songs, tags, artists, albums and rhythm data
+    EditMetadata,
+    /// Add songs to the library and delete anything from it
+    ManageLibrary,
+    /// Remove requests from the queue and request past the per-user limit
+    ManageQueue,
+    /// Delete chat messages and mute users
+    ModerateChat,
+    /// Station settings and listener diagnostics
+    ManageStation,
+    /// Edit or delete other accounts and change roles
+    ManageUsers,
+    /// See, edit and delete other users' playlists, private ones included
+    ManagePlaylists,
+    /// Read the audit log
+    ViewAuditLog,
+}
+
+impl UserRole {
+    pub fn has(self, permission: Permission) -> bool {
+        match self {
+            UserRole::Admin => true,
+            UserRole::Curator => matches!(permission, Permission::EditMetadata),
+            UserRole::Dj => matches!(permission, Permission::ManageQueue),
+            UserRole::User => false,
+        }
+    }
+}
+
+impl Permission {
+    /// Whether only admins hold it, API tokens then need the admin scope
+    pub fn admin_only(self) -> bool {
+        !UserRole::Curator.has(self) && !UserRole::Dj.has(self)
     }
 }
 
@@ -18,14 +105,35 @@ pub struct User {
     #[serde(skip)]
     pub password_hash: String,
     pub artist_id: Option<i64>,
-    pub role: String,
+    pub role: UserRole,
     pub total_listen_time: i64,
+    pub leaderboard_opt_out: bool,
+    pub status: UserStatus,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateUserDto {
     pub username: String,
     pub password: String,
+    pub invite_code: Option<String>,
+    /// Registers the first admin, see `GET /auth/registration`
+    pub setup_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistrationInfoDto {
+    pub mode: RegistrationMode,
+    /// There is no admin yet, the setup token from the server log registers one
+    pub needs_setup: bool,
+    /// `POST /auth/guest` lets people listen without an account
+    pub guests_enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuestDto {
+    pub id: i64,
+    pub name: String,
+    pub expires_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,7 +145,26 @@ pub struct LoginPayload {
 #[derive(Debug, Deserialize)]
 pub struct UpdateUserDto {
     pub username: Option<String>,
-    pub password: Option<String>,
     pub artist_id: Option<i64>,
-    pub role: Option<String>,
+    pub role: Option<UserRole>,
+    pub leaderboard_opt_out: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordDto {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// A reset code made by an admin, the only time it is shown
+#[derive(Debug, Serialize)]
+pub struct PasswordResetDto {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordDto {
+    pub code: String,
+    pub new_password: String,
 }
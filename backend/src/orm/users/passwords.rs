@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use argon2::{
+    password_hash::{PasswordHash, PasswordVerifier},
+    Argon2,
+};
+use chrono::{Duration, Utc};
+
+use crate::state::AppState;
+use crate::error::AppError;
+use super::models::{ChangePasswordDto, PasswordResetDto, ResetPasswordDto};
+use super::{repository, validation};
+use crate::auth::{hash_token, new_secret_token, perm, AuthSession, ClientInfo, Require};
+use crate::config::PASSWORD_RESET_HOURS;
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::throttle::LoginThrottle;
+
+/// Whether `password` matches a stored Argon2 hash
+pub fn verify(password: &str, password_hash: &str) -> Result<bool, AppError> {
+    let parsed_hash = PasswordHash::new(password_hash)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok())
+}
+
+// Takes a cookie session, an API token can't change the password
+pub async fn change_password(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    client: ClientInfo,
+    Json(payload): Json<ChangePasswordDto>,
+) -> Result<StatusCode, AppError> {
+    // Guessing the current password with a stolen session is throttled like logins
+    let keys = LoginThrottle::keys(client.ip.as_deref(), &auth.user.username);
+    let attempt = state.login_throttle.lock().await
+        .begin_attempt(keys)
+        .map_err(AppError::TooManyRequests)?;
+
+    if !verify(&payload.current_password, &auth.user.password_hash)? {
+        return Err(AppError::CustomForbidden("Current password is wrong".to_string()));
+    }
+    state.login_throttle.lock().await.record_success(attempt);
+
+    let errors = validation::password_errors(&payload.new_password, &auth.user.username);
+    if !errors.is_empty() {
+        return Err(AppError::ValidationFailed(errors));
+    }
+
+    repository::set_password(&state.db, auth.user.id, &payload.new_password)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    // Every other device has to log in again, and tokens made by whoever knew the old password stop working
+    crate::orm::sessions::repository::delete_for_user(&state.db, auth.user.id, Some(auth.session.id))
+        .await
+        .map_err(AppError::InternalServerError)?;
+    crate::orm::tokens::repository::delete_for_user(&state.db, auth.user.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &auth.user, AuditAction::Update, AuditEntity::Password, Some(auth.user.id), audit::NOTHING, audit::NOTHING).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A one-time code for a user who forgot their password, to hand over outside of Wavy
+pub async fn create_password_reset(
+    State(state): State<AppState>,
+    Require(admin, _): Require<perm::ManageUsers>,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, Json<PasswordResetDto>), AppError> {
+    let user = repository::find_by_id(&state.db, id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    let code = new_secret_token();
+    let expires_at = Utc::now() + Duration::hours(PASSWORD_RESET_HOURS);
+
+    repository::create_password_reset(&state.db, user.id, &hash_token(&code), admin.id, expires_at)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    audit::record(&state.db, &admin, AuditAction::Create, AuditEntity::PasswordReset, Some(user.id), audit::NOTHING, audit::NOTHING).await;
+
+    Ok((StatusCode::CREATED, Json(PasswordResetDto { code, expires_at })))
+}
+
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordDto>,
+) -> Result<StatusCode, AppError> {
+    let invalid = || AppError::CustomForbidden("Invalid or expired reset code".to_string());
+    let code_hash = hash_token(payload.code.trim());
+
+    // Checked before the code is used up, so a refused password can be retried
+    let user_id = repository::find_password_reset(&state.db, &code_hash)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or_else(invalid)?;
+    let user = repository::find_by_id(&state.db, user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or_else(invalid)?;
+
+    let errors = validation::password_errors(&payload.new_password, &user.username);
+    if !errors.is_empty() {
+        return Err(AppError::ValidationFailed(errors));
+    }
+
+    repository::redeem_password_reset(&state.db, &code_hash)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or_else(invalid)?;
+
+    repository::set_password(&state.db, user.id, &payload.new_password)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    // Whoever had the account before the reset is logged out and loses its API tokens, and the owner can log in right away
+    crate::orm::sessions::repository::delete_for_user(&state.db, user.id, None)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    crate::orm::tokens::repository::delete_for_user(&state.db, user.id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    state.login_throttle.lock().await.clear_account(&user.username);
+    // Made by whoever holds the code, recorded under the account it was for
+    audit::record(&state.db, &user, AuditAction::Update, AuditEntity::Password, Some(user.id), audit::NOTHING, audit::NOTHING).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,5 +1,6 @@
-use sqlx::SqlitePool;
-use super::models::{User, CreateUserDto, UpdateUserDto};
+use chrono::{DateTime, Utc};
+use sqlx::{SqliteExecutor, SqlitePool};
+use super::models::{User, CreateUserDto, UpdateUserDto, UserRole, UserStatus};
 use argon2::{
     password_hash::{
         rand_core::OsRng,
@@ -7,31 +8,43 @@ use argon2::{
     },
     Argon2
 };
+use std::sync::LazyLock;
 
-pub async fn create(pool: &SqlitePool, dto: CreateUserDto) -> Result<User, String> {
+/// Hash of a random password, verified against when logging in as an unknown user
+pub static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| {
+    let password = SaltString::generate(&mut OsRng);
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_str().as_bytes(), &salt)
+        .expect("Failed to hash the dummy password")
+        .to_string()
+});
+
+pub async fn create(
+    pool: &SqlitePool,
+    dto: &CreateUserDto,
+    role: UserRole,
+    status: UserStatus,
+    invite_id: Option<i64>,
+) -> Result<User, String> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
     let password_hash = argon2.hash_password(dto.password.as_bytes(), &salt)
         .map_err(|e| e.to_string())?
         .to_string();
 
-    let user_count: i64 = sqlx::query_scalar!("SELECT COUNT(*) FROM users")
-        .fetch_one(pool)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let role = if user_count == 0 { "admin" } else { "user" };
-
     let user = sqlx::query_as!(
         User,
         r#"
-        INSERT INTO users (username, password_hash, role)
-        VALUES (?, ?, ?)
-        RETURNING id as "id!", username, password_hash, artist_id, role, total_listen_time
+        INSERT INTO users (username, password_hash, role, status, invite_id)
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING id as "id!", username, password_hash, artist_id, role as "role: UserRole", total_listen_time, leaderboard_opt_out, status as "status: UserStatus"
         "#,
         dto.username,
         password_hash,
-        role
+        role,
+        status,
+        invite_id
     )
     .fetch_one(pool)
     .await
@@ -44,7 +57,7 @@ pub async fn find_by_username(pool: &SqlitePool, username: &str) -> Result<Optio
     let user = sqlx::query_as!(
         User,
         r#"
-        SELECT id as "id!", username, password_hash, artist_id, role, total_listen_time
+        SELECT id as "id!", username, password_hash, artist_id, role as "role: UserRole", total_listen_time, leaderboard_opt_out, status as "status: UserStatus"
         FROM users
         WHERE username = ?
         "#,
@@ -61,7 +74,7 @@ pub async fn find_by_id(pool: &SqlitePool, id: i64) -> Result<Option<User>, Stri
     let user = sqlx::query_as!(
         User,
         r#"
-        SELECT id as "id!", username, password_hash, artist_id, role, total_listen_time
+        SELECT id as "id!", username, password_hash, artist_id, role as "role: UserRole", total_listen_time, leaderboard_opt_out, status as "status: UserStatus"
         FROM users
         WHERE id = ?
         "#,
@@ -74,18 +87,8 @@ pub async fn find_by_id(pool: &SqlitePool, id: i64) -> Result<Option<User>, Stri
     Ok(user)
 }
 
-pub async fn update(pool: &SqlitePool, id: i64, dto: UpdateUserDto) -> Result<User, String> {
-    let mut password_hash = None;
-    if let Some(password) = dto.password {
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        password_hash = Some(
-            argon2.hash_password(password.as_bytes(), &salt)
-                .map_err(|e| e.to_string())?
-                .to_string()
-        );
-    }
-
+/// None when there is no such user, or when it would demote the last admin
+pub async fn update(pool: &SqlitePool, id: i64, dto: UpdateUserDto) -> Result<Option<User>, String> {
     // Since we are using standard SQL builder due to dynamic updates, we can't easily use query_as! 
     // effectively without a lot of boilerplate or a query builder.
     // For simplicity with sqlx and partial updates, we build the query string or use strict logic.
@@ -98,42 +101,49 @@ pub async fn update(pool: &SqlitePool, id: i64, dto: UpdateUserDto) -> Result<Us
         separated.push("username = ");
         separated.push_bind_unseparated(username);
     }
-    if let Some(ph) = password_hash {
-        separated.push("password_hash = ");
-        separated.push_bind_unseparated(ph);
-    }
     if let Some(artist_id) = dto.artist_id {
         separated.push("artist_id = ");
         separated.push_bind_unseparated(artist_id);
     }
+    let demotes = dto.role.is_some_and(|role| role != UserRole::Admin);
     if let Some(role) = dto.role {
         separated.push("role = ");
         separated.push_bind_unseparated(role);
     }
+    if let Some(opt_out) = dto.leaderboard_opt_out {
+        separated.push("leaderboard_opt_out = ");
+        separated.push_bind_unseparated(opt_out);
+    }
 
     qb.push(" WHERE id = ");
     qb.push_bind(id);
-    qb.push(" RETURNING id, username, password_hash, artist_id, role, total_listen_time");
+    if demotes {
+        // In the same statement as the write, so two demotions at once can't both pass
+        qb.push(" AND (role != 'admin' OR (SELECT COUNT(*) FROM users WHERE role = 'admin') > 1)");
+    }
+    qb.push(" RETURNING id, username, password_hash, artist_id, role, total_listen_time, leaderboard_opt_out, status");
 
-    let user = qb.build_query_as::<User>()
+    qb.build_query_as::<User>()
         .fetch_optional(pool)
         .await
-        .map_err(|e| e.to_string())?
-        .ok_or("User not found".to_string())?;
-
-    Ok(user)
+        .map_err(|e| e.to_string())
 }
 
-pub async fn delete(pool: &SqlitePool, id: i64) -> Result<(), String> {
-    sqlx::query!(
-        "DELETE FROM users WHERE id = ?",
+/// False when there is no such user, or when it is the last admin
+pub async fn delete<'e>(db: impl SqliteExecutor<'e>, id: i64) -> Result<bool, String> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM users
+        WHERE id = ?
+            AND (role != 'admin' OR (SELECT COUNT(*) FROM users WHERE role = 'admin') > 1)
+        "#,
         id
     )
-    .execute(pool)
+    .execute(db)
     .await
     .map_err(|e| e.to_string())?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 pub async fn increment_listen_time(pool: &SqlitePool, id: i64, seconds: i64) -> Result<(), String> {
@@ -149,18 +159,122 @@ pub async fn increment_listen_time(pool: &SqlitePool, id: i64, seconds: i64) ->
     Ok(())
 }
 
-pub async fn get_top_listeners(pool: &SqlitePool, limit: i64) -> Result<Vec<User>, String> {
+pub async fn count_admins(pool: &SqlitePool) -> Result<i64, String> {
+    sqlx::query_scalar!("SELECT COUNT(*) FROM users WHERE role = 'admin'")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Users with this status, oldest first
+pub async fn find_by_status(pool: &SqlitePool, status: UserStatus) -> Result<Vec<User>, String> {
     sqlx::query_as!(
         User,
         r#"
-        SELECT id as "id!", username, password_hash, artist_id, role, total_listen_time
+        SELECT id as "id!", username, password_hash, artist_id, role as "role: UserRole", total_listen_time, leaderboard_opt_out, status as "status: UserStatus"
         FROM users
-        ORDER BY total_listen_time DESC
-        LIMIT ?
+        WHERE status = ?
+        ORDER BY id
         "#,
-        limit
+        status
     )
     .fetch_all(pool)
     .await
     .map_err(|e| e.to_string())
 }
+
+pub async fn set_status(pool: &SqlitePool, id: i64, status: UserStatus) -> Result<Option<User>, String> {
+    sqlx::query_as!(
+        User,
+        r#"
+        UPDATE users SET status = ?
+        WHERE id = ?
+        RETURNING id as "id!", username, password_hash, artist_id, role as "role: UserRole", total_listen_time, leaderboard_opt_out, status as "status: UserStatus"
+        "#,
+        status,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn set_password(pool: &SqlitePool, id: i64, password: &str) -> Result<(), String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let argon2 = Argon2::default();
+    let password_hash = argon2.hash_password(password.as_bytes(), &salt)
+        .map_err(|e| e.to_string())?
+        .to_string();
+
+    sqlx::query!("UPDATE users SET password_hash = ? WHERE id = ?", password_hash, id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Stores a reset code for the user, replacing any unused one
+pub async fn create_password_reset(
+    pool: &SqlitePool,
+    user_id: i64,
+    code_hash: &str,
+    created_by: i64,
+    expires_at: DateTime<Utc>,
+) -> Result<(), String> {
+    let now = Utc::now();
+
+    sqlx::query!("DELETE FROM password_resets WHERE user_id = ? AND used_at IS NULL", user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO password_resets (user_id, code_hash, created_by, created_at, expires_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        user_id,
+        code_hash,
+        created_by,
+        now,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Whose a valid, unused reset code is
+pub async fn find_password_reset(pool: &SqlitePool, code_hash: &str) -> Result<Option<i64>, String> {
+    let now = Utc::now();
+
+    sqlx::query_scalar!(
+        "SELECT user_id FROM password_resets WHERE code_hash = ? AND used_at IS NULL AND expires_at > ?",
+        code_hash,
+        now
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Marks a valid reset code used, returns whose it is. In one statement so it works only once.
+pub async fn redeem_password_reset(pool: &SqlitePool, code_hash: &str) -> Result<Option<i64>, String> {
+    let now = Utc::now();
+
+    sqlx::query_scalar!(
+        r#"
+        UPDATE password_resets SET used_at = ?1
+        WHERE code_hash = ?2 AND used_at IS NULL AND expires_at > ?1
+        RETURNING user_id
+        "#,
+        now,
+        code_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use crate::config::{
+    get_breached_passwords_path, PASSWORD_MAX_LENGTH, PASSWORD_MIN_LENGTH, USERNAME_MAX_LENGTH, USERNAME_MIN_LENGTH,
+};
+
+// Names that could pass for the station or its staff
+const RESERVED_USERNAMES: &[&str] = &[
+    "admin", "administrator", "root", "system", "wavy", "radio", "station", "dj", "moderator", "mod",
+    "staff", "support", "api", "guest", "anonymous", "null", "undefined", "me",
+];
+
+// Refused even without a breached password list
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "password1", "password123", "12345678", "123456789", "1234567890", "qwertyuiop", "qwerty123",
+    "iloveyou", "sunshine", "football", "baseball", "princess", "11111111", "00000000", "abcdefgh", "letmein1",
+];
+
+/// Lowercased passwords from the breached password file, empty without one
+static BREACHED_PASSWORDS: LazyLock<HashSet<String>> = LazyLock::new(|| {
+    let path = get_breached_passwords_path();
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            let passwords: HashSet<String> = contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect();
+            tracing::info!("Loaded {} breached passwords from {}", passwords.len(), path.display());
+            passwords
+        }
+        Err(e) => {
+            tracing::info!("No breached password list at {} ({}), only common passwords are refused", path.display(), e);
+            HashSet::new()
+        }
+    }
+});
+
+/// Problems with a username, empty if it's fine
+pub fn username_errors(username: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    let length = username.chars().count();
+    if !(USERNAME_MIN_LENGTH..=USERNAME_MAX_LENGTH).contains(&length) {
+        errors.push(format!(
+            "username must be between {} and {} characters",
+            USERNAME_MIN_LENGTH, USERNAME_MAX_LENGTH
+        ));
+    }
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+        errors.push("username can only contain letters, digits, '_', '-' and '.'".to_string());
+    }
+    if !username.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        errors.push("username must start with a letter or digit".to_string());
+    }
+    if RESERVED_USERNAMES.contains(&username.to_lowercase().as_str()) {
+        errors.push("username is reserved".to_string());
+    }
+
+    errors
+}
+
+/// Problems with a new password for `username`, empty if it's fine
+pub fn password_errors(password: &str, username: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    let length = password.chars().count();
+    if length < PASSWORD_MIN_LENGTH {
+        errors.push(format!("password must be at least {} characters", PASSWORD_MIN_LENGTH));
+    }
+    // Hashing is slow enough that huge passwords would be a cheap way to load the server
+    if length > PASSWORD_MAX_LENGTH {
+        errors.push(format!("password must be at most {} characters", PASSWORD_MAX_LENGTH));
+    }
+
+    let lowercase = password.to_lowercase();
+    if lowercase == username.to_lowercase() {
+        errors.push("password can't be the username".to_string());
+    } else if COMMON_PASSWORDS.contains(&lowercase.as_str()) || BREACHED_PASSWORDS.contains(&lowercase) {
+        errors.push("password is too common, it appears in known password leaks".to_string());
+    }
+
+    errors
+}
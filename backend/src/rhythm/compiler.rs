@@ -1,37 +1,249 @@
 use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
+use std::fmt;
+use std::io::{Read, Write};
 use std::path::Path;
 use flate2::Compression;
+use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use serde_json::Value;
 
-pub fn compile(input_path: &str) -> Result<String, Box<dyn Error>> {
-    let path = Path::new(input_path);
+use super::model::{RhythmTrack, RHYTHM_FORMAT_VERSION};
+use super::validate::{self, RhythmError};
 
-    // Determine output path and convert to string early for the return value
-    let output_path = path.with_extension("dat");
+// Compiled layout: MAGIC | container version (u8) | payload length (u32 LE) | CRC32 of payload (u32 LE) | payload
+// The payload is the track as MessagePack, zlib compressed. Data compiled before the
+// header existed is a bare payload and is still accepted when decompiling.
+pub const MAGIC: &[u8; 4] = b"WRHY";
+const CONTAINER_VERSION: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 4 + 4;
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// The source doesn't pass validation
+    Invalid(Vec<RhythmError>),
+    /// The compiled data is truncated, fails its checksum or doesn't decode
+    Corrupted(String),
+    Encode(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Invalid(errors) => {
+                let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            CompileError::Corrupted(msg) => write!(f, "Corrupted rhythm data: {}", msg),
+            CompileError::Encode(msg) => write!(f, "Failed to encode rhythm data: {}", msg),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+/// Validates a JSON rhythm source and compiles it
+pub fn compile_bytes(source: &[u8], duration_ms: Option<u64>) -> Result<Vec<u8>, CompileError> {
+    let source = std::str::from_utf8(source).map_err(|e| {
+        CompileError::Invalid(vec![RhythmError { line: 1, column: 1, message: format!("not valid UTF-8: {}", e) }])
+    })?;
+
+    let track = validate::parse(source, duration_ms).map_err(CompileError::Invalid)?;
+    encode(&track)
+}
+
+/// Verifies and decodes compiled rhythm data
+pub fn decompile_bytes(data: &[u8]) -> Result<RhythmTrack, CompileError> {
+    let payload = if data.starts_with(MAGIC) {
+        unwrap_container(data)?
+    } else {
+        data
+    };
+
+    let mut packed = Vec::new();
+    ZlibDecoder::new(payload)
+        .read_to_end(&mut packed)
+        .map_err(|e| CompileError::Corrupted(e.to_string()))?;
+
+    let value: Value = rmp_serde::from_slice(&packed).map_err(|e| CompileError::Corrupted(e.to_string()))?;
+    let value = match value {
+        Value::Array(events) => serde_json::json!({
+            "version": RHYTHM_FORMAT_VERSION,
+            "events": events,
+        }),
+        other => other,
+    };
+
+    serde_json::from_value(value).map_err(|e| CompileError::Corrupted(e.to_string()))
+}
+
+/// Pipeline: RhythmTrack -> MessagePack -> Zlib -> container with checksum
+pub fn encode(track: &RhythmTrack) -> Result<Vec<u8>, CompileError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+    // Structs as maps, so clients see the same field names as the JSON source
+    let packed = rmp_serde::to_vec_named(track).map_err(|e| CompileError::Encode(e.to_string()))?;
+    encoder.write_all(&packed).map_err(|e| CompileError::Encode(e.to_string()))?;
+    let payload = encoder.finish().map_err(|e| CompileError::Encode(e.to_string()))?;
+
+    let mut crc = flate2::Crc::new();
+    crc.update(&payload);
+
+    let mut output = Vec::with_capacity(HEADER_LEN + payload.len());
+    output.extend_from_slice(MAGIC);
+    output.push(CONTAINER_VERSION);
+    output.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    output.extend_from_slice(&crc.sum().to_le_bytes());
+    output.extend_from_slice(&payload);
+
+    Ok(output)
+}
+
+fn unwrap_container(data: &[u8]) -> Result<&[u8], CompileError> {
+    if data.len() < HEADER_LEN {
+        return Err(CompileError::Corrupted("truncated header".to_string()));
+    }
+
+    let version = data[4];
+    if version != CONTAINER_VERSION {
+        return Err(CompileError::Corrupted(format!("unsupported container version {}", version)));
+    }
+
+    let length = u32::from_le_bytes([data[5], data[6], data[7], data[8]]) as usize;
+    let checksum = u32::from_le_bytes([data[9], data[10], data[11], data[12]]);
+    let payload = &data[HEADER_LEN..];
+
+    if payload.len() != length {
+        return Err(CompileError::Corrupted(format!(
+            "expected {} bytes of payload, found {}",
+            length,
+            payload.len()
+        )));
+    }
+
+    let mut crc = flate2::Crc::new();
+    crc.update(payload);
+    if crc.sum() != checksum {
+        return Err(CompileError::Corrupted("checksum mismatch".to_string()));
+    }
+
+    Ok(payload)
+}
+
+/// Compiles a JSON file. Writes next to the input with a `.dat` extension unless an output is given.
+pub fn compile(input_path: &str, output_path: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let output_path = output_path
+        .map(Path::new)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| Path::new(input_path).with_extension("dat"));
     let output_str = output_path.to_str()
         .ok_or("Invalid output path encoding")?
         .to_string();
 
-    // Read the JSON
-    let file = File::open(input_path)
+    let source = std::fs::read(input_path)
         .map_err(|e| format!("Could not find file '{}': {}", input_path, e))?;
-    let reader = BufReader::new(file);
-    let json_data: Value = serde_json::from_reader(reader)?;
 
-    // Prepare output file
-    let output_file = File::create(&output_path)?;
+    std::fs::write(&output_path, compile_bytes(&source, None)?)?;
+
+    Ok(output_str)
+}
 
-    // Pipeline: JSON -> MessagePack -> Zlib -> File
-    let mut encoder = ZlibEncoder::new(output_file, Compression::default());
-    let mut serializer = rmp_serde::Serializer::new(&mut encoder);
+/// Decompiles a `.dat` file. Writes next to the input with a `.json` extension unless an output is given.
+pub fn decompile(input_path: &str, output_path: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let output_path = output_path
+        .map(Path::new)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| Path::new(input_path).with_extension("json"));
+    let output_str = output_path.to_str()
+        .ok_or("Invalid output path encoding")?
+        .to_string();
 
-    serde::Serialize::serialize(&json_data, &mut serializer)?;
+    let data = std::fs::read(input_path)
+        .map_err(|e| format!("Could not find file '{}': {}", input_path, e))?;
 
-    // Finalize the compression stream
-    encoder.finish()?;
+    let track = decompile_bytes(&data)?;
+    std::fs::write(&output_path, serde_json::to_vec_pretty(&track)?)?;
 
     Ok(output_str)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r##"{
+        "version": 1,
+        "bpm": 128,
+        "offset_ms": 20,
+        "events": [
+            { "time": 0, "identifier": "downbeat" },
+            { "time": 468, "identifier": "flash", "color": "#ff00aa", "intensity": 0.5 },
+            { "time": 937, "identifier": "text", "text": "ñandú 🎵", "duration_ms": 800 }
+        ]
+    }"##;
+
+    fn compiled() -> Vec<u8> {
+        compile_bytes(SOURCE.as_bytes(), None).unwrap()
+    }
+
+    fn assert_corrupted(data: &[u8]) {
+        match decompile_bytes(data) {
+            Err(CompileError::Corrupted(_)) => {}
+            other => panic!("expected corrupted data, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = compiled();
+        assert_eq!(&data[..4], MAGIC);
+        assert_eq!(data[4], CONTAINER_VERSION);
+
+        let track = decompile_bytes(&data).unwrap();
+        assert_eq!(track, validate::parse(SOURCE, None).unwrap());
+        assert_eq!(encode(&track).unwrap(), data);
+    }
+
+    #[test]
+    fn header_only_data_is_rejected() {
+        assert_corrupted(&compiled()[..HEADER_LEN - 1]);
+    }
+
+    #[test]
+    fn truncated_payload_is_rejected() {
+        let data = compiled();
+        assert_corrupted(&data[..data.len() - 1]);
+        assert_corrupted(&data[..HEADER_LEN]);
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        let mut data = compiled();
+        data[9] ^= 0x01;
+        assert_corrupted(&data);
+
+        // A damaged payload with the right length fails the checksum too
+        let mut data = compiled();
+        let last = data.len() - 1;
+        data[last] ^= 0x80;
+        assert_corrupted(&data);
+    }
+
+    #[test]
+    fn bad_magic_or_version_is_rejected() {
+        let mut data = compiled();
+        data[4] = CONTAINER_VERSION + 1;
+        assert_corrupted(&data);
+
+        // Without the magic it is read as a headerless payload, which the header doesn't decode as
+        let mut data = compiled();
+        data[0] = b'X';
+        assert_corrupted(&data);
+    }
+
+    #[test]
+    fn headerless_payload_is_accepted() {
+        let data = compiled();
+        let track = decompile_bytes(&data[HEADER_LEN..]).unwrap();
+        assert_eq!(track.events.len(), 3);
+    }
+}
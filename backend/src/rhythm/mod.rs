@@ -1 +1,4 @@
-mod compiler;
\ No newline at end of file
+pub mod compiler;
+pub mod model;
+pub mod store;
+pub mod validate;
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Current version of the rhythm format. Bump it whenever the event schema changes.
+pub const RHYTHM_FORMAT_VERSION: u32 = 1;
+
+/// A song's rhythm data, as authored in JSON and compiled to `.dat`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RhythmTrack {
+    pub version: u32,
+    /// Tempo the events were authored against, informative for editors and visuals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    /// Shift applied to every event time, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_ms: Option<i64>,
+    pub events: Vec<RhythmEvent>,
+}
+
+impl RhythmTrack {
+    pub fn new(bpm: Option<f64>, events: Vec<RhythmEvent>) -> Self {
+        Self {
+            version: RHYTHM_FORMAT_VERSION,
+            bpm,
+            offset_ms: None,
+            events,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RhythmEvent {
+    /// Milliseconds since the start of the song
+    pub time: u64,
+    #[serde(flatten)]
+    pub kind: RhythmEventKind,
+}
+
+/// What happens at an event. The `identifier` field selects the kind, any payload sits next to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "identifier", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RhythmEventKind {
+    Whistle,
+    Beat,
+    Downbeat,
+    Onset,
+    Flash {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        intensity: Option<f32>,
+    },
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_ms: Option<u64>,
+    },
+}
+
+impl RhythmEventKind {
+    pub const IDENTIFIERS: &'static [&'static str] = &["whistle", "beat", "downbeat", "onset", "flash", "text"];
+}
@@ -0,0 +1,57 @@
+use super::model::RhythmTrack;
+
+// Per-song rhythm data lives in the rhythm directory as `{song_id}.json` (source)
+// and `{song_id}.dat` (compiled, what gets sent to clients)
+
+/// Saves the rhythm source of a song as given, along with its compiled form
+pub async fn save(song_id: i64, source: &[u8], compiled: &[u8]) -> Result<(), String> {
+    tokio::fs::write(crate::config::get_rhythm_source_path(song_id), source)
+        .await
+        .map_err(|e| format!("Failed to save rhythm file: {}", e))?;
+
+    tokio::fs::write(crate::config::get_rhythm_data_path(song_id), compiled)
+        .await
+        .map_err(|e| format!("Failed to save compiled rhythm file: {}", e))
+}
+
+/// Serializes and compiles a track, then saves both
+pub async fn save_track(song_id: i64, track: &RhythmTrack) -> Result<(), String> {
+    let source = serde_json::to_vec_pretty(track).map_err(|e| e.to_string())?;
+    let compiled = super::compiler::encode(track).map_err(|e| e.to_string())?;
+    save(song_id, &source, &compiled).await
+}
+
+/// Loads the rhythm of a song from its source, or decompiles it when only the `.dat` is around
+pub async fn load(song_id: i64) -> Result<Option<RhythmTrack>, String> {
+    match tokio::fs::read_to_string(crate::config::get_rhythm_source_path(song_id)).await {
+        Ok(source) => {
+            return super::validate::parse(&source, None).map(Some).map_err(|errors| {
+                let details: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                format!("Invalid rhythm source: {}", details.join("; "))
+            });
+        }
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.to_string()),
+        Err(_) => {}
+    }
+
+    match tokio::fs::read(crate::config::get_rhythm_data_path(song_id)).await {
+        Ok(data) => super::compiler::decompile_bytes(&data)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Reads the compiled rhythm data of a song, `None` if it has none.
+/// Blocking, used by the loader thread.
+pub fn read_compiled(song_id: i64) -> Option<Vec<u8>> {
+    std::fs::read(crate::config::get_rhythm_data_path(song_id)).ok()
+}
+
+/// Removes both the source and the compiled data. Returns whether anything existed.
+pub async fn delete(song_id: i64) -> bool {
+    let source = tokio::fs::remove_file(crate::config::get_rhythm_source_path(song_id)).await.is_ok();
+    let compiled = tokio::fs::remove_file(crate::config::get_rhythm_data_path(song_id)).await.is_ok();
+    source || compiled
+}
@@ -0,0 +1,301 @@
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::fmt;
+
+use super::model::{RhythmEvent, RhythmEventKind, RhythmTrack, RHYTHM_FORMAT_VERSION};
+
+/// A problem found in a rhythm source, pointing at the line and column it comes from
+#[derive(Debug, Clone)]
+pub struct RhythmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RhythmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+// Header with the events kept as raw JSON so each one can be located in the source
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTrack<'a> {
+    #[serde(borrow)]
+    version: &'a RawValue,
+    #[serde(default)]
+    bpm: Option<f64>,
+    #[serde(default)]
+    offset_ms: Option<i64>,
+    #[serde(borrow)]
+    events: Vec<&'a RawValue>,
+}
+
+/// Parses and validates a rhythm source.
+///
+/// Accepts the versioned format (`{ "version": 1, "events": [...] }`) and the legacy bare
+/// list of events. Every problem found is reported, not just the first one.
+/// With `duration_ms`, events past the end of the song are rejected too.
+pub fn parse(source: &str, duration_ms: Option<u64>) -> Result<RhythmTrack, Vec<RhythmError>> {
+    let is_legacy = source.trim_start().starts_with('[');
+
+    let syntax_error = |e: serde_json::Error| {
+        let (line, column) = position_at(source, offset_in(source, e.line(), e.column()));
+        vec![RhythmError { line, column, message: describe(&e) }]
+    };
+    let (raw_version, bpm, offset_ms, raw_events) = if is_legacy {
+        let events = serde_json::from_str::<Vec<&RawValue>>(source).map_err(syntax_error)?;
+        (None, None, None, events)
+    } else {
+        let track = serde_json::from_str::<RawTrack>(source).map_err(syntax_error)?;
+        (Some(track.version), track.bpm, track.offset_ms, track.events)
+    };
+
+    let mut errors = Vec::new();
+
+    // The legacy format has no version, it is the first one
+    let version = match raw_version {
+        None => RHYTHM_FORMAT_VERSION,
+        Some(value) => match value.get().parse::<u32>() {
+            Ok(version) if (1..=RHYTHM_FORMAT_VERSION).contains(&version) => version,
+            _ => {
+                let (line, column) = position_of(source, value.get());
+                errors.push(RhythmError {
+                    line,
+                    column,
+                    message: format!("unsupported version {} (latest is {})", value.get(), RHYTHM_FORMAT_VERSION),
+                });
+                RHYTHM_FORMAT_VERSION
+            }
+        },
+    };
+
+    if let Some(bpm) = bpm
+        && !(bpm > 0.0 && bpm.is_finite())
+    {
+        errors.push(RhythmError { line: 1, column: 1, message: format!("bpm must be positive, got {}", bpm) });
+    }
+
+    let mut events = Vec::with_capacity(raw_events.len());
+    let mut previous_time: Option<u64> = None;
+
+    for (index, raw_event) in raw_events.iter().enumerate() {
+        let (line, column) = position_of(source, raw_event.get());
+        let error = |message: String| RhythmError { line, column, message };
+
+        let event = match parse_event(raw_event.get()) {
+            Ok(event) => event,
+            Err(EventError::Message(message)) => {
+                errors.push(error(format!("event #{}: {}", index, message)));
+                continue;
+            }
+            Err(EventError::Serde(e)) => {
+                // Positions inside the event are relative to its first character
+                let event_offset = offset_of(source, raw_event.get());
+                let (line, column) = position_at(source, event_offset + offset_in(raw_event.get(), e.line(), e.column()));
+                errors.push(RhythmError { line, column, message: format!("event #{}: {}", index, describe(&e)) });
+                continue;
+            }
+        };
+
+        if let Some(previous) = previous_time
+            && event.time < previous
+        {
+            errors.push(error(format!(
+                "event #{} at {} ms comes before the previous event at {} ms, events must be sorted by time",
+                index, event.time, previous
+            )));
+        }
+        previous_time = Some(event.time);
+
+        let time = event.time as i64 + offset_ms.unwrap_or(0);
+        if time < 0 {
+            errors.push(error(format!("event #{} falls before the start of the song once offset ({} ms)", index, time)));
+        }
+        if let Some(duration) = duration_ms
+            && time > duration as i64
+        {
+            errors.push(error(format!(
+                "event #{} at {} ms is past the end of the song ({} ms)",
+                index, time, duration
+            )));
+        }
+
+        events.push(event);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(RhythmTrack {
+        version,
+        bpm,
+        offset_ms,
+        events,
+    })
+}
+
+enum EventError {
+    Message(String),
+    Serde(serde_json::Error),
+}
+
+// Checks the common fields by hand for readable messages, then lets serde check the payload
+fn parse_event(raw: &str) -> Result<RhythmEvent, EventError> {
+    let value: Value = serde_json::from_str(raw).map_err(EventError::Serde)?;
+
+    let object = value
+        .as_object()
+        .ok_or(EventError::Message("must be an object with `time` and `identifier`".to_string()))?;
+
+    match object.get("time") {
+        None => return Err(EventError::Message("missing `time`".to_string())),
+        Some(time) if time.as_u64().is_none() => {
+            return Err(EventError::Message(format!(
+                "`time` must be a non-negative integer of milliseconds, got {}",
+                time
+            )));
+        }
+        _ => {}
+    }
+
+    match object.get("identifier").map(|i| i.as_str()) {
+        None => return Err(EventError::Message("missing `identifier`".to_string())),
+        Some(None) => return Err(EventError::Message("`identifier` must be a string".to_string())),
+        Some(Some(identifier)) if !RhythmEventKind::IDENTIFIERS.contains(&identifier) => {
+            return Err(EventError::Message(format!(
+                "unknown identifier `{}`, expected one of: {}",
+                identifier,
+                RhythmEventKind::IDENTIFIERS.join(", ")
+            )));
+        }
+        _ => {}
+    }
+
+    serde_json::from_str::<RhythmEvent>(raw).map_err(EventError::Serde)
+}
+
+// serde_json appends its own position to the message, we already report ours
+fn describe(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    let suffix = format!(" at line {} column {}", error.line(), error.column());
+    message.strip_suffix(&suffix).unwrap_or(&message).to_string()
+}
+
+// Byte offset of `fragment`, which must be a slice of `source`
+fn offset_of(source: &str, fragment: &str) -> usize {
+    (fragment.as_ptr() as usize).saturating_sub(source.as_ptr() as usize).min(source.len())
+}
+
+/// 1-based line and column of `fragment`, which must be a slice of `source`
+fn position_of(source: &str, fragment: &str) -> (usize, usize) {
+    position_at(source, offset_of(source, fragment))
+}
+
+/// 1-based line and column of a byte offset, the column counted in characters as editors show it
+fn position_at(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+// Byte offset of a serde_json position, whose column counts bytes
+fn offset_in(text: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = text.split_inclusive('\n').take(line.saturating_sub(1)).map(str::len).sum();
+    (line_start + column.saturating_sub(1)).min(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The only error of parsing `source`, as line, column and message
+    fn single_error(source: &str, duration_ms: Option<u64>) -> (usize, usize, String) {
+        let errors = parse(source, duration_ms).expect_err("the source should be rejected");
+        assert_eq!(errors.len(), 1, "expected one error, got {:?}", errors);
+        let error = &errors[0];
+        (error.line, error.column, error.message.clone())
+    }
+
+    #[test]
+    fn valid_track() {
+        let source = r#"{ "version": 1, "bpm": 120, "events": [
+            { "time": 0, "identifier": "beat" },
+            { "time": 500, "identifier": "text", "text": "hi" }
+        ] }"#;
+        let track = parse(source, Some(1000)).unwrap();
+        assert_eq!(track.events.len(), 2);
+        assert_eq!(track.bpm, Some(120.0));
+    }
+
+    #[test]
+    fn non_numeric_time() {
+        let source = "{\n  \"version\": 1,\n  \"events\": [\n    { \"time\": 0, \"identifier\": \"beat\" },\n    { \"time\": \"soon\", \"identifier\": \"beat\" }\n  ]\n}";
+        let (line, column, message) = single_error(source, None);
+        assert_eq!((line, column), (5, 5));
+        assert!(message.starts_with("event #1: `time` must be"), "{}", message);
+    }
+
+    #[test]
+    fn events_out_of_order() {
+        let source = "[\n  { \"time\": 500, \"identifier\": \"beat\" },\n  { \"time\": 100, \"identifier\": \"beat\" }\n]";
+        let (line, column, message) = single_error(source, None);
+        assert_eq!((line, column), (3, 3));
+        assert!(message.contains("comes before the previous event"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_identifier() {
+        let source = "[\n  { \"time\": 0, \"identifier\": \"beat\" }, { \"time\": 10, \"identifier\": \"boom\" }\n]";
+        let (line, column, message) = single_error(source, None);
+        assert_eq!((line, column), (2, 40));
+        assert!(message.contains("unknown identifier `boom`"), "{}", message);
+    }
+
+    #[test]
+    fn event_past_the_end() {
+        let source = "{ \"version\": 1, \"offset_ms\": 100, \"events\": [\n  { \"time\": 950, \"identifier\": \"beat\" }\n] }";
+        let (line, column, message) = single_error(source, Some(1000));
+        assert_eq!((line, column), (2, 3));
+        assert!(message.contains("past the end of the song"), "{}", message);
+        assert!(parse(source, Some(1050)).is_ok());
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let source = "{\n  \"version\": 2,\n  \"events\": []\n}";
+        let (line, column, message) = single_error(source, None);
+        assert_eq!((line, column), (2, 14));
+        assert!(message.starts_with("unsupported version 2"), "{}", message);
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        // 'ñ', 'ú' and the note take 2, 2 and 4 bytes but are one column each
+        let source = "[\n  { \"time\": 0, \"identifier\": \"text\", \"text\": \"ñandú 🎵\" }, { \"time\": 1, \"identifier\": \"boom\" }\n]";
+        let (line, column, _) = single_error(source, None);
+        assert_eq!((line, column), (2, 59));
+
+        // Errors serde finds inside an event too, it notices unknown payload fields at the closing brace
+        let source = "[\n  { \"time\": 0, \"identifier\": \"text\", \"text\": \"ñandú 🎵\", \"size\": 3 }\n]";
+        let (line, column, message) = single_error(source, None);
+        assert_eq!((line, column), (2, 67));
+        assert!(message.contains("unknown field `size`"), "{}", message);
+    }
+
+    #[test]
+    fn syntax_error_after_multibyte_line() {
+        let source = "{\n  \"version\": 1, \"events\": [ { \"time\": 0, \"identifier\": \"text\", \"text\": \"ñandú\" } ]\n  oops\n}";
+        let (line, column, _) = single_error(source, None);
+        assert_eq!((line, column), (3, 3));
+    }
+}
@@ -1,41 +1,119 @@
 use axum::extract::FromRef;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::{Duration, Instant};
 use crate::orm::songs::models::Song;
+use crate::orm::chat::models::{ChatMessage, SongReaction};
+use crate::orm::tags::models::TagInfo;
+use crate::rhythm::model::RhythmEvent;
+use crate::analysis::AnalysisQueue;
+use crate::throttle::LoginThrottle;
+use crate::streaming::presence::ListenTime;
 use sqlx::SqlitePool;
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, Mutex, RwLock};
 use axum_extra::extract::cookie::Key;
+use uuid::Uuid;
 
-/// Information about a connected listener
+/// Information about one stream connection. A user listening on several devices has one per device.
 #[derive(Clone, Serialize, Debug)]
 pub struct Listener {
+    pub connection_id: Uuid,
+    /// Negative for guests
     pub user_id: i64,
     pub username: String,
+    /// Guests get no listen time, history or leaderboard credit
+    pub is_guest: bool,
     pub connected_at: DateTime<Utc>,
+    /// Last heartbeat, ping with a position or close of a WebSocket tied to this connection.
+    /// Presence follows the stream itself, this only backs it up.
     pub last_heartbeat: DateTime<Utc>,
+    #[serde(skip)]
+    pub activity: Arc<ConnectionActivity>,
     /// The frame index when this listener connected
     pub start_frame_index: u64,
     /// Duration of burst buffer sent to this listener (in milliseconds)
     pub burst_buffer_ms: u64,
     /// The absolute server duration position when this listener connected (precise)
     pub start_total_duration_micros: u128,
-    /// Last time the listener's progress was saved to the database
-    pub last_saved_at: DateTime<Utc>,
+    /// Latest desync measurements, oldest first
+    pub desync_history: VecDeque<DesyncSample>,
+    /// Open WebSockets that named or heartbeated this connection, it isn't idle while there are any
+    #[serde(skip)]
+    pub open_sockets: usize,
 }
 
 impl Listener {
-    /// Check if listener hasn't sent a heartbeat in the specified duration
-    pub fn is_stale(&self, timeout_seconds: i64) -> bool {
-        let now = Utc::now();
-        let elapsed = now.signed_duration_since(self.last_heartbeat);
-        elapsed.num_seconds() > timeout_seconds
+    /// Station position that matches position 0 of the client's audio element (the start of the burst buffer)
+    pub fn client_base_micros(&self) -> u128 {
+        let burst_micros = (self.burst_buffer_ms as u128) * 1_000;
+        self.start_total_duration_micros.saturating_sub(burst_micros)
+    }
+
+    /// Neither audio went out nor a heartbeat came in for `timeout`. A connection that lost its
+    /// network without closing stays open until TCP gives up, often many minutes later.
+    pub fn is_idle(&self, now: DateTime<Utc>, timeout: chrono::Duration) -> bool {
+        if self.open_sockets > 0 {
+            return false;
+        }
+        let last_sent_ms = self.activity.last_sent_ms.load(Ordering::Relaxed);
+        let last_active_ms = last_sent_ms.max(self.last_heartbeat.timestamp_millis());
+        now.timestamp_millis() - last_active_ms > timeout.num_milliseconds()
+    }
+
+    pub fn record_desync(&mut self, sample: DesyncSample) {
+        if self.desync_history.len() >= crate::config::DESYNC_HISTORY_LEN {
+            self.desync_history.pop_front();
+        }
+        self.desync_history.push_back(sample);
     }
 }
 
+/// A stream connection kept alive by a WebSocket, the same id after a reconnect is another connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SocketTie {
+    pub connection_id: Uuid,
+    pub connected_at: DateTime<Utc>,
+}
+
+/// Shared by a connection's entry and its stream body
+#[derive(Debug, Default)]
+pub struct ConnectionActivity {
+    /// Unix ms of the last audio handed to the connection
+    pub last_sent_ms: AtomicI64,
+    /// Set when the connection was ended for being idle, its stream body then ends too
+    pub closed: AtomicBool,
+}
+
+/// Listen time of a user across all of their connections, so concurrent devices count once
+#[derive(Clone, Copy, Debug)]
+pub struct ListenCredit {
+    /// Last time the user's listen time was saved to the database
+    pub last_saved_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DesyncSource {
+    /// HTTP heartbeat, includes the request latency
+    Heartbeat,
+    /// WebSocket ping, corrected with the measured round trip
+    Ping,
+}
+
+/// One measurement of how far a listener's playback is from the station
+#[derive(Clone, Serialize, Debug)]
+pub struct DesyncSample {
+    pub measured_at: DateTime<Utc>,
+    /// Station position minus client position, positive when the client is behind
+    pub desync_ms: i64,
+    pub rtt_ms: Option<f64>,
+    pub source: DesyncSource,
+}
+
 /// Tracks the server's current playback position
 #[derive(Clone, Debug)]
 pub struct ServerPlaybackPosition {
@@ -45,6 +123,17 @@ pub struct ServerPlaybackPosition {
     pub server_start_time: DateTime<Utc>,
     /// Total duration of audio played so far (in microseconds for precision)
     pub total_duration_micros: u128,
+    /// When `total_duration_micros` was last advanced
+    pub updated_at: Instant,
+}
+
+impl ServerPlaybackPosition {
+    /// Station position right now, interpolated between frames.
+    /// Capped to a second past the last frame so a stalled loader doesn't run the clock ahead.
+    pub fn now_micros(&self) -> u128 {
+        let elapsed = self.updated_at.elapsed().min(Duration::from_secs(1));
+        self.total_duration_micros + elapsed.as_micros()
+    }
 }
 
 impl Default for ServerPlaybackPosition {
@@ -53,6 +142,7 @@ impl Default for ServerPlaybackPosition {
             current_frame_index: 0,
             server_start_time: Utc::now(),
             total_duration_micros: 0,
+            updated_at: Instant::now(),
         }
     }
 }
@@ -69,16 +159,140 @@ pub struct CurrentSong {
     #[serde(skip)]
     pub started_at_micros: u128, // High precision tracking
     pub rhythm_data: Option<String>, // Base64 encoded compiled rhythm data
+    #[serde(skip)]
+    pub play_id: Option<i64>, // Row in play_history, listen time is logged against it
+}
+
+/// A rhythm event placed on the station timeline
+#[derive(Clone, Serialize, Debug)]
+pub struct ScheduledRhythmEvent {
+    /// Increasing across the whole server, lets clients and sockets drop duplicates
+    pub seq: u64,
+    pub song_id: i64,
+    /// When the event fires, in the same timeline as `total_duration_micros`
+    pub at_micros: u64,
+    #[serde(flatten)]
+    pub event: RhythmEvent,
+}
+
+/// A song a listener asked to play next
+#[derive(Clone, Serialize, Debug)]
+pub struct SongRequest {
+    pub song_id: i64,
+    pub title: String,
+    pub requested_by: i64,
+    pub requested_by_name: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Runtime configurable behaviour of the station
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct StationSettings {
+    /// Only songs within this BPM range are picked by the loader
+    pub min_bpm: Option<f64>,
+    pub max_bpm: Option<f64>,
+    /// Down-weights disliked songs when shuffling, 0 picks every song with the same chance
+    pub dislike_weight: f64,
+    /// Lets people listen without an account
+    pub guests_enabled: bool,
+    /// Guests listening at once
+    pub max_guests: usize,
+    /// Plays this playlist in order instead of shuffling the library
+    pub playlist_id: Option<i64>,
+    /// Lets every user play songs from `/songs/{id}/audio`, curators and admins always can
+    pub on_demand_enabled: bool,
+}
+
+impl StationSettings {
+    pub fn from_env() -> Self {
+        let (min_bpm, max_bpm) = crate::config::get_station_bpm_range();
+        Self {
+            min_bpm,
+            max_bpm,
+            dislike_weight: crate::config::get_station_dislike_weight(),
+            guests_enabled: crate::config::get_guests_enabled(),
+            max_guests: crate::config::get_max_guests(),
+            playlist_id: None,
+            on_demand_enabled: crate::config::get_on_demand_enabled(),
+        }
+    }
+
+    /// Relative chance of a song being picked early in the shuffle
+    pub fn pick_weight(&self, dislikes: i64) -> f64 {
+        1.0 / (1.0 + self.dislike_weight * dislikes.max(0) as f64)
+    }
+
+    /// Whether a song with this tempo can be picked. Songs that haven't been analyzed yet always can.
+    pub fn accepts_bpm(&self, bpm: Option<f64>) -> bool {
+        match bpm {
+            Some(bpm) => {
+                self.min_bpm.is_none_or(|min| bpm >= min) && self.max_bpm.is_none_or(|max| bpm <= max)
+            }
+            None => true,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct StationData {
-    /// Active stream listeners tracked by their User ID
-    pub listeners: HashMap<i64, Listener>,
+    /// Active stream connections by connection id
+    pub listeners: HashMap<Uuid, Listener>,
+    /// Listen time bookkeeping of users with at least one connection, guests excluded
+    pub listen_credit: HashMap<i64, ListenCredit>,
     /// Server's current playback position
     pub playback_position: ServerPlaybackPosition,
     /// Information about the currently playing song
     pub current_song: Option<CurrentSong>,
+    /// Listen time earned while the current song's play is being recorded, saved with the play once it is
+    pub unrecorded_listen: Vec<ListenTime>,
+    /// Song selection criteria and toggles
+    pub settings: StationSettings,
+    /// Rhythm events already pushed to clients that haven't fired yet, replayed to late joiners
+    pub upcoming_rhythm: VecDeque<ScheduledRhythmEvent>,
+    /// Songs requested by listeners, played before the shuffled library
+    pub song_requests: VecDeque<SongRequest>,
+}
+
+impl StationData {
+    pub fn is_listening(&self, user_id: i64) -> bool {
+        self.listeners.values().any(|l| l.user_id == user_id)
+    }
+
+    /// Listening users, however many connections each has
+    pub fn listener_count(&self) -> usize {
+        self.listeners.values().map(|l| l.user_id).collect::<HashSet<_>>().len()
+    }
+
+    /// Keeps one of the user's connections from going idle while a WebSocket is open, see `connection_mut`.
+    /// `ties` are the socket's, a connection is only tied once per socket.
+    pub fn tie_socket(&mut self, ties: &mut HashSet<SocketTie>, user_id: i64, connection_id: Option<Uuid>) {
+        if let Some(listener) = self.connection_mut(user_id, connection_id)
+            && ties.insert(SocketTie { connection_id: listener.connection_id, connected_at: listener.connected_at })
+        {
+            listener.open_sockets += 1;
+        }
+    }
+
+    /// Releases the connections of a closed socket, they get a full idle timeout from here as if they had sent a heartbeat.
+    /// Connections replaced by a reconnect since are left alone.
+    pub fn untie_socket(&mut self, ties: HashSet<SocketTie>, now: DateTime<Utc>) {
+        for tie in ties {
+            if let Some(listener) = self.listeners.get_mut(&tie.connection_id).filter(|l| l.connected_at == tie.connected_at) {
+                listener.open_sockets = listener.open_sockets.saturating_sub(1);
+                listener.last_heartbeat = now;
+            }
+        }
+    }
+
+    /// The user's connection with this id, or their newest one when no id is given
+    pub fn connection_mut(&mut self, user_id: i64, connection_id: Option<Uuid>) -> Option<&mut Listener> {
+        match connection_id {
+            Some(id) => self.listeners.get_mut(&id).filter(|l| l.user_id == user_id),
+            None => self.listeners.values_mut()
+                .filter(|l| l.user_id == user_id)
+                .max_by_key(|l| l.connected_at),
+        }
+    }
 }
 
 /// Messages sent from the loader to the broadcaster
@@ -99,6 +313,101 @@ pub struct AudioFrame {
 #[serde(tag = "type", content = "data")]
 pub enum StationEvent {
     SongChange(CurrentSong),
+    /// The playing song's metadata was edited
+    SongUpdated(CurrentSong),
+    TagsChanged(SongTags),
+    Progress(ProgressTick),
+    RhythmEvents(Vec<ScheduledRhythmEvent>),
+    Reaction(SongReaction),
+    ChatMessage(ChatMessage),
+    /// Id of a message removed by a moderator
+    ChatMessageDeleted(i64),
+    ListenerJoined(ListenerPresence),
+    ListenerLeft(ListenerPresence),
+    ListenerCount(usize),
+    /// The whole request queue after a change
+    QueueChanged(Vec<SongRequest>),
+}
+
+impl StationEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            StationEvent::SongChange(_) => EventKind::SongChange,
+            StationEvent::SongUpdated(_) => EventKind::SongUpdated,
+            StationEvent::TagsChanged(_) => EventKind::TagsChanged,
+            StationEvent::Progress(_) => EventKind::Progress,
+            StationEvent::RhythmEvents(_) => EventKind::RhythmEvents,
+            StationEvent::Reaction(_) => EventKind::Reaction,
+            StationEvent::ChatMessage(_) => EventKind::ChatMessage,
+            StationEvent::ChatMessageDeleted(_) => EventKind::ChatMessageDeleted,
+            StationEvent::ListenerJoined(_) => EventKind::ListenerJoined,
+            StationEvent::ListenerLeft(_) => EventKind::ListenerLeft,
+            StationEvent::ListenerCount(_) => EventKind::ListenerCount,
+            StationEvent::QueueChanged(_) => EventKind::QueueChanged,
+        }
+    }
+}
+
+/// Event types clients can subscribe to, one per `StationEvent` variant
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    SongChange,
+    SongUpdated,
+    TagsChanged,
+    Progress,
+    RhythmEvents,
+    Reaction,
+    ChatMessage,
+    ChatMessageDeleted,
+    ListenerJoined,
+    ListenerLeft,
+    ListenerCount,
+    QueueChanged,
+}
+
+impl EventKind {
+    pub const ALL: &'static [EventKind] = &[
+        EventKind::SongChange,
+        EventKind::SongUpdated,
+        EventKind::TagsChanged,
+        EventKind::Progress,
+        EventKind::RhythmEvents,
+        EventKind::Reaction,
+        EventKind::ChatMessage,
+        EventKind::ChatMessageDeleted,
+        EventKind::ListenerJoined,
+        EventKind::ListenerLeft,
+        EventKind::ListenerCount,
+        EventKind::QueueChanged,
+    ];
+
+    /// What a connection receives unless it asks otherwise. Rhythm events are opt-in.
+    pub fn default_set() -> HashSet<EventKind> {
+        Self::ALL.iter().copied().filter(|k| *k != EventKind::RhythmEvents).collect()
+    }
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct ListenerPresence {
+    pub user_id: i64,
+    pub username: String,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct SongTags {
+    pub song_id: i64,
+    pub tags: Vec<TagInfo>,
+}
+
+/// Periodic playback position of the playing song
+#[derive(Clone, Serialize, Debug)]
+pub struct ProgressTick {
+    pub song_id: i64,
+    pub position_ms: u64,
+    pub duration_ms: u64,
+    /// Station timeline position, the same timeline as `started_at_ms`
+    pub station_position_ms: u64,
 }
 
 #[derive(Clone)]
@@ -109,6 +418,10 @@ pub struct AppState {
     pub station: Arc<RwLock<StationData>>,
     pub db: SqlitePool,
     pub cookie_key: Key,
+    pub login_throttle: Arc<Mutex<LoginThrottle>>,
+    /// Hash of the one-time token that registers the first admin, while there is none
+    pub setup_token_hash: Arc<Mutex<Option<String>>>,
+    pub analysis_queue: AnalysisQueue,
 }
 
 impl FromRef<AppState> for Arc<RwLock<StationData>> {
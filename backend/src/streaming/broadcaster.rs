@@ -1,10 +1,47 @@
-use crate::state::{AudioFrame, StationData, StreamMessage, StationEvent, CurrentSong};
+use crate::state::{AudioFrame, StationData, StreamMessage, StationEvent, CurrentSong, ProgressTick, ScheduledRhythmEvent};
 use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Duration;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tokio::sync::{broadcast, mpsc, RwLock};
-use crate::config::BURST_BUFFER_SECONDS;
+use sqlx::SqlitePool;
+use crate::config::{BURST_BUFFER_SECONDS, PROGRESS_TICK_SECONDS, RHYTHM_LOOKAHEAD_SECONDS};
+use crate::streaming::presence::{self, ListenTime};
+
+/// Saves the listen time of the song that ended, then records the new play and hands its id to the current song
+/// along with the listen time held back until then
+async fn record_song_start(
+    db: SqlitePool,
+    station: Arc<RwLock<StationData>>,
+    song_id: i64,
+    started_at: DateTime<Utc>,
+    duration_ms: u64,
+    earned: Vec<ListenTime>,
+    ended_play: Option<(i64, i64)>,
+) {
+    presence::save_listen_time(&db, earned, ended_play).await;
+
+    let play_id = match crate::orm::history::repository::record_play(&db, song_id, started_at, duration_ms as i64).await {
+        Ok(play_id) => play_id,
+        Err(e) => {
+            tracing::error!("Failed to record play of song #{}: {}", song_id, e);
+            return;
+        }
+    };
+
+    let held = {
+        let mut station_guard = station.write().await;
+        match station_guard.current_song.as_mut() {
+            Some(song) if song.id == song_id && song.started_at == started_at => {
+                song.play_id = Some(play_id);
+                std::mem::take(&mut station_guard.unrecorded_listen)
+            }
+            // Another song started meanwhile and took the held time with its own
+            _ => Vec::new(),
+        }
+    };
+    presence::save_listen_time(&db, held, Some((play_id, song_id))).await;
+}
 
 pub async fn start(
     tx: broadcast::Sender<AudioFrame>,
@@ -12,9 +49,18 @@ pub async fn start(
     mut rx: mpsc::Receiver<StreamMessage>,
     history: Arc<RwLock<VecDeque<AudioFrame>>>,
     station: Arc<RwLock<StationData>>,
+    db: SqlitePool,
 ) {
     let mut next_send_time = tokio::time::Instant::now();
 
+    // Rhythm events of the current song that haven't been pushed to clients yet
+    let mut pending_rhythm: VecDeque<ScheduledRhythmEvent> = VecDeque::new();
+    let mut rhythm_seq: u64 = 0;
+    let lookahead_micros = (RHYTHM_LOOKAHEAD_SECONDS * 1_000_000.0) as u128;
+
+    let progress_tick_micros = (PROGRESS_TICK_SECONDS * 1_000_000.0) as u128;
+    let mut last_progress_micros: u128 = 0;
+
     loop {
         let msg = match rx.recv().await {
             Some(m) => m,
@@ -23,29 +69,79 @@ pub async fn start(
 
         let frame = match msg {
             StreamMessage::SongStart(song, duration_ms, raw_rhythm) => {
+                let track = raw_rhythm.as_deref().and_then(|data| {
+                    crate::rhythm::compiler::decompile_bytes(data)
+                        .map_err(|e| tracing::warn!("Invalid rhythm data for song #{}: {}", song.id, e))
+                        .ok()
+                });
+
                 let rhythm_data = raw_rhythm.map(|data| {
                     use base64::{Engine as _, engine::general_purpose};
                     general_purpose::STANDARD.encode(data)
                 });
 
+                // The song starts playing now, not when the loader queued it
+                let started_at = Utc::now();
+
                 let mut current_song = CurrentSong {
                     id: song.id,
                     title: song.title,
                     artist_names: song.artist_names,
                     album_title: song.album_title,
                     duration_ms,
-                    started_at: Utc::now(),
+                    started_at,
                     started_at_ms: 0, // Will be set below
                     started_at_micros: 0,
                     rhythm_data,
+                    play_id: None, // Set once the play is recorded
                 };
 
-                {
+                let (earned, ended_play) = {
                     let mut station_guard = station.write().await;
+                    // Listen time up to now belongs to the song that just ended. If its play never got recorded
+                    // the time held for it is saved without a play, rather than credited to the next song.
+                    let mut earned = std::mem::take(&mut station_guard.unrecorded_listen);
+                    earned.extend(presence::take_listen_time(&mut station_guard, started_at));
+                    let ended_play = presence::current_play(&station_guard);
+
                     let micros = station_guard.playback_position.total_duration_micros;
                     current_song.started_at_micros = micros;
                     current_song.started_at_ms = (micros / 1_000) as u64; // Correct rounding downwards is fine for display
                     station_guard.current_song = Some(current_song.clone());
+                    station_guard.upcoming_rhythm.clear();
+                    (earned, ended_play)
+                };
+
+                // Written off the pacing loop, a slow or locked DB mustn't hold up the audio
+                tokio::spawn(record_song_start(
+                    db.clone(),
+                    station.clone(),
+                    current_song.id,
+                    started_at,
+                    duration_ms,
+                    earned,
+                    ended_play,
+                ));
+
+                // Place the song's rhythm events on the station timeline
+                pending_rhythm.clear();
+                if let Some(track) = track {
+                    let offset_ms = track.offset_ms.unwrap_or(0);
+                    for event in track.events {
+                        let time_ms = event.time as i64 + offset_ms;
+                        if time_ms < 0 {
+                            continue;
+                        }
+                        rhythm_seq += 1;
+                        pending_rhythm.push_back(ScheduledRhythmEvent {
+                            seq: rhythm_seq,
+                            song_id: current_song.id,
+                            at_micros: (current_song.started_at_micros + time_ms as u128 * 1_000) as u64,
+                            event,
+                        });
+                    }
+                    // Legacy data isn't guaranteed to be sorted
+                    pending_rhythm.make_contiguous().sort_by_key(|e| e.at_micros);
                 }
 
                 let _ = event_tx.send(StationEvent::SongChange(current_song));
@@ -63,6 +159,39 @@ pub async fn start(
             station_guard.playback_position.current_frame_index += 1;
             // High-precision accumulation (microseconds)
             station_guard.playback_position.total_duration_micros += frame.duration.as_micros();
+            station_guard.playback_position.updated_at = std::time::Instant::now();
+
+            let position = station_guard.playback_position.total_duration_micros;
+
+            // Forget pushed events that already fired
+            while station_guard.upcoming_rhythm.front().is_some_and(|e| (e.at_micros as u128) < position) {
+                station_guard.upcoming_rhythm.pop_front();
+            }
+
+            // Push the events entering the lookahead window
+            let mut due = Vec::new();
+            while pending_rhythm.front().is_some_and(|e| (e.at_micros as u128) <= position + lookahead_micros) {
+                if let Some(event) = pending_rhythm.pop_front() {
+                    due.push(event);
+                }
+            }
+
+            if !due.is_empty() {
+                station_guard.upcoming_rhythm.extend(due.iter().cloned());
+                let _ = event_tx.send(StationEvent::RhythmEvents(due));
+            }
+
+            if position.saturating_sub(last_progress_micros) >= progress_tick_micros
+                && let Some(song) = &station_guard.current_song
+            {
+                last_progress_micros = position;
+                let _ = event_tx.send(StationEvent::Progress(ProgressTick {
+                    song_id: song.id,
+                    position_ms: (position.saturating_sub(song.started_at_micros) / 1_000) as u64,
+                    duration_ms: song.duration_ms,
+                    station_position_ms: (position / 1_000) as u64,
+                }));
+            }
         }
 
         // Add to history buffer for new joiners
@@ -80,7 +209,7 @@ pub async fn start(
             }
 
             // Remove old frames
-            while history_guard.len() > 0 {
+            while !history_guard.is_empty() {
                 if let Some(_oldest) = history_guard.front() {
                     let remaining_duration: Duration = history_guard.iter()
                         .skip(1)
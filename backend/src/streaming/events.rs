@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use tokio::sync::broadcast;
+
+use crate::orm::songs::models::Song;
+use crate::state::{AppState, Listener, ListenerPresence, SongTags, StationData, StationEvent};
+
+fn presence(listener: &Listener) -> ListenerPresence {
+    ListenerPresence {
+        user_id: listener.user_id,
+        username: listener.username.clone(),
+    }
+}
+
+/// Announces a user that started streaming, followed by the new count.
+/// Another connection of a user who is already listening isn't announced.
+pub fn listener_joined(event_tx: &broadcast::Sender<StationEvent>, station: &StationData, listener: &Listener) {
+    let connections = station.listeners.values().filter(|l| l.user_id == listener.user_id).count();
+    if connections > 1 {
+        return;
+    }
+
+    let _ = event_tx.send(StationEvent::ListenerJoined(presence(listener)));
+    let _ = event_tx.send(StationEvent::ListenerCount(station.listener_count()));
+}
+
+/// Announces the users whose last connection was removed, followed by the new count
+pub fn listeners_left(event_tx: &broadcast::Sender<StationEvent>, station: &StationData, removed: &[Listener]) {
+    let mut announced = HashSet::new();
+    for listener in removed {
+        if !station.is_listening(listener.user_id) && announced.insert(listener.user_id) {
+            let _ = event_tx.send(StationEvent::ListenerLeft(presence(listener)));
+        }
+    }
+
+    if !announced.is_empty() {
+        let _ = event_tx.send(StationEvent::ListenerCount(station.listener_count()));
+    }
+}
+
+pub fn queue_changed(event_tx: &broadcast::Sender<StationEvent>, station: &StationData) {
+    let _ = event_tx.send(StationEvent::QueueChanged(station.song_requests.iter().cloned().collect()));
+}
+
+/// Refreshes the playing song after an edit. Does nothing if `song` isn't playing.
+pub async fn song_updated(state: &AppState, song: &Song) {
+    let mut station_guard = state.station.write().await;
+
+    let Some(current) = station_guard.current_song.as_mut().filter(|c| c.id == song.id) else {
+        return;
+    };
+
+    current.title = song.title.clone();
+    current.artist_names = song.artist_names.clone();
+    current.album_title = song.album_title.clone();
+
+    let _ = state.event_tx.send(StationEvent::SongUpdated(current.clone()));
+}
+
+/// Sends the tags of the playing song after they change. Does nothing if `song_id` isn't playing.
+pub async fn song_tags_changed(state: &AppState, song_id: i64) {
+    let is_playing = state.station.read().await.current_song.as_ref().is_some_and(|c| c.id == song_id);
+    if !is_playing {
+        return;
+    }
+
+    match crate::orm::tags::repository::find_by_song(&state.db, song_id).await {
+        Ok(tags) => {
+            let _ = state.event_tx.send(StationEvent::TagsChanged(SongTags { song_id, tags }));
+        }
+        Err(e) => tracing::error!("Failed to load tags of song #{}: {}", song_id, e),
+    }
+}
@@ -1,23 +1,39 @@
-use crate::state::{AppState, AudioFrame, Listener, StationEvent, CurrentSong};
+use crate::state::{
+    AppState, AudioFrame, DesyncSample, DesyncSource, EventKind, ListenCredit, Listener, CurrentSong, StationData, StationSettings,
+};
+use std::collections::{HashMap, HashSet};
 use axum::{
     body::Body,
-    extract::{State, ws::{WebSocketUpgrade, WebSocket, Message, Utf8Bytes}},
+    extract::{Path, Query, State, ws::WebSocketUpgrade},
     http::{header, StatusCode},
     response::{Json, Response},
 };
 use chrono::{Utc};
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt;
-use crate::auth::AuthUser;
-use crate::streaming::model::{ActiveListenerDto, HeartbeatQuery, HeartbeatResponse};
+use uuid::Uuid;
+use crate::auth::{perm, AuthListener, AuthUser, Require};
+use crate::orm::audit::{self, models::{AuditAction, AuditEntity}};
+use crate::orm::tokens::models::ApiScope;
+use crate::error::AppError;
+use crate::streaming::model::{
+    ActiveListenerDto, HeartbeatQuery, HeartbeatResponse, ListenerDesyncDto, StreamQuery, UpdateStationSettingsDto, WsQuery,
+};
+use crate::streaming::{events, socket};
+use crate::streaming::presence::ListenerStream;
+
+/// Response header of `/stream` naming the connection, for heartbeats
+pub const CONNECTION_ID_HEADER: &str = "X-Wavy-Connection-Id";
 
 pub async fn stream_audio(
     State(state): State<AppState>,
-    user: AuthUser,
-) -> Response {
+    listener: AuthListener,
+    Query(query): Query<StreamQuery>,
+) -> Result<Response, AppError> {
+    let user_id = listener.id;
+    let username = listener.name;
+
     let rx = state.tx.subscribe();
-    let user_id = user.0.id;
-    let username = user.0.username.clone();
 
     // Send burst buffer (catch-up frames for new joiners)
     let (history_data, burst_buffer_ms): (Vec<AudioFrame>, u64) = {
@@ -29,34 +45,64 @@ pub async fn stream_audio(
         (frames, total_ms)
     };
 
-    tracing::info!(
-        "User {} ({}) connected to stream, sending {} buffered frames ({} ms)",
-        username,
-        user_id,
-        history_data.len(),
-        burst_buffer_ms
-    );
-
-    // Track this listener
-    {
+    // Track this connection
+    let (connection_id, connected_at, activity) = {
         let mut station_guard = state.station.write().await;
+
+        // Checked under the same lock as the insert, so guests connecting at once can't go past the limit
+        if listener.is_guest {
+            let guests = station_guard.listeners.values()
+                .filter(|l| l.is_guest)
+                .map(|l| l.user_id)
+                .collect::<HashSet<_>>()
+                .len();
+            // A guest already listening takes its own place
+            if !station_guard.is_listening(user_id) && guests >= station_guard.settings.max_guests {
+                return Err(AppError::ServiceUnavailable("The station is full of guests, please log in or try again later".to_string()));
+            }
+        }
+
         let current_frame_index = station_guard.playback_position.current_frame_index;
         let start_total_duration_micros = station_guard.playback_position.total_duration_micros;
-        
-        station_guard.listeners.insert(
-            user_id,
-            Listener {
+
+        // Reconnecting with the same id replaces the earlier connection, an id someone else uses is ignored
+        let connection_id = query.connection_id
+            .filter(|id| station_guard.listeners.get(id).is_none_or(|l| l.user_id == user_id))
+            .unwrap_or_else(Uuid::new_v4);
+
+        let listener = Listener {
+                connection_id,
                 user_id,
                 username: username.clone(),
+                is_guest: listener.is_guest,
                 connected_at: Utc::now(),
                 last_heartbeat: Utc::now(),
+                activity: Default::default(),
                 start_frame_index: current_frame_index,
                 burst_buffer_ms,
                 start_total_duration_micros,
-                last_saved_at: Utc::now(),
-            },
-        );
-    }
+                desync_history: Default::default(),
+                open_sockets: 0,
+        };
+
+        // Concurrent devices share the credit of the first one
+        if !listener.is_guest {
+            station_guard.listen_credit.entry(user_id).or_insert(ListenCredit { last_saved_at: Utc::now() });
+        }
+
+        if station_guard.listeners.insert(connection_id, listener.clone()).is_none() {
+            events::listener_joined(&state.event_tx, &station_guard, &listener);
+        }
+        (connection_id, listener.connected_at, listener.activity.clone())
+    };
+
+    tracing::info!(
+        "User {} ({}) connected to stream, sending {} buffered frames ({} ms)",
+        username,
+        user_id,
+        history_data.len(),
+        burst_buffer_ms
+    );
 
     // Create stream from history
     let burst_stream = tokio_stream::iter(history_data.into_iter().map(|f| Ok(f.data)));
@@ -64,73 +110,83 @@ pub async fn stream_audio(
     // Create stream from live broadcast
     let live_stream = BroadcastStream::new(rx).map(|result| match result {
         Ok(frame) => Ok(frame.data),
-        Err(e) => Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Broadcast lag: {}", e),
-        )),
+        Err(e) => Err(std::io::Error::other(format!("Broadcast lag: {}", e))),
     });
 
-    // Send audio stream
+    // Send audio stream, the connection ends when the client drops it
     let combined_stream = burst_stream.chain(live_stream);
+    let body = ListenerStream::new(combined_stream, state.clone(), connection_id, connected_at, activity);
 
-    Response::builder()
+    Ok(Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "audio/mpeg")
         .header(header::CACHE_CONTROL, "no-cache, no-store")
         .header("X-Content-Type-Options", "nosniff")
-        .body(Body::from_stream(combined_stream))
-        .unwrap()
+        .header(CONNECTION_ID_HEADER, connection_id.to_string())
+        .body(Body::from_stream(body))
+        .unwrap())
 }
 
 pub async fn heartbeat(
     State(state): State<AppState>,
-    user: AuthUser,
+    listener: AuthListener,
     Json(query): Json<HeartbeatQuery>,
 ) -> Result<Json<HeartbeatResponse>, StatusCode> {
     let mut station_guard = state.station.write().await;
-    let user_id = user.0.id;
-    
-    // Check if listener exists, if so update heartbeat
-    if let Some(listener) = station_guard.listeners.get_mut(&user_id) {
-        listener.last_heartbeat = Utc::now();
-    } else {
-        // If not found (maybe restarted server or cleaned up), we return 404
-        // The client should probably reconnect
-        return Err(StatusCode::NOT_FOUND);
-    }
-    
-    let listener = station_guard.listeners.get(&user_id).unwrap();
-    let server_now_micros = station_guard.playback_position.total_duration_micros;
 
-    // Calculate absolute client position using high-precision micros
-    
-    // Listener start: S_connect (micros)
-    // Burst buffer: B (ms) -> B * 1000 (micros)
+    // If not found (maybe restarted server or cleaned up), we return 404
+    // The client should probably reconnect
+    apply_heartbeat(&mut station_guard, listener.id, query.connection_id, query.client_position_ms)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Refreshes one of the user's connections and measures its desync. `None` if there is no such connection.
+/// Shared by the HTTP route and the WebSocket command.
+pub(super) fn apply_heartbeat(
+    station: &mut StationData,
+    user_id: i64,
+    connection_id: Option<Uuid>,
+    client_position_ms: Option<u64>,
+) -> Option<HeartbeatResponse> {
+    let server_now_micros = station.playback_position.total_duration_micros;
+
+    // Check if the connection exists, if so update heartbeat
+    let listener = station.connection_mut(user_id, connection_id)?;
+    listener.last_heartbeat = Utc::now();
+
     // Client base pos: S_connect - B
-    
-    let burst_micros = (listener.burst_buffer_ms as u128) * 1_000;
-    let client_base_micros = listener.start_total_duration_micros.saturating_sub(burst_micros);
+    let client_base_micros = listener.client_base_micros();
     
     // Return values for API (converted to ms)
     let server_position_ms = (server_now_micros / 1_000) as u64;
     let client_base_pos_ms = (client_base_micros / 1_000) as u64;
 
-    let desync_ms = if let Some(client_pos_ms) = query.client_position_ms {
+    let desync_ms = if let Some(client_pos_ms) = client_position_ms {
         let client_abs_micros = client_base_micros + ((client_pos_ms as u128) * 1_000);
         
         // Desync = Server - Client
         let diff = (server_now_micros as i128) - (client_abs_micros as i128);
-        
-        (diff / 1_000) as i64
+        let desync_ms = (diff / 1_000) as i64;
+
+        listener.record_desync(DesyncSample {
+            measured_at: Utc::now(),
+            desync_ms,
+            rtt_ms: None,
+            source: DesyncSource::Heartbeat,
+        });
+
+        desync_ms
     } else {
         0
     };
 
-    Ok(Json(HeartbeatResponse {
+    Some(HeartbeatResponse {
+        connection_id: listener.connection_id,
         desync_ms,
         server_position_ms,
         client_base_pos_ms,
-    }))
+    })
 }
 
 pub async fn get_active_listeners(
@@ -138,64 +194,180 @@ pub async fn get_active_listeners(
     _user: AuthUser,
 ) -> Json<Vec<ActiveListenerDto>> {
     let station_guard = state.station.read().await;
+    Json(active_listeners(&station_guard))
+}
+
+/// One entry per user, however many devices they listen on
+pub(super) fn active_listeners(station: &StationData) -> Vec<ActiveListenerDto> {
     let now = Utc::now();
-    
+
+    let mut by_user: HashMap<i64, ActiveListenerDto> = HashMap::new();
+    for l in station.listeners.values() {
+        let entry = by_user.entry(l.user_id).or_insert_with(|| ActiveListenerDto {
+            username: l.username.clone(),
+            connected_at: l.connected_at,
+            listen_time_ms: 0,
+            connections: 0,
+        });
+        entry.connected_at = entry.connected_at.min(l.connected_at);
+        entry.connections += 1;
+    }
+
+    by_user.into_values()
+        .map(|mut dto| {
+            dto.listen_time_ms = now.signed_duration_since(dto.connected_at).num_milliseconds();
+            dto
+        })
+        .collect()
+}
+
+pub async fn get_listener_desync(
+    State(state): State<AppState>,
+    _: Require<perm::ManageStation>,
+) -> Json<Vec<ListenerDesyncDto>> {
+    let station_guard = state.station.read().await;
+
     let listeners = station_guard.listeners.values()
         .map(|l| {
-            let listen_time_ms = now
-                .signed_duration_since(l.connected_at)
-                .num_milliseconds();
-            
-            ActiveListenerDto {
+            let count = l.desync_history.len() as f64;
+            let average_desync_ms = (count > 0.0)
+                .then(|| l.desync_history.iter().map(|s| s.desync_ms as f64).sum::<f64>() / count);
+
+            let rtts: Vec<f64> = l.desync_history.iter().filter_map(|s| s.rtt_ms).collect();
+            let average_rtt_ms = (!rtts.is_empty()).then(|| rtts.iter().sum::<f64>() / rtts.len() as f64);
+
+            ListenerDesyncDto {
+                connection_id: l.connection_id,
+                user_id: l.user_id,
                 username: l.username.clone(),
-                connected_at: l.connected_at,
-                listen_time_ms,
+                latest_desync_ms: l.desync_history.back().map(|s| s.desync_ms),
+                average_desync_ms,
+                average_rtt_ms,
+                samples: l.desync_history.iter().cloned().collect(),
             }
         })
         .collect();
-        
+
     Json(listeners)
 }
 
 pub async fn get_current_song(
     State(state): State<AppState>,
-    _user: AuthUser,
+    _listener: AuthListener,
 ) -> Json<Option<CurrentSong>> {
     let station_guard = state.station.read().await;
     Json(station_guard.current_song.clone())
 }
 
-pub async fn ws_handler(
+pub async fn get_station_settings(
     State(state): State<AppState>,
-    _user: AuthUser,
-    ws: WebSocketUpgrade,
-) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    _: Require<perm::ManageStation>,
+) -> Json<StationSettings> {
+    let station_guard = state.station.read().await;
+    Json(station_guard.settings.clone())
 }
 
-async fn handle_socket(mut socket: WebSocket, state: AppState) {
-    let mut rx = state.event_tx.subscribe();
-    
-    // Send current state first
-    {
-        let guard = state.station.read().await;
-        if let Some(song) = &guard.current_song {
-            let event = StationEvent::SongChange(song.clone());
-            if let Ok(json) = serde_json::to_string(&event) {
-                let msg = Message::Text(Utf8Bytes::from(json));
-                if socket.send(msg).await.is_err() {
-                    return;
-                }
-            }
-        }
+pub async fn update_station_settings(
+    State(state): State<AppState>,
+    Require(actor, _): Require<perm::ManageStation>,
+    Json(payload): Json<UpdateStationSettingsDto>,
+) -> Result<Json<StationSettings>, AppError> {
+    // Checked before taking the station lock
+    if let Some(Some(playlist_id)) = payload.playlist_id {
+        crate::orm::playlists::repository::find_by_id(&state.db, playlist_id)
+            .await
+            .map_err(AppError::InternalServerError)?
+            .ok_or(AppError::NotFound("Playlist not found".to_string()))?;
     }
 
-    while let Ok(event) = rx.recv().await {
-        if let Ok(json) = serde_json::to_string(&event) {
-            let msg = Message::Text(Utf8Bytes::from(json));
-            if socket.send(msg).await.is_err() {
-                break;
-            }
+    let mut station_guard = state.station.write().await;
+    let before = station_guard.settings.clone();
+    let mut settings = before.clone();
+
+    if let Some(min_bpm) = payload.min_bpm {
+        settings.min_bpm = min_bpm;
+    }
+    if let Some(max_bpm) = payload.max_bpm {
+        settings.max_bpm = max_bpm;
+    }
+    if let Some(dislike_weight) = payload.dislike_weight {
+        if !dislike_weight.is_finite() || dislike_weight < 0.0 {
+            return Err(AppError::BadRequest("dislike_weight must be zero or positive".to_string()));
         }
+        settings.dislike_weight = dislike_weight;
+    }
+    if let Some(guests_enabled) = payload.guests_enabled {
+        settings.guests_enabled = guests_enabled;
     }
-}
\ No newline at end of file
+    if let Some(max_guests) = payload.max_guests {
+        settings.max_guests = max_guests;
+    }
+    if let Some(playlist_id) = payload.playlist_id {
+        settings.playlist_id = playlist_id;
+    }
+    if let Some(on_demand_enabled) = payload.on_demand_enabled {
+        settings.on_demand_enabled = on_demand_enabled;
+    }
+
+    if let (Some(min), Some(max)) = (settings.min_bpm, settings.max_bpm)
+        && min > max
+    {
+        return Err(AppError::BadRequest("min_bpm can't be greater than max_bpm".to_string()));
+    }
+
+    station_guard.settings = settings.clone();
+    drop(station_guard);
+
+    audit::record(&state.db, &actor, AuditAction::Update, AuditEntity::StationSettings, None, Some(&before), Some(&settings)).await;
+    Ok(Json(settings))
+}
+
+/// Drops a pending song request from the queue
+pub async fn remove_song_request(
+    State(state): State<AppState>,
+    Require(actor, _): Require<perm::ManageQueue>,
+    Path(song_id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let mut station_guard = state.station.write().await;
+
+    let position = station_guard.song_requests.iter()
+        .position(|r| r.song_id == song_id)
+        .ok_or(AppError::NotFound("Song is not requested".to_string()))?;
+    let request = station_guard.song_requests.remove(position);
+
+    events::queue_changed(&state.event_tx, &station_guard);
+    drop(station_guard);
+
+    audit::record(&state.db, &actor, AuditAction::Delete, AuditEntity::SongRequest, Some(song_id), request.as_ref(), audit::NOTHING).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn ws_handler(
+    State(state): State<AppState>,
+    AuthUser(user, scopes): AuthUser,
+    Query(query): Query<WsQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let mut events = match &query.events {
+        Some(list) => list.split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(|kind| {
+                serde_json::from_value::<EventKind>(serde_json::Value::String(kind.to_string()))
+                    .map_err(|_| AppError::BadRequest(format!("Unknown event kind '{}'", kind)))
+            })
+            .collect::<Result<HashSet<_>, _>>()?,
+        None => EventKind::default_set(),
+    };
+
+    match query.rhythm {
+        Some(true) => events.insert(EventKind::RhythmEvents),
+        Some(false) => events.remove(&EventKind::RhythmEvents),
+        None => false,
+    };
+
+    // Cookie sessions can always chat
+    let can_chat = scopes.is_none_or(|scopes| scopes.contains(ApiScope::ChatWrite));
+
+    Ok(ws.on_upgrade(move |socket| socket::handle_socket(socket, state, user, events, can_chat, query.connection_id)))
+}
@@ -1,6 +1,7 @@
 use crate::config::DEFAULT_SAMPLE_RATE;
 use crate::state::{AppState, AudioFrame, StreamMessage};
 use bytes::Bytes;
+use std::collections::VecDeque;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
@@ -15,13 +16,35 @@ use crate::orm::songs::models::Song;
 pub fn start(tx: mpsc::Sender<StreamMessage>, state: Arc<AppState>) {
     tokio::spawn(async move {
         loop {
-            let songs = match crate::orm::songs::repository::find_all(&state.db).await {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::error!("Failed to fetch songs from DB: {}", e);
-                    tokio::time::sleep(Duration::from_secs(5)).await;
-                    continue;
-                }
+            let settings = state.station.read().await.settings.clone();
+
+            // A playlist plays in its own order, the library is shuffled
+            let playlist_songs = match settings.playlist_id {
+                Some(playlist_id) => match crate::orm::playlists::repository::find_songs(&state.db, playlist_id).await {
+                    Ok(songs) if !songs.is_empty() => Some(songs),
+                    Ok(_) => {
+                        tracing::warn!("Station playlist #{} is empty or gone, playing the whole library", playlist_id);
+                        None
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to fetch station playlist #{}: {}", playlist_id, e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            let shuffle = playlist_songs.is_none();
+
+            let songs = match playlist_songs {
+                Some(songs) => songs,
+                None => match crate::orm::songs::repository::find_all(&state.db).await {
+                    Ok(s) => s,
+                    Err(e) => {
+                        tracing::error!("Failed to fetch songs from DB: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        continue;
+                    }
+                },
             };
 
             if songs.is_empty() {
@@ -30,14 +53,49 @@ pub fn start(tx: mpsc::Sender<StreamMessage>, state: Arc<AppState>) {
                 continue;
             }
 
-            // Shuffle songs
-            let mut play_list = songs;
-            tokio::task::block_in_place(|| {
-                use rand::seq::SliceRandom;
-                play_list.shuffle(&mut rand::rng());
-            });
+            // Apply the station's selection criteria, falling back to everything rather than going silent
+            let eligible: Vec<Song> = songs.iter()
+                .filter(|s| settings.accepts_bpm(s.bpm))
+                .cloned()
+                .collect();
+
+            let songs = if eligible.is_empty() {
+                tracing::warn!("No songs match the station BPM range, playing them all");
+                songs
+            } else {
+                eligible
+            };
+
+            // Weighted shuffle (Efraimidis-Spirakis): each song gets the key u^(1/w) and the highest keys play first,
+            // so disliked songs tend to come later. With no dislike weight every weight is 1, a plain shuffle.
+            let play_list = if !shuffle {
+                songs
+            } else {
+                tokio::task::block_in_place(|| {
+                    use rand::Rng;
+                    let mut rng = rand::rng();
+                    let mut keyed: Vec<(f64, Song)> = songs.into_iter()
+                        .map(|song| {
+                            let weight = settings.pick_weight(song.dislikes);
+                            (rng.random::<f64>().powf(1.0 / weight), song)
+                        })
+                        .collect();
+                    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+                    keyed.into_iter().map(|(_, song)| song).collect::<Vec<Song>>()
+                })
+            };
+
+            // Requested songs go first, then the playlist or the shuffled library
+            let mut play_list: VecDeque<Song> = play_list.into();
+            loop {
+                let song_data = match next_request(&state).await {
+                    Some(song) => song,
+                    None => match play_list.pop_front() {
+                        Some(song) => song,
+                        None => break,
+                    },
+                };
 
-            for song_data in play_list {
                 let file_path = crate::config::get_music_dir().join(format!("{}.mp3", song_data.id));
 
                 if !file_path.exists() {
@@ -65,6 +123,27 @@ pub fn start(tx: mpsc::Sender<StreamMessage>, state: Arc<AppState>) {
     });
 }
 
+/// Pops the oldest listener request, skipping songs deleted since they were requested
+async fn next_request(state: &AppState) -> Option<Song> {
+    loop {
+        let request = {
+            let mut station_guard = state.station.write().await;
+            let request = station_guard.song_requests.pop_front()?;
+            super::events::queue_changed(&state.event_tx, &station_guard);
+            request
+        };
+
+        match crate::orm::songs::repository::find_by_id(&state.db, request.song_id).await {
+            Ok(Some(song)) => {
+                tracing::info!("Playing song #{} requested by {}", song.id, request.requested_by_name);
+                return Some(song);
+            }
+            Ok(None) => tracing::warn!("Requested song #{} no longer exists", request.song_id),
+            Err(e) => tracing::error!("Failed to load requested song #{}: {}", request.song_id, e),
+        }
+    }
+}
+
 fn stream_mp3_file(
     path: &Path,
     tx: &mpsc::Sender<StreamMessage>,
@@ -107,11 +186,11 @@ fn stream_mp3_file(
 
     let dec_opts: DecoderOptions = Default::default();
     let mut decoder = symphonia::default::get_codecs()
-        .make(&codec_params, &dec_opts)
+        .make(codec_params, &dec_opts)
         .map_err(|e| format!("Decoder error: {}", e))?;
 
-    // Load rhythm data (save.dat) if present
-    let rhythm_data = std::fs::read(crate::config::get_save_dat_path()).ok();
+    // Load this song's rhythm data, if it has any
+    let rhythm_data = crate::rhythm::store::read_compiled(db_song.id);
 
     let duration_ms = if let Some(n_frames) = codec_params.n_frames {
         // This is an approximation for MP3, but usually accurate enough for progress bars
@@ -123,9 +202,9 @@ fn stream_mp3_file(
     // Send SongStart event before first frame
     if tokio::task::block_in_place(|| {
         tokio::runtime::Handle::current().block_on(async {
-            tx.send(StreamMessage::SongStart(db_song.clone(), duration_ms, rhythm_data)).await
+            tx.send(StreamMessage::SongStart(db_song.clone(), duration_ms, rhythm_data)).await.is_err()
         })
-    }).is_err() {
+    }) {
         return Err("Channel closed".to_string());
     }
 
@@ -166,9 +245,9 @@ fn stream_mp3_file(
 
         if tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
-                tx.send(StreamMessage::Frame(audio_frame)).await
+                tx.send(StreamMessage::Frame(audio_frame)).await.is_err()
             })
-        }).is_err() {
+        }) {
             return Err("Channel closed".to_string());
         }
     }
@@ -1,4 +1,8 @@
 pub mod broadcaster;
+pub mod events;
 pub mod loader;
 pub mod handlers;
-mod model;
\ No newline at end of file
+pub mod presence;
+mod model;
+mod protocol;
+mod socket;
\ No newline at end of file
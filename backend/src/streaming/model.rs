@@ -1,8 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::state::DesyncSample;
 
 #[derive(Serialize)]
 pub struct HeartbeatResponse {
+    pub connection_id: Uuid,
     pub desync_ms: i64,
     pub server_position_ms: u64,
     pub client_base_pos_ms: u64,
@@ -10,12 +13,61 @@ pub struct HeartbeatResponse {
 
 #[derive(Deserialize)]
 pub struct HeartbeatQuery {
+    /// From the `X-Wavy-Connection-Id` header of `/stream`, the newest connection when omitted
+    pub(crate) connection_id: Option<Uuid>,
     pub(crate) client_position_ms: Option<u64>,
 }
 
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    /// Chosen by the client, so players that can't read response headers can still heartbeat.
+    /// Reusing it replaces the earlier connection.
+    pub connection_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct WsQuery {
+    /// Opt in to rhythm events pushed ahead of time
+    pub rhythm: Option<bool>,
+    /// Comma separated event kinds to receive, all but rhythm events by default
+    pub events: Option<String>,
+    /// Stream connection kept from going idle while the socket is open
+    pub connection_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+pub struct ListenerDesyncDto {
+    pub connection_id: Uuid,
+    pub user_id: i64,
+    pub username: String,
+    pub latest_desync_ms: Option<i64>,
+    pub average_desync_ms: Option<f64>,
+    pub average_rtt_ms: Option<f64>,
+    pub samples: Vec<DesyncSample>,
+}
+
 #[derive(Serialize)]
 pub struct ActiveListenerDto {
     pub username: String,
+    /// When the user's earliest current connection started
     pub connected_at: DateTime<Utc>,
     pub listen_time_ms: i64,
-}
\ No newline at end of file
+    /// Devices the user is listening on
+    pub connections: usize,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateStationSettingsDto {
+    /// `Some(None)` (explicit null) clears the bound
+    #[serde(default, with = "crate::orm::double_option")]
+    pub min_bpm: Option<Option<f64>>,
+    #[serde(default, with = "crate::orm::double_option")]
+    pub max_bpm: Option<Option<f64>>,
+    pub dislike_weight: Option<f64>,
+    pub guests_enabled: Option<bool>,
+    pub max_guests: Option<usize>,
+    /// `Some(None)` goes back to the whole library
+    #[serde(default, with = "crate::orm::double_option")]
+    pub playlist_id: Option<Option<i64>>,
+    pub on_demand_enabled: Option<bool>,
+}
@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use tokio_stream::Stream;
+use uuid::Uuid;
+
+use crate::config::LISTENER_IDLE_TIMEOUT_SECONDS;
+use crate::orm;
+use crate::state::{AppState, ConnectionActivity, StationData};
+use crate::streaming::events;
+
+/// Listen time earned by a user, ready to be saved
+pub struct ListenTime {
+    pub user_id: i64,
+    pub seconds: i64,
+}
+
+/// The `/stream` body. Dropped when the client disconnects, which ends its connection right away
+/// instead of waiting for heartbeats to stop. Connections that go idle are ended by `end_idle_connections`.
+pub struct ListenerStream<S> {
+    inner: S,
+    state: AppState,
+    connection_id: Uuid,
+    connected_at: DateTime<Utc>,
+    activity: Arc<ConnectionActivity>,
+}
+
+impl<S> ListenerStream<S> {
+    pub fn new(
+        inner: S,
+        state: AppState,
+        connection_id: Uuid,
+        connected_at: DateTime<Utc>,
+        activity: Arc<ConnectionActivity>,
+    ) -> Self {
+        Self { inner, state, connection_id, connected_at, activity }
+    }
+}
+
+impl<S: Stream + Unpin> Stream for ListenerStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.activity.closed.load(Ordering::Relaxed) {
+            return Poll::Ready(None);
+        }
+
+        let item = Pin::new(&mut self.inner).poll_next(cx);
+        // Only polled while the client takes data, a stalled connection stops updating this
+        if let Poll::Ready(Some(_)) = item {
+            self.activity.last_sent_ms.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+        }
+        item
+    }
+}
+
+impl<S> Drop for ListenerStream<S> {
+    fn drop(&mut self) {
+        let state = self.state.clone();
+        let connection_id = self.connection_id;
+        let connected_at = self.connected_at;
+
+        // No runtime left to end it on while shutting down
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                end_connection(&state, connection_id, connected_at).await;
+            });
+        }
+    }
+}
+
+/// Removes a connection. When it was the user's last one, the listen time since the last save is saved too.
+/// Does nothing if a reconnect with the same id already replaced it.
+pub async fn end_connection(state: &AppState, connection_id: Uuid, connected_at: DateTime<Utc>) {
+    let mut station_guard = state.station.write().await;
+
+    let is_this_connection = station_guard.listeners.get(&connection_id).is_some_and(|l| l.connected_at == connected_at);
+    if !is_this_connection {
+        return;
+    }
+    let Some(removed) = station_guard.listeners.remove(&connection_id) else {
+        return;
+    };
+    tracing::info!("User {} ({}) disconnected from stream, connection {}", removed.username, removed.user_id, connection_id);
+
+    let mut earned = Vec::new();
+    if !station_guard.is_listening(removed.user_id)
+        && let Some(credit) = station_guard.listen_credit.remove(&removed.user_id)
+    {
+        // The rest of the last second is rounded rather than lost
+        let elapsed_ms = Utc::now().signed_duration_since(credit.last_saved_at).num_milliseconds();
+        let seconds = (elapsed_ms + 500) / 1000;
+        if seconds > 0 {
+            earned.push(ListenTime { user_id: removed.user_id, seconds });
+        }
+    }
+
+    let (earned, current_play) = attribute_listen_time(&mut station_guard, earned);
+    events::listeners_left(&state.event_tx, &station_guard, &[removed]);
+    drop(station_guard);
+
+    save_listen_time(&state.db, earned, current_play).await;
+}
+
+/// Ends the connections that went idle, the fallback for ones whose network went away without closing them
+pub async fn end_idle_connections(state: &AppState) {
+    let now = Utc::now();
+    let timeout = Duration::seconds(LISTENER_IDLE_TIMEOUT_SECONDS);
+    let idle = idle_connections(&*state.station.read().await, now, timeout);
+
+    for (connection_id, connected_at, activity) in idle {
+        tracing::info!("Ending idle connection {}", connection_id);
+        activity.closed.store(true, Ordering::Relaxed);
+        end_connection(state, connection_id, connected_at).await;
+    }
+}
+
+fn idle_connections(station: &StationData, now: DateTime<Utc>, timeout: Duration) -> Vec<(Uuid, DateTime<Utc>, Arc<ConnectionActivity>)> {
+    station.listeners.values()
+        .filter(|l| l.is_idle(now, timeout))
+        .map(|l| (l.connection_id, l.connected_at, l.activity.clone()))
+        .collect()
+}
+
+/// The play and song that listen time earned now is credited to
+pub fn current_play(station: &StationData) -> Option<(i64, i64)> {
+    station.current_song.as_ref()
+        .and_then(|song| song.play_id.map(|play_id| (play_id, song.id)))
+}
+
+/// The listen time to save now and the play it goes to. While the current song's play is still
+/// being recorded the time is held back in `unrecorded_listen`, and saved with the play once it is.
+pub fn attribute_listen_time(station: &mut StationData, earned: Vec<ListenTime>) -> (Vec<ListenTime>, Option<(i64, i64)>) {
+    match &station.current_song {
+        Some(song) if song.play_id.is_none() => {
+            station.unrecorded_listen.extend(earned);
+            (Vec::new(), None)
+        }
+        _ => (earned, current_play(station)),
+    }
+}
+
+/// Takes the whole seconds each listening user earned since the last save, once however many devices
+/// they listen on. The remainder is kept for the next time.
+pub fn take_listen_time(station: &mut StationData, now: DateTime<Utc>) -> Vec<ListenTime> {
+    // Users without a connection left stop earning listen time
+    let listening: HashSet<i64> = station.listeners.values().map(|l| l.user_id).collect();
+    station.listen_credit.retain(|user_id, _| listening.contains(user_id));
+
+    let mut earned = Vec::new();
+    for (&user_id, credit) in station.listen_credit.iter_mut() {
+        let elapsed_ms = now.signed_duration_since(credit.last_saved_at).num_milliseconds();
+
+        if elapsed_ms >= 1000 {
+            let seconds = elapsed_ms / 1000;
+            earned.push(ListenTime { user_id, seconds });
+            // Advance by the exact amount saved, preserving the remainder
+            credit.last_saved_at += Duration::seconds(seconds);
+        } else if elapsed_ms < 0 {
+            // Handle potential clock skew
+            credit.last_saved_at = now;
+        }
+    }
+
+    earned
+}
+
+/// Adds earned listen time to the users' totals, their hourly listening and the history of `current_play`
+pub async fn save_listen_time(db: &SqlitePool, earned: Vec<ListenTime>, current_play: Option<(i64, i64)>) {
+    let now = Utc::now();
+
+    for ListenTime { user_id, seconds } in earned {
+        if let Err(e) = orm::users::repository::increment_listen_time(db, user_id, seconds).await {
+            tracing::error!("Failed to update listen time for user {}: {}", user_id, e);
+        }
+
+        if let Err(e) = orm::leaderboard::repository::log_listen_hour(db, user_id, now, seconds * 1000).await {
+            tracing::error!("Failed to log listen time for user {}: {}", user_id, e);
+        }
+
+        if let Some((play_id, song_id)) = current_play
+            && let Err(e) = orm::history::repository::log_listen(db, user_id, play_id, song_id, seconds * 1000).await
+        {
+            tracing::error!("Failed to log listen for user {}: {}", user_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{CurrentSong, Listener};
+
+    fn listener(user_id: i64, connected_at: DateTime<Utc>) -> Listener {
+        Listener {
+            connection_id: Uuid::new_v4(),
+            user_id,
+            username: "listener".to_string(),
+            is_guest: false,
+            connected_at,
+            last_heartbeat: connected_at,
+            activity: Default::default(),
+            start_frame_index: 0,
+            burst_buffer_ms: 0,
+            start_total_duration_micros: 0,
+            desync_history: Default::default(),
+            open_sockets: 0,
+        }
+    }
+
+    #[test]
+    fn socket_keeps_only_its_connection() {
+        let start = Utc::now();
+        let timeout = Duration::seconds(LISTENER_IDLE_TIMEOUT_SECONDS);
+        let mut station = StationData::default();
+
+        // A laptop with the socket open and a phone whose stream died, both quiet for too long
+        let laptop = listener(1, start);
+        let phone = listener(1, start + Duration::seconds(1));
+        let (laptop_id, phone_id) = (laptop.connection_id, phone.connection_id);
+        station.listeners.insert(laptop_id, laptop);
+        station.listeners.insert(phone_id, phone);
+
+        let mut ties = HashSet::new();
+        station.tie_socket(&mut ties, 1, Some(laptop_id));
+        // Heartbeating the same connection again doesn't tie it twice
+        station.tie_socket(&mut ties, 1, Some(laptop_id));
+
+        let later = start + timeout + Duration::seconds(10);
+        let idle: Vec<Uuid> = idle_connections(&station, later, timeout).into_iter().map(|(id, _, _)| id).collect();
+        assert_eq!(idle, vec![phone_id]);
+
+        // Closing the socket starts the laptop's timeout over
+        station.untie_socket(ties, later);
+        assert!(idle_connections(&station, later + Duration::seconds(1), timeout).iter().all(|(id, _, _)| *id != laptop_id));
+        assert!(idle_connections(&station, later + timeout + Duration::seconds(1), timeout).iter().any(|(id, _, _)| *id == laptop_id));
+    }
+
+    #[test]
+    fn listen_time_waits_for_the_play() {
+        let mut station = StationData {
+            current_song: Some(CurrentSong {
+                id: 7,
+                title: "Song".to_string(),
+                artist_names: None,
+                album_title: None,
+                duration_ms: 180_000,
+                started_at: Utc::now(),
+                started_at_ms: 0,
+                started_at_micros: 0,
+                rhythm_data: None,
+                play_id: None,
+            }),
+            ..Default::default()
+        };
+
+        // Held back while the play is being recorded
+        let (now, play) = attribute_listen_time(&mut station, vec![ListenTime { user_id: 1, seconds: 10 }]);
+        assert!(now.is_empty() && play.is_none());
+        assert_eq!(station.unrecorded_listen.len(), 1);
+
+        // Then credited to it as it is earned
+        station.current_song.as_mut().unwrap().play_id = Some(3);
+        let (now, play) = attribute_listen_time(&mut station, vec![ListenTime { user_id: 1, seconds: 10 }]);
+        assert_eq!(now.len(), 1);
+        assert_eq!(play, Some((3, 7)));
+        assert_eq!(station.unrecorded_listen.len(), 1);
+    }
+
+    #[test]
+    fn socket_ties_newest_connection_without_id() {
+        let start = Utc::now();
+        let mut station = StationData::default();
+        let old = listener(1, start);
+        let new = listener(1, start + Duration::seconds(5));
+        let new_id = new.connection_id;
+        station.listeners.insert(old.connection_id, old);
+        station.listeners.insert(new_id, new);
+
+        let mut ties = HashSet::new();
+        station.tie_socket(&mut ties, 1, None);
+        // Someone else's connection can't be tied
+        station.tie_socket(&mut ties, 2, Some(new_id));
+
+        assert_eq!(ties.len(), 1);
+        assert_eq!(station.listeners[&new_id].open_sockets, 1);
+    }
+}
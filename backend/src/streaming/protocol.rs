@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::state::{CurrentSong, EventKind, SongRequest, StationEvent};
+use crate::streaming::model::{ActiveListenerDto, HeartbeatResponse};
+
+/// Version of the WebSocket envelope. Clients may omit it, newer versions are rejected.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Fields shared by every client message, next to the command's `type` and `data`
+#[derive(Deserialize)]
+pub struct ClientEnvelope {
+    #[serde(default = "current_version")]
+    pub v: u32,
+    /// Echoed back as `reply_to` in the reply
+    pub id: Option<String>,
+}
+
+fn current_version() -> u32 {
+    PROTOCOL_VERSION
+}
+
+/// Commands clients send over the WebSocket
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ClientCommand {
+    /// Start receiving station events, or change what is received
+    Subscribe(Option<SubscribeCommand>),
+    Unsubscribe,
+    Heartbeat(HeartbeatCommand),
+    Ping(PingCommand),
+    RequestSong(RequestSongCommand),
+    React(ReactCommand),
+    Chat(ChatCommand),
+    GetCurrentSong,
+    GetListeners,
+}
+
+#[derive(Deserialize, Default)]
+pub struct SubscribeCommand {
+    /// Replaces the event kinds received
+    pub events: Option<Vec<EventKind>>,
+    /// Adds or removes the rhythm events of the playing song
+    pub rhythm: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct HeartbeatCommand {
+    /// The stream connection to refresh, the newest one when omitted
+    pub connection_id: Option<Uuid>,
+    pub client_position_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct PingCommand {
+    /// Client clock when the ping was sent, echoed back untouched
+    pub client_sent_at: f64,
+    /// Playback position of the client's audio element when the ping was sent
+    pub client_position_ms: Option<f64>,
+    /// Round trip measured by the previous exchange, used to correct the desync
+    pub last_rtt_ms: Option<f64>,
+    /// The stream connection the position belongs to, the newest one when omitted
+    pub connection_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct RequestSongCommand {
+    pub song_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ReactCommand {
+    /// An emoji
+    #[serde(alias = "emoji")]
+    pub reaction: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChatCommand {
+    pub body: String,
+}
+
+/// Every message the server sends
+#[derive(Serialize)]
+pub struct ServerEnvelope {
+    pub v: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    #[serde(flatten)]
+    pub message: ServerMessage,
+}
+
+impl ServerEnvelope {
+    pub fn new(message: ServerMessage) -> Self {
+        Self { v: PROTOCOL_VERSION, reply_to: None, message }
+    }
+
+    pub fn reply(id: Option<String>, message: ServerMessage) -> Self {
+        Self { v: PROTOCOL_VERSION, reply_to: id, message }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ServerMessage {
+    /// First message of every connection
+    Welcome(WelcomeMessage),
+    /// A broadcast station event
+    Event(StationEvent),
+    /// The command was applied and has nothing else to return
+    Ack,
+    Pong(PongMessage),
+    HeartbeatAck(HeartbeatResponse),
+    CurrentSong(Option<CurrentSong>),
+    Listeners(Vec<ActiveListenerDto>),
+    SongQueued(QueuedSongMessage),
+    Error(ErrorMessage),
+}
+
+#[derive(Serialize)]
+pub struct WelcomeMessage {
+    pub protocol_version: u32,
+    pub user_id: i64,
+    pub username: String,
+}
+
+/// Station timestamps of a ping exchange.
+/// RTT = (client_received - client_sent_at) - (server_sent_micros - server_received_micros) / 1000
+#[derive(Serialize)]
+pub struct PongMessage {
+    pub client_sent_at: f64,
+    pub server_received_micros: u64,
+    pub server_sent_micros: u64,
+}
+
+#[derive(Serialize)]
+pub struct QueuedSongMessage {
+    /// 1-based place in the request queue
+    pub position: usize,
+    pub request: SongRequest,
+}
+
+#[derive(Serialize)]
+pub struct ErrorMessage {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ErrorMessage {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<AppError> for ErrorMessage {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::InternalServerError(msg) => {
+                tracing::error!("WebSocket command failed: {}", msg);
+                Self::new("internal", "Internal server error")
+            }
+            AppError::BadRequest(msg) => Self::new("bad_request", msg),
+            AppError::Unauthorized(msg) => Self::new("unauthorized", msg),
+            AppError::CustomForbidden(msg) => Self::new("forbidden", msg),
+            AppError::WrongCredentials => Self::new("unauthorized", "Wrong credentials"),
+            AppError::NotFound(msg) => Self::new("not_found", msg),
+            AppError::Conflict(msg) => Self::new("conflict", msg),
+            AppError::ServiceUnavailable(msg) => Self::new("unavailable", msg),
+            AppError::ValidationFailed(details) => Self::new("validation_failed", details.join("\n")),
+            AppError::TooManyRequests(retry_after) => {
+                Self::new("too_many_requests", format!("Too many attempts, try again in {} seconds", retry_after))
+            }
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use axum::extract::ws::{close_code, CloseFrame, Message, Utf8Bytes, WebSocket};
+use chrono::Utc;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::config::{
+    CHAT_COOLDOWN_MS, MAX_CHAT_MESSAGE_LENGTH, MAX_REACTION_LENGTH, MAX_SONG_REQUESTS, MAX_SONG_REQUESTS_PER_USER,
+    REACTION_COOLDOWN_MS,
+};
+use crate::error::AppError;
+use crate::orm::chat;
+use crate::orm::users::models::{Permission, User};
+use crate::state::{AppState, DesyncSample, DesyncSource, EventKind, SocketTie, SongRequest, StationEvent};
+use crate::streaming::events;
+use crate::streaming::handlers::{active_listeners, apply_heartbeat};
+use crate::streaming::protocol::{
+    ClientCommand, ClientEnvelope, ErrorMessage, PingCommand, PongMessage, QueuedSongMessage, ServerEnvelope,
+    ServerMessage, WelcomeMessage, PROTOCOL_VERSION,
+};
+
+/// Per connection state
+struct Session {
+    user: User,
+    /// Whether broadcast station events are forwarded
+    subscribed: bool,
+    /// Event kinds forwarded while subscribed
+    events: HashSet<EventKind>,
+    /// Set by `Subscribe`, the current state is sent again after the reply
+    needs_snapshot: bool,
+    /// Highest rhythm event sequence sent, so events replayed on subscribe aren't sent twice
+    last_rhythm_seq: u64,
+    last_reaction_at: Option<Instant>,
+    last_chat_at: Option<Instant>,
+    /// False for API tokens without the `chat:write` scope
+    can_chat: bool,
+    /// Stream connections this socket named or heartbeated, kept from going idle while it is open
+    ties: HashSet<SocketTie>,
+}
+
+impl Session {
+    /// The event as this connection should receive it, if at all
+    fn filter(&mut self, event: StationEvent) -> Option<StationEvent> {
+        if !self.subscribed || !self.events.contains(&event.kind()) {
+            return None;
+        }
+
+        match event {
+            StationEvent::RhythmEvents(events) => {
+                let fresh: Vec<_> = events.into_iter().filter(|e| e.seq > self.last_rhythm_seq).collect();
+                self.last_rhythm_seq = fresh.last()?.seq;
+                Some(StationEvent::RhythmEvents(fresh))
+            }
+            other => Some(other),
+        }
+    }
+}
+
+pub async fn handle_socket(
+    socket: WebSocket,
+    state: AppState,
+    user: User,
+    events: HashSet<EventKind>,
+    can_chat: bool,
+    connection_id: Option<Uuid>,
+) {
+    // Connections start subscribed, `Unsubscribe` turns the events off
+    let mut session = Session {
+        user,
+        subscribed: true,
+        events,
+        needs_snapshot: false,
+        last_rhythm_seq: 0,
+        last_reaction_at: None,
+        last_chat_at: None,
+        can_chat,
+        ties: HashSet::new(),
+    };
+
+    // The stream connection named on connect counts as present until the socket closes, however it closes
+    if connection_id.is_some() {
+        state.station.write().await.tie_socket(&mut session.ties, session.user.id, connection_id);
+    }
+    serve(socket, &state, &mut session).await;
+    state.station.write().await.untie_socket(std::mem::take(&mut session.ties), Utc::now());
+}
+
+async fn serve(mut socket: WebSocket, state: &AppState, session: &mut Session) {
+    let mut rx = state.event_tx.subscribe();
+
+    let welcome = ServerMessage::Welcome(WelcomeMessage {
+        protocol_version: PROTOCOL_VERSION,
+        user_id: session.user.id,
+        username: session.user.username.clone(),
+    });
+
+    if send(&mut socket, &ServerEnvelope::new(welcome)).await.is_err()
+        || send_snapshot(&mut socket, state, session).await.is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    // axum answers the client's close frame for us
+                    Some(Ok(Message::Close(_))) | None => return,
+                    Some(Err(e)) => {
+                        tracing::debug!("WebSocket of user {} failed: {}", session.user.id, e);
+                        return;
+                    }
+                    Some(Ok(_)) => continue,
+                };
+
+                let reply = handle_message(state, session, &text).await;
+                if send(&mut socket, &reply).await.is_err() {
+                    return;
+                }
+
+                if session.needs_snapshot {
+                    session.needs_snapshot = false;
+                    if send_snapshot(&mut socket, state, session).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            event = rx.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("WebSocket of user {} lagged, {} events skipped", session.user.id, skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if let Some(event) = session.filter(event)
+                    && send(&mut socket, &ServerEnvelope::new(ServerMessage::Event(event))).await.is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    // The station stopped, tell the client instead of dropping the connection
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: close_code::AWAY,
+            reason: Utf8Bytes::from_static("Station stopped"),
+        })))
+        .await;
+}
+
+async fn handle_message(state: &AppState, session: &mut Session, text: &str) -> ServerEnvelope {
+    let envelope = match serde_json::from_str::<ClientEnvelope>(text) {
+        Ok(envelope) => envelope,
+        Err(e) => return error_reply(None, ErrorMessage::new("invalid_message", e.to_string())),
+    };
+
+    if envelope.v > PROTOCOL_VERSION {
+        let message = format!("Protocol version {} is not supported, latest is {}", envelope.v, PROTOCOL_VERSION);
+        return error_reply(envelope.id, ErrorMessage::new("unsupported_version", message));
+    }
+
+    let command = match serde_json::from_str::<ClientCommand>(text) {
+        Ok(command) => command,
+        Err(e) => return error_reply(envelope.id, ErrorMessage::new("invalid_message", e.to_string())),
+    };
+
+    match run_command(state, session, command).await {
+        Ok(message) => ServerEnvelope::reply(envelope.id, message),
+        Err(error) => error_reply(envelope.id, error),
+    }
+}
+
+fn error_reply(id: Option<String>, error: ErrorMessage) -> ServerEnvelope {
+    ServerEnvelope::reply(id, ServerMessage::Error(error))
+}
+
+async fn run_command(state: &AppState, session: &mut Session, command: ClientCommand) -> Result<ServerMessage, ErrorMessage> {
+    let user_id = session.user.id;
+
+    match command {
+        ClientCommand::Subscribe(options) => {
+            let options = options.unwrap_or_default();
+            if let Some(events) = options.events {
+                session.events = events.into_iter().collect();
+            }
+            match options.rhythm {
+                Some(true) => session.events.insert(EventKind::RhythmEvents),
+                Some(false) => session.events.remove(&EventKind::RhythmEvents),
+                None => false,
+            };
+            session.subscribed = true;
+            session.needs_snapshot = true;
+            Ok(ServerMessage::Ack)
+        }
+        ClientCommand::Unsubscribe => {
+            session.subscribed = false;
+            Ok(ServerMessage::Ack)
+        }
+        ClientCommand::Heartbeat(heartbeat) => {
+            let mut station_guard = state.station.write().await;
+            let reply = apply_heartbeat(&mut station_guard, user_id, heartbeat.connection_id, heartbeat.client_position_ms)
+                .map(ServerMessage::HeartbeatAck)
+                .ok_or_else(|| ErrorMessage::new("not_listening", "Not connected to the stream"));
+            station_guard.tie_socket(&mut session.ties, user_id, heartbeat.connection_id);
+            reply
+        }
+        ClientCommand::Ping(ping) => Ok(ServerMessage::Pong(handle_ping(state, session, ping).await)),
+        ClientCommand::RequestSong(request) => request_song(state, &session.user, request.song_id).await,
+        ClientCommand::React(_) | ClientCommand::Chat(_) if !session.can_chat => {
+            Err(AppError::CustomForbidden("API token lacks the chat:write scope".to_string()).into())
+        }
+        ClientCommand::React(react) => send_reaction(state, session, &react.reaction).await,
+        ClientCommand::Chat(chat) => send_chat(state, session, &chat.body).await,
+        ClientCommand::GetCurrentSong => {
+            let station_guard = state.station.read().await;
+            Ok(ServerMessage::CurrentSong(station_guard.current_song.clone()))
+        }
+        ClientCommand::GetListeners => {
+            let station_guard = state.station.read().await;
+            Ok(ServerMessage::Listeners(active_listeners(&station_guard)))
+        }
+    }
+}
+
+/// Stamps a ping with the station timeline and, when the client reports its playback
+/// position, records how far it is from the station. A ping with a position also counts as a heartbeat.
+async fn handle_ping(state: &AppState, session: &mut Session, ping: PingCommand) -> PongMessage {
+    let user_id = session.user.id;
+    let mut station_guard = state.station.write().await;
+    let server_received_micros = station_guard.playback_position.now_micros();
+
+    if let Some(client_position_ms) = ping.client_position_ms
+        && let Some(listener) = station_guard.connection_mut(user_id, ping.connection_id)
+    {
+        listener.last_heartbeat = Utc::now();
+
+        // The position was sampled when the ping left the client, half a round trip ago
+        let one_way_micros = ping.last_rtt_ms.map_or(0.0, |rtt| rtt.max(0.0) * 500.0);
+        let station_at_send = server_received_micros as f64 - one_way_micros;
+        let client_abs_micros = listener.client_base_micros() as f64 + client_position_ms.max(0.0) * 1_000.0;
+
+        listener.record_desync(DesyncSample {
+            measured_at: Utc::now(),
+            desync_ms: ((station_at_send - client_abs_micros) / 1_000.0).round() as i64,
+            rtt_ms: ping.last_rtt_ms,
+            source: DesyncSource::Ping,
+        });
+        station_guard.tie_socket(&mut session.ties, user_id, ping.connection_id);
+    }
+
+    PongMessage {
+        client_sent_at: ping.client_sent_at,
+        server_received_micros: server_received_micros as u64,
+        server_sent_micros: station_guard.playback_position.now_micros() as u64,
+    }
+}
+
+/// Rejects users muted by a moderator
+async fn ensure_not_muted(state: &AppState, user_id: i64) -> Result<(), ErrorMessage> {
+    let muted_until = chat::repository::find_active_mute(&state.db, user_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    match muted_until {
+        Some(until) => Err(ErrorMessage::new("muted", format!("You are muted until {}", until.to_rfc3339()))),
+        None => Ok(()),
+    }
+}
+
+/// Characters that are an emoji on their own
+fn is_pictograph(c: char) -> bool {
+    matches!(
+        c as u32,
+        0xA9 | 0xAE | 0x203C | 0x2049 | 0x2122 | 0x2139 | 0x2194..=0x2199 | 0x21A9..=0x21AA
+            | 0x231A..=0x231B | 0x2328 | 0x23CF | 0x23E9..=0x23F3 | 0x23F8..=0x23FA | 0x24C2
+            | 0x25AA..=0x25AB | 0x25B6 | 0x25C0 | 0x25FB..=0x25FE | 0x2600..=0x27BF | 0x2934..=0x2935
+            | 0x2B05..=0x2B07 | 0x2B1B..=0x2B1C | 0x2B50 | 0x2B55 | 0x3030 | 0x303D | 0x3297 | 0x3299
+            // Flags, skin tones and the rest of the emoji blocks
+            | 0x1F000..=0x1FAFF
+    )
+}
+
+/// Characters that only change the emoji next to them: joiners, variation selectors and flag tags
+fn is_emoji_component(c: char) -> bool {
+    matches!(c as u32, 0x200D | 0xFE0E | 0xFE0F | 0x20E3 | 0xE0020..=0xE007F)
+}
+
+/// Whether `text` is one or more emoji and nothing else
+fn is_emoji(text: &str) -> bool {
+    let mut chars = text.chars().peekable();
+    let mut any = false;
+
+    while let Some(c) = chars.next() {
+        if matches!(c, '0'..='9' | '#' | '*') {
+            // Only part of a keycap like 1️⃣
+            chars.next_if_eq(&'\u{FE0F}');
+            if chars.next_if_eq(&'\u{20E3}').is_none() {
+                return false;
+            }
+        } else if !is_pictograph(c) {
+            if !is_emoji_component(c) {
+                return false;
+            }
+            continue;
+        }
+        any = true;
+    }
+
+    any
+}
+
+/// Stores an emoji at the current position of the playing song and broadcasts it
+async fn send_reaction(state: &AppState, session: &mut Session, reaction: &str) -> Result<ServerMessage, ErrorMessage> {
+    let reaction = reaction.trim();
+    if reaction.chars().count() > MAX_REACTION_LENGTH || !is_emoji(reaction) {
+        return Err(AppError::BadRequest(format!(
+            "Reactions must be emoji, at most {} characters",
+            MAX_REACTION_LENGTH
+        ))
+        .into());
+    }
+
+    let cooldown = Duration::from_millis(REACTION_COOLDOWN_MS);
+    if session.last_reaction_at.is_some_and(|at| at.elapsed() < cooldown) {
+        return Err(ErrorMessage::new("rate_limited", "Too many reactions, slow down"));
+    }
+
+    ensure_not_muted(state, session.user.id).await?;
+
+    let (song_id, position_ms) = {
+        let station_guard = state.station.read().await;
+        let song = station_guard.current_song.as_ref()
+            .ok_or(AppError::Conflict("Nothing is playing".to_string()))?;
+        let position = station_guard.playback_position.now_micros().saturating_sub(song.started_at_micros);
+        (song.id, (position / 1_000) as i64)
+    };
+
+    session.last_reaction_at = Some(Instant::now());
+
+    let reaction = chat::repository::create_reaction(&state.db, session.user.id, song_id, reaction, position_ms)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let _ = state.event_tx.send(StationEvent::Reaction(reaction));
+
+    Ok(ServerMessage::Ack)
+}
+
+/// Stores a chat message with the song playing and broadcasts it
+async fn send_chat(state: &AppState, session: &mut Session, body: &str) -> Result<ServerMessage, ErrorMessage> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Messages must be between 1 and {} characters",
+            MAX_CHAT_MESSAGE_LENGTH
+        ))
+        .into());
+    }
+
+    let cooldown = Duration::from_millis(CHAT_COOLDOWN_MS);
+    if session.last_chat_at.is_some_and(|at| at.elapsed() < cooldown) {
+        return Err(ErrorMessage::new("rate_limited", "Too many messages, slow down"));
+    }
+
+    ensure_not_muted(state, session.user.id).await?;
+
+    session.last_chat_at = Some(Instant::now());
+
+    let song_id = state.station.read().await.current_song.as_ref().map(|s| s.id);
+    let message = chat::repository::create_message(&state.db, session.user.id, song_id, body)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let _ = state.event_tx.send(StationEvent::ChatMessage(message));
+
+    Ok(ServerMessage::Ack)
+}
+
+async fn request_song(state: &AppState, user: &User, song_id: i64) -> Result<ServerMessage, ErrorMessage> {
+    let song = crate::orm::songs::repository::find_by_id(&state.db, song_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("Song not found".to_string()))?;
+
+    if !crate::config::get_music_dir().join(format!("{}.mp3", song.id)).exists() {
+        return Err(AppError::NotFound("Song has no audio file".to_string()).into());
+    }
+
+    let mut station_guard = state.station.write().await;
+
+    if station_guard.current_song.as_ref().is_some_and(|s| s.id == song.id) {
+        return Err(AppError::Conflict("Song is already playing".to_string()).into());
+    }
+    if station_guard.song_requests.iter().any(|r| r.song_id == song.id) {
+        return Err(AppError::Conflict("Song is already requested".to_string()).into());
+    }
+    if station_guard.song_requests.len() >= MAX_SONG_REQUESTS {
+        return Err(AppError::Conflict("The request queue is full".to_string()).into());
+    }
+    // DJs run the queue, the per-user limit doesn't apply to them
+    if !user.role.has(Permission::ManageQueue)
+        && station_guard.song_requests.iter().filter(|r| r.requested_by == user.id).count() >= MAX_SONG_REQUESTS_PER_USER
+    {
+        return Err(AppError::Conflict(format!(
+            "You can have at most {} pending requests",
+            MAX_SONG_REQUESTS_PER_USER
+        ))
+        .into());
+    }
+
+    let request = SongRequest {
+        song_id: song.id,
+        title: song.title,
+        requested_by: user.id,
+        requested_by_name: user.username.clone(),
+        requested_at: Utc::now(),
+    };
+    station_guard.song_requests.push_back(request.clone());
+    events::queue_changed(&state.event_tx, &station_guard);
+    let position = station_guard.song_requests.len();
+    drop(station_guard);
+
+    // Counted for the leaderboards, the queue itself lives in memory
+    if let Err(e) = crate::orm::leaderboard::repository::log_request(&state.db, user.id, song.id, request.requested_at).await {
+        tracing::error!("Failed to log song request of user {}: {}", user.id, e);
+    }
+
+    Ok(ServerMessage::SongQueued(QueuedSongMessage { position, request }))
+}
+
+/// Sends the current song, listener count, request queue and the rhythm events that haven't fired yet,
+/// as far as the connection is subscribed to them
+async fn send_snapshot(socket: &mut WebSocket, state: &AppState, session: &mut Session) -> Result<(), axum::Error> {
+    if !session.subscribed {
+        return Ok(());
+    }
+
+    let mut events = Vec::new();
+    {
+        let guard = state.station.read().await;
+        if let Some(song) = &guard.current_song {
+            events.push(StationEvent::SongChange(song.clone()));
+        }
+        events.push(StationEvent::ListenerCount(guard.listener_count()));
+        events.push(StationEvent::QueueChanged(guard.song_requests.iter().cloned().collect()));
+
+        // Late joiners only get the events that haven't fired yet
+        if session.events.contains(&EventKind::RhythmEvents) {
+            let position = guard.playback_position.total_duration_micros;
+            let remaining: Vec<_> = guard.upcoming_rhythm.iter()
+                .filter(|e| (e.at_micros as u128) >= position)
+                .cloned()
+                .collect();
+            events.push(StationEvent::RhythmEvents(remaining));
+        }
+    }
+
+    for event in events {
+        if let Some(event) = session.filter(event) {
+            send(socket, &ServerEnvelope::new(ServerMessage::Event(event))).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn send(socket: &mut WebSocket, envelope: &ServerEnvelope) -> Result<(), axum::Error> {
+    match serde_json::to_string(envelope) {
+        Ok(json) => socket.send(Message::Text(Utf8Bytes::from(json))).await,
+        Err(e) => {
+            tracing::error!("Failed to serialize WebSocket message: {}", e);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_reactions() {
+        for reaction in ["👍", "🔥🔥", "❤️", "👍🏽", "👨‍👩‍👧", "🇪🇸", "🏴󠁧󠁢󠁳󠁣󠁴󠁿", "1️⃣", "#️⃣", "*⃣", "☕", "©️"] {
+            assert!(is_emoji(reaction), "{} should be accepted", reaction);
+        }
+    }
+
+    #[test]
+    fn text_reactions() {
+        for reaction in ["", "lol", "1", "#", "ñandú", "音楽", "すごい", "👍 👍", "👍lol", "\u{200D}", "\u{FE0F}"] {
+            assert!(!is_emoji(reaction), "{:?} should be refused", reaction);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::{
+    LOGIN_ACCOUNT_FREE_ATTEMPTS, LOGIN_ACCOUNT_LOCKOUT_FAILURES, LOGIN_BACKOFF_BASE_SECONDS, LOGIN_BACKOFF_MAX_SECONDS,
+    LOGIN_FAILURE_WINDOW_MINUTES, LOGIN_IP_FREE_ATTEMPTS, LOGIN_IP_LOCKOUT_FAILURES, LOGIN_LOCKOUT_MINUTES,
+    LOGIN_THROTTLE_MAX_ENTRIES,
+};
+
+/// What failed logins are counted against
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThrottleKey {
+    Ip(String),
+    /// Lowercased so case variants of a name share the count
+    Account(String),
+}
+
+impl ThrottleKey {
+    /// Failures allowed before each further one is delayed, and the count that locks it out
+    fn limits(&self) -> (u32, u32) {
+        match self {
+            // Generous, many listeners can share an address behind a NAT
+            ThrottleKey::Ip(_) => (LOGIN_IP_FREE_ATTEMPTS, LOGIN_IP_LOCKOUT_FAILURES),
+            ThrottleKey::Account(_) => (LOGIN_ACCOUNT_FREE_ATTEMPTS, LOGIN_ACCOUNT_LOCKOUT_FAILURES),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Failures {
+    fn is_expired(&self, now: Instant) -> bool {
+        let window = Duration::from_secs(LOGIN_FAILURE_WINDOW_MINUTES * 60);
+        self.blocked_until.is_none_or(|until| until <= now) && now.duration_since(self.last_failure) > window
+    }
+}
+
+/// Whether a failed login locked anything out
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Lockout {
+    pub ip: bool,
+    pub account: bool,
+}
+
+/// A login let through by `begin_attempt`. It already counts as failed, `record_success` takes that back.
+#[derive(Debug)]
+pub struct LoginAttempt {
+    keys: [ThrottleKey; 2],
+    /// What counting the attempt as failed locked out
+    pub lockout: Lockout,
+    /// Each key's block before the attempt and the one the attempt set
+    blocks: [(Option<Instant>, Option<Instant>); 2],
+}
+
+/// Failed logins per client address and per account, with exponential backoff and lockout.
+/// Kept in memory, a restart forgets them.
+#[derive(Debug, Default)]
+pub struct LoginThrottle {
+    failures: HashMap<ThrottleKey, Failures>,
+}
+
+impl LoginThrottle {
+    pub fn keys(ip: Option<&str>, identity: &str) -> [ThrottleKey; 2] {
+        [
+            ThrottleKey::Ip(ip.unwrap_or("unknown").to_string()),
+            ThrottleKey::Account(identity.trim().to_lowercase()),
+        ]
+    }
+
+    /// Lets a login through unless the keys are blocked, in which case it's the seconds until one may be tried.
+    /// The attempt is counted as failed right away, so parallel attempts can't all get past the check
+    /// before the first failure is known.
+    pub fn begin_attempt(&mut self, keys: [ThrottleKey; 2]) -> Result<LoginAttempt, u64> {
+        self.begin_attempt_at(keys, Instant::now())
+    }
+
+    fn begin_attempt_at(&mut self, keys: [ThrottleKey; 2], now: Instant) -> Result<LoginAttempt, u64> {
+        if let Some(retry_after) = self.retry_after(&keys, now) {
+            return Err(retry_after);
+        }
+        if self.failures.len() >= LOGIN_THROTTLE_MAX_ENTRIES {
+            self.purge_at(now);
+        }
+        let new_keys = keys.iter().filter(|key| !self.failures.contains_key(key)).count();
+        self.make_room(new_keys, now);
+
+        let mut lockout = Lockout::default();
+        let blocks = [0, 1].map(|i| {
+            // Still full of blocked keys, a new one goes uncounted
+            if !self.failures.contains_key(&keys[i]) && self.failures.len() >= LOGIN_THROTTLE_MAX_ENTRIES {
+                return (None, None);
+            }
+            let (before, set, locked) = self.count_failure(&keys[i], now);
+            if locked {
+                match keys[i] {
+                    ThrottleKey::Ip(_) => lockout.ip = true,
+                    ThrottleKey::Account(_) => lockout.account = true,
+                }
+            }
+            (before, set)
+        });
+
+        Ok(LoginAttempt { keys, lockout, blocks })
+    }
+
+    /// Seconds until a login may be attempted with these keys, `None` if it may now
+    fn retry_after(&self, keys: &[ThrottleKey], now: Instant) -> Option<u64> {
+        keys.iter()
+            .filter_map(|key| self.failures.get(key)?.blocked_until)
+            .filter(|until| *until > now)
+            .map(|until| until.duration_since(now).as_secs().max(1))
+            .max()
+    }
+
+    /// Evicts the entries that failed least recently until `needed` more fit under the cap.
+    /// Blocked ones are kept, so flooding with new keys can't lift a block.
+    fn make_room(&mut self, needed: usize, now: Instant) {
+        while self.failures.len() + needed > LOGIN_THROTTLE_MAX_ENTRIES {
+            let oldest = self.failures.iter()
+                .filter(|(_, failures)| failures.blocked_until.is_none_or(|until| until <= now))
+                .min_by_key(|(_, failures)| failures.last_failure)
+                .map(|(key, _)| key.clone());
+            let Some(oldest) = oldest else { break };
+            self.failures.remove(&oldest);
+        }
+    }
+
+    /// Adds a failure to the key, returns its block before and after and whether that locked it out
+    fn count_failure(&mut self, key: &ThrottleKey, now: Instant) -> (Option<Instant>, Option<Instant>, bool) {
+        let failures = self.failures.entry(key.clone()).or_insert(Failures {
+            count: 0,
+            last_failure: now,
+            blocked_until: None,
+        });
+        if failures.is_expired(now) {
+            failures.count = 0;
+        }
+
+        failures.count += 1;
+        failures.last_failure = now;
+
+        let (free_attempts, lockout_after) = key.limits();
+        let locked = failures.count >= lockout_after;
+        let delay = if locked {
+            Some(Duration::from_secs(LOGIN_LOCKOUT_MINUTES * 60))
+        } else if failures.count > free_attempts {
+            let doublings = (failures.count - free_attempts - 1).min(16);
+            let seconds = (LOGIN_BACKOFF_BASE_SECONDS << doublings).min(LOGIN_BACKOFF_MAX_SECONDS);
+            Some(Duration::from_secs(seconds))
+        } else {
+            None
+        };
+
+        let before = failures.blocked_until;
+        failures.blocked_until = delay.map(|delay| now + delay);
+        (before, failures.blocked_until, locked)
+    }
+
+    /// Clears the account's failures and takes the attempt back off the address. The address keeps
+    /// its earlier failures, otherwise logging into one's own account would reset them while guessing at others.
+    pub fn record_success(&mut self, attempt: LoginAttempt) {
+        for (key, (before, set)) in attempt.keys.iter().zip(attempt.blocks) {
+            match key {
+                ThrottleKey::Account(_) => {
+                    self.failures.remove(key);
+                }
+                ThrottleKey::Ip(_) => {
+                    if let Some(failures) = self.failures.get_mut(key) {
+                        failures.count = failures.count.saturating_sub(1);
+                        // Unless another failure has blocked it since
+                        if failures.blocked_until == set {
+                            failures.blocked_until = before;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Forgets an account's failures, after its password was reset
+    pub fn clear_account(&mut self, identity: &str) {
+        self.failures.remove(&ThrottleKey::Account(identity.trim().to_lowercase()));
+    }
+
+    /// Drops failures that no longer count, returns how many
+    pub fn purge(&mut self) -> usize {
+        self.purge_at(Instant::now())
+    }
+
+    fn purge_at(&mut self, now: Instant) -> usize {
+        let before = self.failures.len();
+        self.failures.retain(|_, failures| !failures.is_expired(now));
+        before - self.failures.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LOGIN_ACCOUNT_LOCKOUT_FAILURES;
+
+    fn keys() -> [ThrottleKey; 2] {
+        LoginThrottle::keys(Some("192.0.2.1"), "Someone")
+    }
+
+    /// Fails `count` logins, each once the previous block is over, returns when the last one was made
+    fn fail(throttle: &mut LoginThrottle, count: u32, mut now: Instant) -> Instant {
+        for _ in 0..count {
+            if let Some(wait) = throttle.retry_after(&keys(), now) {
+                now += Duration::from_secs(wait);
+            }
+            throttle.begin_attempt_at(keys(), now).expect("attempt should be let through");
+        }
+        now
+    }
+
+    #[test]
+    fn backoff_doubles_after_the_free_attempts() {
+        let mut throttle = LoginThrottle::default();
+        let now = fail(&mut throttle, LOGIN_ACCOUNT_FREE_ATTEMPTS, Instant::now());
+        assert_eq!(throttle.retry_after(&keys(), now), None);
+
+        let mut expected = LOGIN_BACKOFF_BASE_SECONDS;
+        let mut now = now;
+        for _ in 0..3 {
+            now = fail(&mut throttle, 1, now);
+            assert_eq!(throttle.retry_after(&keys(), now), Some(expected));
+            assert_eq!(throttle.begin_attempt_at(keys(), now).unwrap_err(), expected);
+            expected *= 2;
+        }
+    }
+
+    #[test]
+    fn account_locks_out_at_the_threshold() {
+        let mut throttle = LoginThrottle::default();
+        let now = fail(&mut throttle, LOGIN_ACCOUNT_LOCKOUT_FAILURES - 1, Instant::now());
+        let now = now + Duration::from_secs(LOGIN_BACKOFF_MAX_SECONDS);
+
+        let attempt = throttle.begin_attempt_at(keys(), now).unwrap();
+        assert_eq!(attempt.lockout, Lockout { ip: false, account: true });
+        assert_eq!(throttle.retry_after(&keys(), now), Some(LOGIN_LOCKOUT_MINUTES * 60));
+    }
+
+    #[test]
+    fn failures_reset_after_the_window() {
+        let mut throttle = LoginThrottle::default();
+        let now = fail(&mut throttle, LOGIN_ACCOUNT_FREE_ATTEMPTS + 1, Instant::now());
+
+        let later = now + Duration::from_secs(LOGIN_FAILURE_WINDOW_MINUTES * 60 + 1);
+        let later = fail(&mut throttle, LOGIN_ACCOUNT_FREE_ATTEMPTS, later);
+        assert_eq!(throttle.retry_after(&keys(), later), None);
+    }
+
+    #[test]
+    fn parallel_attempts_are_counted_before_they_finish() {
+        let mut throttle = LoginThrottle::default();
+        let now = Instant::now();
+        for _ in 0..LOGIN_ACCOUNT_FREE_ATTEMPTS + 1 {
+            throttle.begin_attempt_at(keys(), now).unwrap();
+        }
+
+        assert!(throttle.begin_attempt_at(keys(), now).is_err());
+    }
+
+    #[test]
+    fn success_clears_the_account_and_undoes_the_attempt() {
+        let mut throttle = LoginThrottle::default();
+        let now = Instant::now();
+        // Other accounts from the same address, up to where the next failure would be delayed
+        for i in 0..LOGIN_IP_FREE_ATTEMPTS {
+            throttle.begin_attempt_at(LoginThrottle::keys(Some("192.0.2.1"), &format!("other{}", i)), now).unwrap();
+        }
+
+        let attempt = throttle.begin_attempt_at(keys(), now).unwrap();
+        assert!(throttle.retry_after(&keys(), now).is_some());
+        throttle.record_success(attempt);
+
+        assert!(!throttle.failures.contains_key(&keys()[1]));
+        let ip = &throttle.failures[&keys()[0]];
+        assert_eq!(ip.count, LOGIN_IP_FREE_ATTEMPTS);
+        assert_eq!(ip.blocked_until, None);
+    }
+
+    /// Fills the throttle to the cap with other addresses, failed at `now` and blocked until `blocked_until`
+    fn fill(throttle: &mut LoginThrottle, now: Instant, blocked_until: Option<Instant>) {
+        for i in 0..LOGIN_THROTTLE_MAX_ENTRIES {
+            throttle.failures.insert(ThrottleKey::Ip(format!("filler{}", i)), Failures {
+                count: 1,
+                last_failure: now + Duration::from_millis(i as u64),
+                blocked_until,
+            });
+        }
+    }
+
+    #[test]
+    fn full_throttle_evicts_the_oldest_unblocked_entries() {
+        let mut throttle = LoginThrottle::default();
+        let now = Instant::now();
+        fill(&mut throttle, now, None);
+
+        throttle.begin_attempt_at(keys(), now + Duration::from_secs(1)).unwrap();
+        assert_eq!(throttle.failures.len(), LOGIN_THROTTLE_MAX_ENTRIES);
+        assert!(keys().iter().all(|key| throttle.failures.contains_key(key)));
+        assert!(!throttle.failures.contains_key(&ThrottleKey::Ip("filler0".to_string())));
+        assert!(!throttle.failures.contains_key(&ThrottleKey::Ip("filler1".to_string())));
+        assert!(throttle.failures.contains_key(&ThrottleKey::Ip("filler2".to_string())));
+    }
+
+    #[test]
+    fn full_throttle_keeps_blocked_entries() {
+        let mut throttle = LoginThrottle::default();
+        let now = Instant::now();
+        fill(&mut throttle, now, Some(now + Duration::from_secs(60)));
+
+        let attempt = throttle.begin_attempt_at(keys(), now).unwrap();
+        assert_eq!(attempt.lockout, Lockout::default());
+        assert_eq!(throttle.failures.len(), LOGIN_THROTTLE_MAX_ENTRIES);
+        assert!(keys().iter().all(|key| !throttle.failures.contains_key(key)));
+        assert!(throttle.begin_attempt_at([ThrottleKey::Ip("filler0".to_string()), keys()[1].clone()], now).is_err());
+    }
+}
@@ -121,6 +121,13 @@ export default function LoginPage() {
                             Sign up here
                         </Link>
                     </p>
+
+                    <p className="text-center text-sky-800/60 font-medium text-sm">
+                        Forgot your password? Ask an admin for a{' '}
+                        <Link href="/reset-password" className="text-sky-600 hover:text-sky-700 font-bold underline decoration-sky-500/30 underline-offset-4">
+                            reset code
+                        </Link>
+                    </p>
                 </GlassCard>
             </motion.div>
         </div>
@@ -1,21 +1,34 @@
 'use client';
 
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import { useAuth } from '@/contexts/AuthContext';
 import { GlassCard } from '@/components/ui/GlassCard';
 import { GlassButton } from '@/components/ui/GlassButton';
 import { useRouter } from 'next/navigation';
 import Link from 'next/link';
-import { UserPlus, User, Lock, AlertCircle } from 'lucide-react';
+import { UserPlus, User, Lock, AlertCircle, Ticket, KeyRound } from 'lucide-react';
+import { api } from '@/lib/api';
+import { RegistrationInfo } from '@/lib/types';
 import { motion } from 'framer-motion';
 
 export default function RegisterPage() {
     const [username, setUsername] = useState('');
     const [password, setPassword] = useState('');
     const [confirmPassword, setConfirmPassword] = useState('');
+    const [inviteCode, setInviteCode] = useState('');
+    const [setupToken, setSetupToken] = useState('');
+    const [registration, setRegistration] = useState<RegistrationInfo | null>(null);
+    const [pending, setPending] = useState(false);
     const [error, setError] = useState<string | null>(null);
     const [loading, setLoading] = useState(false);
 
+    useEffect(() => {
+        api.auth.registration().then(setRegistration).catch(() => setRegistration(null));
+    }, []);
+
+    const needsSetup = registration?.needs_setup ?? false;
+    const acceptsInvites = registration?.mode === 'invite' || registration?.mode === 'approval';
+
     const { register } = useAuth();
     const router = useRouter();
 
@@ -31,8 +44,17 @@ export default function RegisterPage() {
         setLoading(true);
 
         try {
-            await register({ username, password });
-            router.push('/');
+            const user = await register({
+                username,
+                password,
+                invite_code: inviteCode.trim() || undefined,
+                setup_token: setupToken.trim() || undefined,
+            });
+            if (user.status === 'pending') {
+                setPending(true);
+            } else {
+                router.push('/');
+            }
         } catch (err: any) {
             setError(err.message || 'Failed to register. Please try again.');
         } finally {
@@ -57,6 +79,18 @@ export default function RegisterPage() {
                         <p className="text-sky-800/60 font-medium">Create an account to start vibing</p>
                     </div>
 
+                    {pending && (
+                        <p className="p-4 bg-emerald-500/10 border border-emerald-500/20 rounded-2xl text-emerald-700 text-sm font-medium text-center">
+                            Your account was created and is waiting for an admin to approve it. You can log in once it is approved.
+                        </p>
+                    )}
+
+                    {registration?.mode === 'closed' && !needsSetup && (
+                        <p className="p-4 bg-sky-500/10 border border-sky-500/20 rounded-2xl text-sky-800 text-sm font-medium text-center">
+                            Registration is closed on this station.
+                        </p>
+                    )}
+
                     <form onSubmit={handleSubmit} className="space-y-6">
                         <div className="space-y-4">
                             <div className="space-y-2">
@@ -109,6 +143,43 @@ export default function RegisterPage() {
                                     />
                                 </div>
                             </div>
+
+                            {acceptsInvites && !setupToken && (
+                                <div className="space-y-2">
+                                    <label className="text-sm font-bold text-sky-900 px-1">Invite Code</label>
+                                    <div className="relative group">
+                                        <div className="absolute inset-y-0 left-0 pl-4 flex items-center pointer-events-none">
+                                            <Ticket className="h-5 w-5 text-sky-400 group-focus-within:text-sky-600 transition-colors" />
+                                        </div>
+                                        <input
+                                            type="text"
+                                            required={registration?.mode === 'invite'}
+                                            value={inviteCode}
+                                            onChange={(e) => setInviteCode(e.target.value)}
+                                            className="w-full pl-12 pr-4 py-3 bg-white/50 border border-white/40 focus:border-sky-400 focus:ring-4 focus:ring-sky-400/10 outline-none rounded-2xl transition-all font-medium text-sky-900"
+                                            placeholder={registration?.mode === 'approval' ? 'Optional, skips the approval' : 'Code from your invite'}
+                                        />
+                                    </div>
+                                </div>
+                            )}
+
+                            {needsSetup && (
+                                <div className="space-y-2">
+                                    <label className="text-sm font-bold text-sky-900 px-1">Setup Token</label>
+                                    <div className="relative group">
+                                        <div className="absolute inset-y-0 left-0 pl-4 flex items-center pointer-events-none">
+                                            <KeyRound className="h-5 w-5 text-sky-400 group-focus-within:text-sky-600 transition-colors" />
+                                        </div>
+                                        <input
+                                            type="text"
+                                            value={setupToken}
+                                            onChange={(e) => setSetupToken(e.target.value)}
+                                            className="w-full pl-12 pr-4 py-3 bg-white/50 border border-white/40 focus:border-sky-400 focus:ring-4 focus:ring-sky-400/10 outline-none rounded-2xl transition-all font-medium text-sky-900"
+                                            placeholder="From the server log"
+                                        />
+                                    </div>
+                                </div>
+                            )}
                         </div>
 
                         {error && (
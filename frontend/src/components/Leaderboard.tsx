@@ -5,17 +5,17 @@ import { GlassCard } from './ui/GlassCard';
 import { Trophy, Clock, Medal, Crown } from 'lucide-react';
 import { motion } from 'framer-motion';
 import { api } from '@/lib/api';
-import { User } from '@/lib/types';
+import { LeaderboardEntry } from '@/lib/types';
 
 export const Leaderboard = () => {
-    const [leaders, setLeaders] = useState<User[]>([]);
+    const [leaders, setLeaders] = useState<LeaderboardEntry[]>([]);
     const [loading, setLoading] = useState(true);
 
     const fetchLeaderboard = async () => {
         try {
             const data = await api.users.leaderboard();
-            if (data && Array.isArray(data)) {
-                setLeaders(data);
+            if (data && Array.isArray(data.entries)) {
+                setLeaders(data.entries);
             }
         } catch (err) {
             console.error("Failed to fetch leaderboard:", err);
@@ -65,7 +65,7 @@ export const Leaderboard = () => {
                 ) : (
                     leaders.map((leader, index) => (
                         <motion.div
-                            key={leader.id}
+                            key={leader.user_id}
                             initial={{ opacity: 0, x: -10 }}
                             animate={{ opacity: 1, x: 0 }}
                             transition={{ delay: index * 0.1 }}
@@ -93,7 +93,7 @@ export const Leaderboard = () => {
                                     <h3 className="text-sm font-bold text-sky-900 leading-none mb-1 max-w-[120px] truncate">{leader.username}</h3>
                                     <div className="flex items-center gap-1 text-[10px] text-sky-800/50 font-bold uppercase tracking-tighter">
                                         <Clock className="w-2.5 h-2.5" />
-                                        {formatTime(leader.total_listen_time)}
+                                        {formatTime(leader.value)}
                                     </div>
                                 </div>
                             </div>
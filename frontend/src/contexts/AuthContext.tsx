@@ -8,7 +8,8 @@ interface AuthContextType {
     user: User | null;
     loading: boolean;
     login: (credentials: any) => Promise<void>;
-    register: (data: any) => Promise<void>;
+    /** Resolves to the new user, who isn't logged in while their status is pending */
+    register: (data: any) => Promise<User>;
     logout: () => Promise<void>;
     refreshUser: () => Promise<void>;
 }
@@ -41,7 +42,10 @@ export const AuthProvider = ({ children }: { children: React.ReactNode }) => {
 
     const register = async (data: any) => {
         const userData = await api.auth.register(data);
-        setUser(userData);
+        if (userData.status === 'active') {
+            setUser(userData);
+        }
+        return userData;
     };
 
     const logout = async () => {
@@ -2,7 +2,7 @@
 
 import React, { createContext, useContext, useEffect, useState, useRef } from 'react';
 import { api } from '@/lib/api';
-import { CurrentSong, StationEvent } from '@/lib/types';
+import { CurrentSong, PongMessage, ServerMessage } from '@/lib/types';
 import { useAuth } from './AuthContext';
 import { decompileRhythm } from '@/lib/rhythm';
 
@@ -15,8 +15,16 @@ interface SyncContextType {
     currentSong: CurrentSong | null;
     isConnected: boolean;
     rhythmEvents: RhythmEvent[];
+    // Station timeline (ms) = performance.now() + clockOffsetMs
+    clockOffsetMs: number | null;
+    rttMs: number | null;
 }
 
+const PROTOCOL_VERSION = 1;
+const PING_INTERVAL_MS = 2000;
+// Samples kept to pick the lowest-latency exchange from
+const CLOCK_SAMPLES = 8;
+
 const SyncContext = createContext<SyncContextType | undefined>(undefined);
 
 export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
@@ -24,8 +32,47 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
     const [currentSong, setCurrentSong] = useState<CurrentSong | null>(null);
     const [rhythmEvents, setRhythmEvents] = useState<RhythmEvent[]>([]);
     const [isConnected, setIsConnected] = useState(false);
+    const [clockOffsetMs, setClockOffsetMs] = useState<number | null>(null);
+    const [rttMs, setRttMs] = useState<number | null>(null);
     const wsRef = useRef<WebSocket | null>(null);
     const reconnectTimeoutRef = useRef<NodeJS.Timeout | null>(null);
+    const pingIntervalRef = useRef<NodeJS.Timeout | null>(null);
+    const clockSamplesRef = useRef<{ rtt: number; offset: number }[]>([]);
+    const lastRttRef = useRef<number | null>(null);
+
+    const sendPing = (socket: WebSocket) => {
+        if (socket.readyState !== WebSocket.OPEN) return;
+        socket.send(JSON.stringify({
+            v: PROTOCOL_VERSION,
+            type: 'Ping',
+            data: { client_sent_at: performance.now(), last_rtt_ms: lastRttRef.current },
+        }));
+    };
+
+    const handlePong = (pong: PongMessage) => {
+        const receivedAt = performance.now();
+        const serverReceived = pong.server_received_micros / 1000;
+        const serverSent = pong.server_sent_micros / 1000;
+
+        const rtt = Math.max(0, (receivedAt - pong.client_sent_at) - (serverSent - serverReceived));
+        const offset = ((serverReceived - pong.client_sent_at) + (serverSent - receivedAt)) / 2;
+        lastRttRef.current = rtt;
+
+        const samples = [...clockSamplesRef.current, { rtt, offset }].slice(-CLOCK_SAMPLES);
+        clockSamplesRef.current = samples;
+
+        // The exchange with the lowest round trip has the least asymmetric delay
+        const best = samples.reduce((a, b) => (b.rtt < a.rtt ? b : a));
+        setClockOffsetMs(best.offset);
+        setRttMs(rtt);
+    };
+
+    const stopPinging = () => {
+        if (pingIntervalRef.current) {
+            clearInterval(pingIntervalRef.current);
+            pingIntervalRef.current = null;
+        }
+    };
 
     const connect = () => {
         if (wsRef.current?.readyState === WebSocket.OPEN) return;
@@ -40,6 +87,10 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
         socket.onopen = () => {
             console.log('[Sync] WebSocket Connected');
             setIsConnected(true);
+            clockSamplesRef.current = [];
+            sendPing(socket);
+            stopPinging();
+            pingIntervalRef.current = setInterval(() => sendPing(socket), PING_INTERVAL_MS);
             if (reconnectTimeoutRef.current) {
                 clearTimeout(reconnectTimeoutRef.current);
                 reconnectTimeoutRef.current = null;
@@ -48,10 +99,16 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
 
         socket.onmessage = (event) => {
             try {
-                const stationEvent: StationEvent = JSON.parse(event.data);
-                if (stationEvent.type === 'SongChange') {
-                    console.log('[Sync] New song:', stationEvent.data);
-                    setCurrentSong(stationEvent.data);
+                const message: ServerMessage = JSON.parse(event.data);
+                if (message.type === 'Event' && message.data.type === 'SongChange') {
+                    console.log('[Sync] New song:', message.data.data);
+                    setCurrentSong(message.data.data);
+                } else if (message.type === 'Event' && message.data.type === 'SongUpdated') {
+                    setCurrentSong(message.data.data);
+                } else if (message.type === 'Pong') {
+                    handlePong(message.data);
+                } else if (message.type === 'Error') {
+                    console.warn('[Sync] Server error:', message.data.code, message.data.message);
                 }
             } catch (err) {
                 console.error('[Sync] Failed to parse message:', err);
@@ -61,6 +118,7 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
         socket.onclose = () => {
             console.log('[Sync] WebSocket Closed, reconnecting...');
             setIsConnected(false);
+            stopPinging();
             wsRef.current = null;
             if (user) {
                 reconnectTimeoutRef.current = setTimeout(connect, 3000);
@@ -98,6 +156,7 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
             if (reconnectTimeoutRef.current) {
                 clearTimeout(reconnectTimeoutRef.current);
             }
+            stopPinging();
         };
     }, [user, authLoading]);
 
@@ -106,8 +165,13 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
             console.log('[Sync] Rhythm data detected, decompiling...');
             decompileRhythm(currentSong.rhythm_data).then(data => {
                 console.log('[Sync] Decompiled rhythm JSON:', data);
-                if (Array.isArray(data)) {
-                    const sorted = [...data].sort((a, b) => a.time - b.time);
+                // Versioned tracks wrap the events, older data is a bare list
+                const events = Array.isArray(data) ? data : data?.events;
+                if (Array.isArray(events)) {
+                    const offset = Array.isArray(data) ? 0 : (data.offset_ms ?? 0);
+                    const sorted = events
+                        .map((e: RhythmEvent) => ({ ...e, time: e.time + offset }))
+                        .sort((a: RhythmEvent, b: RhythmEvent) => a.time - b.time);
                     setRhythmEvents(sorted as RhythmEvent[]);
                 } else {
                     setRhythmEvents([]);
@@ -122,7 +186,7 @@ export const SyncProvider = ({ children }: { children: React.ReactNode }) => {
     }, [currentSong]);
 
     return (
-        <SyncContext.Provider value={{ currentSong, isConnected, rhythmEvents }}>
+        <SyncContext.Provider value={{ currentSong, isConnected, rhythmEvents, clockOffsetMs, rttMs }}>
             {children}
         </SyncContext.Provider>
     );
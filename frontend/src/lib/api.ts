@@ -1,8 +1,12 @@
 
-import { Song, PlaybackStats, ServerStatus, VibeTag, User, ActiveListener, CurrentSong } from './types';
+import { Song, PlaybackStats, ServerStatus, VibeTag, User, ActiveListener, CurrentSong, Leaderboard, LeaderboardMetric, LeaderboardPeriod, AuthSession, RegistrationInfo, GuestSession, Playlist, PlaylistDetail, PlaylistVisibility } from './types';
 
 export const API_BASE_URL = process.env.NEXT_PUBLIC_API_URL || '/api';
 
+// Names this tab's stream connection, so a user listening on several devices is tracked per device.
+// randomUUID is missing outside secure contexts, the server then heartbeats the newest connection.
+const connectionId = typeof crypto !== 'undefined' && 'randomUUID' in crypto ? crypto.randomUUID() : null;
+
 /**
  * Fetch wrapper for the Wavy Radio API.
  * Ensures credentials (session cookies) are always included.
@@ -44,13 +48,23 @@ export const api = {
     auth: {
         me: () => wavyFetch<User>('/auth/me'),
         login: (body: any) => wavyFetch<User>('/auth/login', { method: 'POST', body: JSON.stringify(body) }),
+        registration: () => wavyFetch<RegistrationInfo>('/auth/registration'),
         register: (body: any) => wavyFetch<User>('/auth/register', { method: 'POST', body: JSON.stringify(body) }),
+        guest: () => wavyFetch<GuestSession>('/auth/guest', { method: 'POST' }),
         logout: () => wavyFetch('/auth/logout', { method: 'POST' }),
+        sessions: () => wavyFetch<AuthSession[]>('/auth/sessions'),
+        revokeSession: (id: number) => wavyFetch(`/auth/sessions/${id}`, { method: 'DELETE' }),
+        logoutEverywhere: () => wavyFetch('/auth/sessions', { method: 'DELETE' }),
+        changePassword: (currentPassword: string, newPassword: string) =>
+            wavyFetch('/auth/password', { method: 'POST', body: JSON.stringify({ current_password: currentPassword, new_password: newPassword }) }),
+        resetPassword: (code: string, newPassword: string) =>
+            wavyFetch('/auth/password/reset', { method: 'POST', body: JSON.stringify({ code, new_password: newPassword }) }),
     },
 
     // Users
     users: {
-        leaderboard: () => wavyFetch<User[]>('/users/leaderboard'),
+        leaderboard: (metric: LeaderboardMetric = 'listen_time', period: LeaderboardPeriod = 'all') =>
+            wavyFetch<Leaderboard>(`/users/leaderboard?metric=${metric}&period=${period}`),
     },
 
     // Songs
@@ -63,6 +77,7 @@ export const api = {
             body: formData,
         }),
         getImageUrl: (id: number) => `${API_BASE_URL}/songs/${id}/image`,
+        getAudioUrl: (id: number) => `${API_BASE_URL}/songs/${id}/audio`,
         update: (id: number, data: any) => wavyFetch(`/songs/${id}`, { method: 'POST', body: JSON.stringify(data) }),
         delete: (id: number) => wavyFetch(`/songs/${id}`, { method: 'DELETE' }),
         getTags: (id: number) => wavyFetch<{ tag_id: number, score: number, name: string }[]>(`/songs/${id}/tags`),
@@ -70,6 +85,24 @@ export const api = {
         removeTag: (id: number, tagId: number) => wavyFetch(`/songs/${id}/tags/${tagId}`, { method: 'DELETE' }),
     },
 
+    // Playlists
+    playlists: {
+        list: () => wavyFetch<Playlist[]>('/playlists'),
+        get: (id: number) => wavyFetch<PlaylistDetail>(`/playlists/${id}`),
+        create: (name: string, visibility: PlaylistVisibility = 'private', description?: string) =>
+            wavyFetch<Playlist>('/playlists', { method: 'POST', body: JSON.stringify({ name, visibility, description }) }),
+        update: (id: number, data: { name?: string, description?: string, visibility?: PlaylistVisibility, version?: number }) =>
+            wavyFetch<PlaylistDetail>(`/playlists/${id}`, { method: 'POST', body: JSON.stringify(data) }),
+        delete: (id: number) => wavyFetch(`/playlists/${id}`, { method: 'DELETE' }),
+        addSong: (id: number, songId: number, version?: number, position?: number) =>
+            wavyFetch<PlaylistDetail>(`/playlists/${id}/songs`, { method: 'POST', body: JSON.stringify({ song_id: songId, position, version }) }),
+        moveSong: (id: number, entryId: number, position: number, version?: number) =>
+            wavyFetch<PlaylistDetail>(`/playlists/${id}/songs/${entryId}`, { method: 'POST', body: JSON.stringify({ position, version }) }),
+        removeSong: (id: number, entryId: number, version?: number) =>
+            wavyFetch<PlaylistDetail>(`/playlists/${id}/songs/${entryId}${version !== undefined ? `?version=${version}` : ''}`, { method: 'DELETE' }),
+        exportUrl: (id: number, format: 'm3u' | 'xspf') => `${API_BASE_URL}/playlists/${id}/export?format=${format}`,
+    },
+
     // Albums
     albums: {
         list: () => wavyFetch<{ id: number, title: string }[]>('/albums'),
@@ -101,10 +134,11 @@ export const api = {
         currentSong: () => wavyFetch<CurrentSong | null>('/song/current'),
         heartbeat: (client_position_ms: number) =>
             wavyFetch<{
+                connection_id: string,
                 desync_ms: number,
                 server_position_ms: number,
                 client_base_pos_ms: number
-            }>('/heartbeat', { method: 'POST', body: JSON.stringify({ client_position_ms }) }),
+            }>('/heartbeat', { method: 'POST', body: JSON.stringify({ connection_id: connectionId, client_position_ms }) }),
     },
 
     // Listeners
@@ -113,13 +147,13 @@ export const api = {
     },
 
     // Audio Stream URL
-    streamUrl: `${API_BASE_URL}/stream`,
+    streamUrl: connectionId ? `${API_BASE_URL}/stream?connection_id=${connectionId}` : `${API_BASE_URL}/stream`,
     getWsUrl: () => {
         const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
         const host = window.location.host;
         const wsBase = API_BASE_URL.startsWith('http')
             ? API_BASE_URL.replace(/^http/, 'ws')
             : `${protocol}//${host}${API_BASE_URL}`;
-        return `${wsBase}/ws`;
+        return connectionId ? `${wsBase}/ws?connection_id=${connectionId}` : `${wsBase}/ws`;
     }
 };
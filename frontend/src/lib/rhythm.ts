@@ -1,18 +1,70 @@
 import { decode } from '@msgpack/msgpack';
 
+// Compiled rhythm container: "WRHY" | version (u8) | payload length (u32 LE) | CRC32 (u32 LE) | zlib payload
+const RHYTHM_MAGIC = [0x57, 0x52, 0x48, 0x59];
+const RHYTHM_HEADER_LEN = 13;
+
+const CRC_TABLE = (() => {
+    const table = new Uint32Array(256);
+    for (let n = 0; n < 256; n++) {
+        let c = n;
+        for (let k = 0; k < 8; k++) {
+            c = c & 1 ? 0xedb88320 ^ (c >>> 1) : c >>> 1;
+        }
+        table[n] = c >>> 0;
+    }
+    return table;
+})();
+
+function crc32(bytes: Uint8Array): number {
+    let crc = 0xffffffff;
+    for (let i = 0; i < bytes.length; i++) {
+        crc = CRC_TABLE[(crc ^ bytes[i]) & 0xff] ^ (crc >>> 8);
+    }
+    return (crc ^ 0xffffffff) >>> 0;
+}
+
+/**
+ * Strips and checks the container header, returning the zlib payload.
+ * Data compiled before the header existed is returned as is.
+ */
+export function unwrapRhythmContainer(bytes: Uint8Array): Uint8Array {
+    const hasMagic = RHYTHM_MAGIC.every((b, i) => bytes[i] === b);
+    if (!hasMagic) return bytes;
+
+    if (bytes.length < RHYTHM_HEADER_LEN) {
+        throw new Error('Rhythm data is truncated (incomplete header)');
+    }
+
+    const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    const length = view.getUint32(5, true);
+    const checksum = view.getUint32(9, true);
+    const payload = bytes.subarray(RHYTHM_HEADER_LEN);
+
+    if (payload.length !== length) {
+        throw new Error(`Rhythm data is truncated (expected ${length} bytes, got ${payload.length})`);
+    }
+    if (crc32(payload) !== checksum) {
+        throw new Error('Rhythm data failed its checksum');
+    }
+
+    return payload;
+}
+
 /**
  * Decompiles the rhythm data from a base64 string.
- * This handles base64 decoding, zlib decompression (via DecompressionStream), 
- * and MessagePack decoding.
+ * This handles base64 decoding, the container checksum, zlib decompression
+ * (via DecompressionStream) and MessagePack decoding.
  */
 export async function decompileRhythm(base64Data: string): Promise<any> {
     try {
         // 1. Base64 to ArrayBuffer
         const binaryString = atob(base64Data);
-        const bytes = new Uint8Array(binaryString.length);
+        const raw = new Uint8Array(binaryString.length);
         for (let i = 0; i < binaryString.length; i++) {
-            bytes[i] = binaryString.charCodeAt(i);
+            raw[i] = binaryString.charCodeAt(i);
         }
+        const bytes = unwrapRhythmContainer(raw);
 
         // 2. Wrap in a Response and use DecompressionStream (deflate for Zlib)
         const stream = new ReadableStream({
@@ -7,6 +7,8 @@ export interface Song {
     album_id?: number;
     has_image: boolean;
     match_error?: number; // For vibe search
+    likes?: number;
+    dislikes?: number;
 }
 
 export interface CurrentSong {
@@ -23,8 +25,77 @@ export interface CurrentSong {
 export type StationEvent = {
     type: 'SongChange';
     data: CurrentSong;
+} | {
+    type: 'RhythmEvents';
+    data: unknown[];
+} | {
+    type: 'SongUpdated';
+    data: CurrentSong;
+} | {
+    type: 'TagsChanged';
+    data: { song_id: number; tags: { tag_id: number; name: string; score: number }[] };
+} | {
+    type: 'Progress';
+    data: { song_id: number; position_ms: number; duration_ms: number; station_position_ms: number };
+} | {
+    type: 'Reaction';
+    data: SongReaction;
+} | {
+    type: 'ChatMessage';
+    data: ChatMessage;
+} | {
+    type: 'ChatMessageDeleted';
+    data: number;
+} | {
+    type: 'ListenerJoined' | 'ListenerLeft';
+    data: { user_id: number; username: string };
+} | {
+    type: 'ListenerCount';
+    data: number;
+} | {
+    type: 'QueueChanged';
+    data: unknown[];
 };
 
+// Envelope of every WebSocket message from the server
+export type ServerMessage = { v: number; reply_to?: string } & (
+    | { type: 'Welcome'; data: { protocol_version: number; user_id: number; username: string } }
+    | { type: 'Event'; data: StationEvent }
+    | { type: 'Ack' }
+    | { type: 'Pong'; data: PongMessage }
+    | { type: 'HeartbeatAck'; data: { desync_ms: number; server_position_ms: number; client_base_pos_ms: number } }
+    | { type: 'CurrentSong'; data: CurrentSong | null }
+    | { type: 'Listeners'; data: ActiveListener[] }
+    | { type: 'SongQueued'; data: { position: number; request: unknown } }
+    | { type: 'Error'; data: { code: string; message: string } }
+);
+
+export interface ChatMessage {
+    id: number;
+    user_id: number;
+    username: string;
+    song_id: number | null;
+    song_title: string | null;
+    body: string;
+    created_at: string;
+}
+
+export interface SongReaction {
+    id: number;
+    user_id: number;
+    username: string;
+    song_id: number;
+    emoji: string;
+    position_ms: number;
+    created_at: string;
+}
+
+export interface PongMessage {
+    client_sent_at: number;
+    server_received_micros: number;
+    server_sent_micros: number;
+}
+
 export interface PlaybackStats {
     session_seconds: number;
     total_seconds: number;
@@ -41,16 +112,95 @@ export interface VibeTag {
     target_score: number;
 }
 
+export type UserRole = 'user' | 'curator' | 'dj' | 'admin';
+
+export type UserStatus = 'active' | 'pending';
+
+export type RegistrationMode = 'open' | 'invite' | 'approval' | 'closed';
+
+export interface RegistrationInfo {
+    mode: RegistrationMode;
+    needs_setup: boolean;
+    guests_enabled: boolean;
+}
+
+export interface GuestSession {
+    id: number;
+    name: string;
+    expires_at: string;
+}
+
 export interface User {
     id: number;
     username: string;
-    role: string;
+    role: UserRole;
     total_listen_time: number;
     artist_id?: number | null;
+    leaderboard_opt_out: boolean;
+    status: UserStatus;
+}
+
+export interface AuthSession {
+    id: number;
+    created_at: string;
+    expires_at: string;
+    last_seen_at: string;
+    user_agent: string | null;
+    ip: string | null;
+    current: boolean;
+}
+
+export type LeaderboardMetric = 'listen_time' | 'songs_requested' | 'streak';
+export type LeaderboardPeriod = 'day' | 'week' | 'month' | 'year' | 'all';
+
+export interface LeaderboardEntry {
+    rank: number;
+    user_id: number;
+    username: string;
+    value: number; // Seconds, requests or days depending on the metric
+}
+
+export interface Leaderboard {
+    metric: LeaderboardMetric;
+    period: LeaderboardPeriod;
+    entries: LeaderboardEntry[];
 }
 
 export interface ActiveListener {
     username: string;
     connected_at: string;
     listen_time_ms: number;
+    connections: number;
+}
+
+export type PlaylistVisibility = 'private' | 'public' | 'collaborative';
+
+export interface Playlist {
+    id: number;
+    owner_id: number;
+    owner_name: string;
+    name: string;
+    description: string | null;
+    visibility: PlaylistVisibility;
+    version: number; // Sent back with edits, a stale one fails with 409
+    song_count: number;
+    duration_ms: number;
+    created_at: string;
+    updated_at: string;
+}
+
+export interface PlaylistEntry {
+    id: number;
+    position: number;
+    song_id: number;
+    title: string;
+    artist_names: string | null;
+    album_title: string | null;
+    duration_ms: number | null;
+    added_by: number | null;
+    added_at: string;
+}
+
+export interface PlaylistDetail extends Playlist {
+    songs: PlaylistEntry[];
 }
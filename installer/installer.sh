@@ -113,6 +113,18 @@ else
     echo "Existing COOKIE_KEY found in .env, skipping generation."
 fi
 
+# The backend sits behind Nginx, which sets X-Real-IP
+if ! grep -q "TRUST_PROXY_HEADERS=" "$ENV_FILE"; then
+    echo "Adding TRUST_PROXY_HEADERS to .env..."
+    echo "TRUST_PROXY_HEADERS=true" >> "$ENV_FILE"
+fi
+
+# open, invite, approval or closed
+if ! grep -q "REGISTRATION_MODE=" "$ENV_FILE"; then
+    echo "Adding REGISTRATION_MODE to .env..."
+    echo "REGISTRATION_MODE=invite" >> "$ENV_FILE"
+fi
+
 # Backend build
 echo "Building Rust Backend..."
 cd "$INSTALL_DIR/backend" || exit
@@ -152,6 +164,9 @@ echo "------------------------------------------------------"
 echo "Current status: Services are running locally."
 echo "Note: The application is NOT yet accessible to the public."
 echo "You will need an Nginx reverse proxy to expose Wavy to the web."
+echo
+echo "To create the first admin account, register with the setup token"
+echo "printed in the backend log: journalctl -u $BACKEND_SVC | grep 'setup token'"
 echo "------------------------------------------------------"
 echo
 echo